@@ -0,0 +1,552 @@
+//! # Serde ROSMSG Derive
+//!
+//! Derive macro implementing `serde_rosmsg::RosMessage` for structures,
+//! after checking them against their ROS message definition.
+//!
+//! Since ROSMSG data is not self-describing, the order and types of fields
+//! are the only thing tying a structure to the wire format. The macro
+//! parses the message definition at compile time, and reports every field
+//! whose name, position or type does not match it.
+//!
+//! The definition is read from the file given by the `file` attribute,
+//! relative to the crate's `Cargo.toml`. Messages the definition depends on
+//! are searched for next to it, within the ROS package layout, and within
+//! the standard messages bundled with `serde_rosmsg`. Without the `file`
+//! attribute, the message itself is taken from the bundled ones.
+//!
+//! Field types are matched by name. Builtin types need to be written as
+//! primitives, `String`, `Vec<T>` and `[T; N]`, while `time`, `duration`
+//! and nested messages need to be types with the same name, like `Time`
+//! or `Point`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! extern crate serde_rosmsg;
+//! #[macro_use]
+//! extern crate serde_derive;
+//! #[macro_use]
+//! extern crate serde_rosmsg_derive;
+//! use serde_rosmsg::RosMessage;
+//!
+//! #[derive(Debug,Serialize,Deserialize,PartialEq,RosMsg)]
+//! #[rosmsg(type = "geometry_msgs/Point")]
+//! struct Point {
+//!     x: f64,
+//!     y: f64,
+//!     z: f64,
+//! }
+//!
+//! #[derive(Debug,Serialize,Deserialize,PartialEq,RosMsg)]
+//! #[rosmsg(type = "geometry_msgs/Polygon")]
+//! struct Polygon {
+//!     points: Vec<Point32>,
+//! }
+//!
+//! #[derive(Debug,Serialize,Deserialize,PartialEq,RosMsg)]
+//! #[rosmsg(type = "geometry_msgs/Point32")]
+//! struct Point32 {
+//!     x: f32,
+//!     y: f32,
+//!     z: f32,
+//! }
+//!
+//! fn main() {
+//!     assert_eq!(Point::MD5SUM, "4a842b65f413084dc2b10fb484ea7f17");
+//!     assert!(Polygon::DEFINITION.contains("MSG: geometry_msgs/Point32"));
+//! }
+//! ```
+//!
+//! Structures that don't match fail to compile:
+//!
+//! ```rust,compile_fail
+//! extern crate serde_rosmsg;
+//! #[macro_use]
+//! extern crate serde_derive;
+//! #[macro_use]
+//! extern crate serde_rosmsg_derive;
+//!
+//! #[derive(Serialize,Deserialize,RosMsg)]
+//! #[rosmsg(type = "geometry_msgs/Point")]
+//! struct Point {
+//!     x: f64,
+//!     z: f64,
+//!     y: f64,
+//! }
+//!
+//! fn main() {}
+//! ```
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate serde_rosmsg;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use serde_rosmsg::catalog;
+use serde_rosmsg::schema::{BaseType, FieldType, Msg, Schema};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Error, Expr, Fields, GenericArgument, Lit, LitStr, PathArguments,
+          Result, Type};
+
+/// Implement `RosMessage` for a structure matching a message definition.
+#[proc_macro_derive(RosMsg, attributes(rosmsg))]
+pub fn derive_ros_msg(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => compile_errors(error).into(),
+    }
+}
+
+/// Report errors through `compile_error!`, which unlike `::core::compile_error!`
+/// resolves within crates of any edition.
+fn compile_errors(error: Error) -> TokenStream2 {
+    error.into_iter()
+        .map(|error| {
+            let message = error.to_string();
+            quote_spanned!(error.span()=> compile_error!(#message);)
+        })
+        .collect()
+}
+
+struct Attributes {
+    ros_type: LitStr,
+    file: Option<LitStr>,
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2> {
+    let attributes = parse_attributes(input)?;
+    let ros_type = attributes.ros_type.value();
+    let (schema, file) = match attributes.file {
+        Some(ref file) => {
+            let path = manifest_dir().join(file.value());
+            (load_schema(&ros_type, &path).map_err(|message| Error::new(file.span(), message))?,
+             Some(path))
+        }
+        None => {
+            (load_bundled_schema(&ros_type)
+                 .map_err(|message| Error::new(attributes.ros_type.span(), message))?,
+             None)
+        }
+    };
+    check_fields(input, schema.root())?;
+
+    let md5sum = schema.md5sum().map_err(|e| Error::new(Span::call_site(), e.to_string()))?;
+    let definition = schema.message_definition()
+        .map_err(|e| Error::new(Span::call_site(), e.to_string()))?;
+    // Referencing the file makes the compiler rebuild the crate when it changes
+    let dependency = file.map(|path| {
+        let path = path.to_string_lossy().into_owned();
+        quote! { const _: &'static [u8] = include_bytes!(#path); }
+    });
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        const _: () = {
+            #dependency
+
+            impl #impl_generics ::serde_rosmsg::RosMessage for #name #type_generics
+                #where_clause
+            {
+                const ROS_TYPE: &'static str = #ros_type;
+                const MD5SUM: &'static str = #md5sum;
+                const DEFINITION: &'static str = #definition;
+            }
+        };
+    })
+}
+
+fn parse_attributes(input: &DeriveInput) -> Result<Attributes> {
+    let mut ros_type = None;
+    let mut file = None;
+    for attribute in &input.attrs {
+        if !attribute.path().is_ident("rosmsg") {
+            continue;
+        }
+        attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("type") {
+                    ros_type = Some(meta.value()?.parse::<LitStr>()?);
+                    Ok(())
+                } else if meta.path.is_ident("file") {
+                    file = Some(meta.value()?.parse::<LitStr>()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `type` or `file`"))
+                }
+            })?;
+    }
+    match ros_type {
+        Some(ros_type) => Ok(Attributes { ros_type, file }),
+        None => {
+            Err(Error::new(input.ident.span(),
+                           "missing the message type, like \
+                            #[rosmsg(type = \"geometry_msgs/Pose\")]"))
+        }
+    }
+}
+
+fn manifest_dir() -> PathBuf {
+    env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from).unwrap_or_default()
+}
+
+fn load_schema(ros_type: &str, path: &Path) -> ::std::result::Result<Schema, String> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    // Within ROS packages, messages are stored at `package/msg/Name.msg`
+    let packages = if directory.file_name().is_some_and(|name| name == "msg") {
+        directory.parent().and_then(Path::parent)
+    } else {
+        directory.parent()
+    };
+    let package = ros_type.split('/').next().unwrap_or("");
+    Schema::build(ros_type, |name| {
+            if name == ros_type {
+                return Some(source.clone());
+            }
+            let mut parts = name.splitn(2, '/');
+            let (dependency_package, dependency) = (parts.next()?, parts.next()?);
+            let file_name = format!("{}.msg", dependency);
+            let mut candidates = Vec::new();
+            if dependency_package == package {
+                candidates.push(directory.join(&file_name));
+            }
+            if let Some(packages) = packages {
+                candidates.push(packages.join(dependency_package).join("msg").join(&file_name));
+                candidates.push(packages.join(dependency_package).join(&file_name));
+            }
+            candidates.iter()
+                .filter_map(|path| fs::read_to_string(path).ok())
+                .next()
+                .or_else(|| catalog::definition(name).map(String::from))
+        })
+        .map_err(|e| e.to_string())
+}
+
+fn load_bundled_schema(ros_type: &str) -> ::std::result::Result<Schema, String> {
+    if catalog::definition(ros_type).is_none() {
+        return Err(format!("{} is not a bundled message, its definition needs to be \
+                            provided with the `file` attribute",
+                           ros_type));
+    }
+    Schema::build(ros_type, |name| catalog::definition(name).map(String::from))
+        .map_err(|e| e.to_string())
+}
+
+fn check_fields(input: &DeriveInput, msg: &Msg) -> Result<()> {
+    let fields = match input.data {
+        Data::Struct(ref data) => {
+            match data.fields {
+                Fields::Named(ref fields) => fields,
+                Fields::Unit if msg.fields.is_empty() => return Ok(()),
+                _ => return Err(Error::new(input.ident.span(), "expected named fields")),
+            }
+        }
+        _ => return Err(Error::new(input.ident.span(), "only structures can be messages")),
+    };
+    let ros_type = msg.full_name();
+    let mut errors = Vec::new();
+    for (index, field) in fields.named.iter().enumerate() {
+        let name = field_name(field)?;
+        let expected = match msg.fields.get(index) {
+            Some(expected) => expected,
+            None => {
+                errors.push(Error::new(field.span(),
+                                       format!("{} has no field `{}` at this position",
+                                               ros_type,
+                                               name)));
+                continue;
+            }
+        };
+        if expected.name != name {
+            errors.push(Error::new(field.span(),
+                                   format!("expected field `{} {}` of {}, found `{}`",
+                                           expected.field_type,
+                                           expected.name,
+                                           ros_type,
+                                           name)));
+        } else if !type_matches(&field.ty, &expected.field_type) {
+            errors.push(Error::new(field.ty.span(),
+                                   format!("field `{}` of {} has type `{}`",
+                                           name,
+                                           ros_type,
+                                           expected.field_type)));
+        }
+    }
+    for missing in msg.fields.iter().skip(fields.named.len()) {
+        errors.push(Error::new(fields.brace_token.span.close(),
+                               format!("missing field `{} {}` of {}",
+                                       missing.field_type,
+                                       missing.name,
+                                       ros_type)));
+    }
+    let mut errors = errors.into_iter();
+    match errors.next() {
+        Some(mut error) => {
+            error.extend(errors);
+            Err(error)
+        }
+        None => Ok(()),
+    }
+}
+
+fn field_name(field: &syn::Field) -> Result<String> {
+    for attribute in &field.attrs {
+        if !attribute.path().is_ident("serde") {
+            continue;
+        }
+        let mut rename = None;
+        attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.input.peek(syn::Token![=]) {
+                    meta.value()?.parse::<Expr>()?;
+                } else if meta.input.peek(syn::token::Paren) {
+                    meta.parse_nested_meta(|meta| {
+                            meta.value()?.parse::<Expr>()?;
+                            Ok(())
+                        })?;
+                }
+                Ok(())
+            })?;
+        if let Some(rename) = rename {
+            return Ok(rename);
+        }
+    }
+    let name = field.ident.as_ref().map(|ident| ident.to_string()).unwrap_or_default();
+    Ok(name.trim_start_matches("r#").into())
+}
+
+fn type_matches(ty: &Type, field_type: &FieldType) -> bool {
+    match *field_type {
+        FieldType::Single(ref base) => base_matches(ty, base),
+        FieldType::Array(ref base) => {
+            match generic_argument(ty, "Vec") {
+                Some(item) => base_matches(item, base),
+                None => false,
+            }
+        }
+        FieldType::FixedArray(ref base, size) => {
+            match *unwrap_type(ty) {
+                Type::Array(ref array) => {
+                    array_length(&array.len) == Some(size) && base_matches(&array.elem, base)
+                }
+                _ => false,
+            }
+        }
+    }
+}
+
+fn base_matches(ty: &Type, base: &BaseType) -> bool {
+    let expected = match *base {
+        BaseType::Bool => "bool",
+        BaseType::Int8 | BaseType::Byte => "i8",
+        BaseType::UInt8 | BaseType::Char => "u8",
+        BaseType::Int16 => "i16",
+        BaseType::UInt16 => "u16",
+        BaseType::Int32 => "i32",
+        BaseType::UInt32 => "u32",
+        BaseType::Int64 => "i64",
+        BaseType::UInt64 => "u64",
+        BaseType::Float32 => "f32",
+        BaseType::Float64 => "f64",
+        BaseType::String => "String",
+        BaseType::Time => "Time",
+        BaseType::Duration => "Duration",
+        BaseType::Message(ref name) => name.rsplit('/').next().unwrap_or(""),
+    };
+    type_name(ty).is_some_and(|name| name == expected)
+}
+
+fn unwrap_type(ty: &Type) -> &Type {
+    match *ty {
+        Type::Group(ref group) => unwrap_type(&group.elem),
+        Type::Paren(ref paren) => unwrap_type(&paren.elem),
+        ref ty => ty,
+    }
+}
+
+fn type_name(ty: &Type) -> Option<String> {
+    match *unwrap_type(ty) {
+        Type::Path(ref path) if path.qself.is_none() => {
+            path.path.segments.last().map(|segment| segment.ident.to_string())
+        }
+        _ => None,
+    }
+}
+
+fn generic_argument<'a>(ty: &'a Type, name: &str) -> Option<&'a Type> {
+    let segment = match *unwrap_type(ty) {
+        Type::Path(ref path) if path.qself.is_none() => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != name {
+        return None;
+    }
+    match segment.arguments {
+        PathArguments::AngleBracketed(ref arguments) if arguments.args.len() == 1 => {
+            match arguments.args[0] {
+                GenericArgument::Type(ref ty) => Some(ty),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn array_length(length: &Expr) -> Option<usize> {
+    match *length {
+        Expr::Lit(ref literal) => {
+            match literal.lit {
+                Lit::Int(ref value) => value.base10_parse().ok(),
+                _ => None,
+            }
+        }
+        Expr::Group(ref group) => array_length(&group.expr),
+        Expr::Paren(ref paren) => array_length(&paren.expr),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_str(input: &str) -> Result<String> {
+        expand(&syn::parse_str(input).unwrap()).map(|tokens| tokens.to_string())
+    }
+
+    fn errors(input: &str) -> Vec<String> {
+        expand_str(input).unwrap_err().into_iter().map(|error| error.to_string()).collect()
+    }
+
+    #[test]
+    fn implements_bundled_messages() {
+        let code = expand_str("#[rosmsg(type = \"geometry_msgs/Pose\")]
+                               struct Pose {
+                                   position: geometry::Point,
+                                   orientation: Quaternion,
+                               }")
+            .unwrap();
+        assert!(code.contains("impl :: serde_rosmsg :: RosMessage for Pose"));
+        assert!(code.contains("\"e45d45a5a1ce597b249e23fb30fc871f\""));
+        assert!(code.contains("\"geometry_msgs/Pose\""));
+        assert!(!code.contains("include_bytes"));
+    }
+
+    #[test]
+    fn implements_messages_from_files() {
+        let code = expand_str("#[rosmsg(type = \"geometry_msgs/PoseWithCovariance\",
+                                        file = \"../src/catalog/geometry_msgs/\
+                                                 PoseWithCovariance.msg\")]
+                               struct PoseWithCovariance {
+                                   pose: Pose,
+                                   #[serde(with = \"serde_rosmsg::types::big_array\")]
+                                   covariance: [f64; 36],
+                               }")
+            .unwrap();
+        assert!(code.contains("impl :: serde_rosmsg :: RosMessage for PoseWithCovariance"));
+        assert!(code.contains("\"c23e848cf1b7533a8d7c259073a97e6f\""));
+        assert!(code.contains("include_bytes"));
+    }
+
+    #[test]
+    fn checks_builtin_types() {
+        let code = expand_str("#[rosmsg(type = \"sensor_msgs/CameraInfo\")]
+                               struct CameraInfo {
+                                   header: std_msgs::Header,
+                                   height: u32,
+                                   width: u32,
+                                   distortion_model: ::std::string::String,
+                                   D: Vec<f64>,
+                                   K: [f64; 9],
+                                   R: [f64; 9],
+                                   P: [f64; 12],
+                                   binning_x: u32,
+                                   binning_y: u32,
+                                   roi: RegionOfInterest,
+                               }");
+        assert!(code.is_ok());
+        let code = expand_str("#[rosmsg(type = \"visualization_msgs/Marker\")]
+                               struct Marker {
+                                   header: Header,
+                                   ns: String,
+                                   id: i32,
+                                   #[serde(rename = \"type\", default)]
+                                   kind: i32,
+                                   action: i32,
+                                   pose: Pose,
+                                   scale: Vector3,
+                                   color: ColorRGBA,
+                                   lifetime: Duration,
+                                   frame_locked: bool,
+                                   points: Vec<Point>,
+                                   colors: Vec<ColorRGBA>,
+                                   text: String,
+                                   mesh_resource: String,
+                                   mesh_use_embedded_materials: bool,
+                               }");
+        assert!(code.is_ok());
+    }
+
+    #[test]
+    fn reports_mismatched_fields() {
+        assert_eq!(errors("#[rosmsg(type = \"geometry_msgs/Point\")]
+                           struct Point {
+                               x: f64,
+                               z: f64,
+                               y: f32,
+                           }"),
+                   vec!["expected field `float64 y` of geometry_msgs/Point, found `z`",
+                        "expected field `float64 z` of geometry_msgs/Point, found `y`"]);
+        assert_eq!(errors("#[rosmsg(type = \"geometry_msgs/Point\")]
+                           struct Point {
+                               x: f64,
+                               y: f32,
+                           }"),
+                   vec!["field `y` of geometry_msgs/Point has type `float64`",
+                        "missing field `float64 z` of geometry_msgs/Point"]);
+        assert_eq!(errors("#[rosmsg(type = \"geometry_msgs/PoseWithCovariance\")]
+                           struct PoseWithCovariance {
+                               pose: Pose,
+                               covariance: [f64; 35],
+                               extra: u8,
+                           }"),
+                   vec!["field `covariance` of geometry_msgs/PoseWithCovariance has type \
+                         `float64[36]`",
+                        "geometry_msgs/PoseWithCovariance has no field `extra` at this \
+                         position"]);
+        assert_eq!(errors("#[rosmsg(type = \"geometry_msgs/PoseArray\")]
+                           struct PoseArray {
+                               header: Header,
+                               poses: Vec<Point>,
+                           }"),
+                   vec!["field `poses` of geometry_msgs/PoseArray has type \
+                         `geometry_msgs/Pose[]`"]);
+    }
+
+    #[test]
+    fn reports_bad_attributes() {
+        assert_eq!(errors("struct Point { x: f64 }"),
+                   vec!["missing the message type, like \
+                         #[rosmsg(type = \"geometry_msgs/Pose\")]"]);
+        assert_eq!(errors("#[rosmsg(kind = \"geometry_msgs/Point\")] struct Point { x: f64 }"),
+                   vec!["expected `type` or `file`"]);
+        assert_eq!(errors("#[rosmsg(type = \"my_msgs/Point\")] struct Point { x: f64 }"),
+                   vec!["my_msgs/Point is not a bundled message, its definition needs to be \
+                         provided with the `file` attribute"]);
+        assert_eq!(errors("#[rosmsg(type = \"my_msgs/Point\", file = \"missing.msg\")]
+                           struct Point { x: f64 }")
+                       .len(),
+                   1);
+        assert_eq!(errors("#[rosmsg(type = \"geometry_msgs/Point\")] struct Point(f64);"),
+                   vec!["expected named fields"]);
+    }
+}
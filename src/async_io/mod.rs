@@ -0,0 +1,471 @@
+//! Read and write messages over asynchronous streams.
+//!
+//! Requires the `async` feature.
+//!
+//! These are the asynchronous counterparts of
+//! [`from_reader`](../de/fn.from_reader.html) and
+//! [`to_writer`](../ser/fn.to_writer.html), with one version for the
+//! [`futures::io`](futures/index.html) traits and one for the
+//! [`tokio::io`](tokio/index.html) traits. For a stream or sink of
+//! messages, consider the [`codec`](../codec/index.html) module instead.
+//!
+//! # Cancellation safety
+//!
+//! Reading a message keeps all of its progress within the
+//! [`ReadBuffer`](struct.ReadBuffer.html) passed to it. If the future gets
+//! dropped before completing, like when it loses a `select!`, the bytes
+//! read so far stay in the buffer. Reading again with the same buffer and
+//! reader continues where the dropped future stopped, so the stream never
+//! gets out of sync. Using another buffer, or the same buffer with another
+//! reader, does lose that progress.
+//!
+//! Writing a message is not cancellation safe. Dropping the future midway
+//! can leave part of a message written, so the connection should be
+//! dropped as well.
+//!
+//! # Examples
+//!
+//! ```rust
+//! extern crate futures_io;
+//! extern crate serde_rosmsg;
+//!
+//! use serde_rosmsg::async_io::ReadBuffer;
+//! use serde_rosmsg::async_io::futures::{from_async_reader, to_async_writer};
+//! use std::future::Future;
+//! use std::pin::Pin;
+//! use std::task::{Context, Poll, Waker};
+//!
+//! fn block_on<F: Future + Unpin>(mut future: F) -> F::Output {
+//!     let mut context = Context::from_waker(Waker::noop());
+//!     loop {
+//!         if let Poll::Ready(output) = Pin::new(&mut future).poll(&mut context) {
+//!             return output;
+//!         }
+//!     }
+//! }
+//!
+//! fn main() {
+//! let mut data = Vec::new();
+//! block_on(to_async_writer(&mut data, &String::from("Hello"))).unwrap();
+//! block_on(to_async_writer(&mut data, &String::from("World"))).unwrap();
+//!
+//! let mut reader = &data[..];
+//! let mut buffer = ReadBuffer::new();
+//! let message: String = block_on(from_async_reader(&mut reader, &mut buffer)).unwrap();
+//! assert_eq!(message, "Hello");
+//! let message: String = block_on(from_async_reader(&mut reader, &mut buffer)).unwrap();
+//! assert_eq!(message, "World");
+//! }
+//! ```
+
+use super::codec::DEFAULT_MAX_LENGTH;
+use super::de::Deserializer;
+use super::error::{Error, ErrorKind, Result};
+use super::ser::Serializer;
+use byteorder::{ByteOrder, LittleEndian};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{self, IoSlice};
+use std::task::{Context, Poll};
+
+pub mod futures;
+pub mod tokio;
+
+/// Most memory committed to a message before more of its data arrives.
+const READ_STEP: usize = 64 * 1024;
+
+/// Reusable buffer holding a message while it's being read.
+///
+/// The buffer keeps its capacity between messages, so reading many
+/// messages only allocates when a message is larger than all the previous
+/// ones. It grows in steps as the data of a message arrives, rather than
+/// trusting the length prefix up front.
+#[derive(Clone, Debug)]
+pub struct ReadBuffer {
+    data: Vec<u8>,
+    filled: usize,
+    max_length: usize,
+}
+
+impl ReadBuffer {
+    /// Create a buffer with the default length limit.
+    pub fn new() -> ReadBuffer {
+        ReadBuffer::with_max_length(DEFAULT_MAX_LENGTH)
+    }
+
+    /// Create a buffer that rejects messages longer than `max_length`
+    /// bytes.
+    pub fn with_max_length(max_length: usize) -> ReadBuffer {
+        ReadBuffer {
+            data: Vec::new(),
+            filled: 0,
+            max_length,
+        }
+    }
+
+    /// Check if a cancelled read left part of a message within the buffer.
+    pub fn is_partial(&self) -> bool {
+        self.filled > 0
+    }
+
+    /// Fill the buffer with a message, and decode it once it's complete.
+    fn poll_message<T, F>(&mut self, cx: &mut Context, mut read: F) -> Poll<Result<T>>
+        where T: DeserializeOwned,
+              F: FnMut(&mut Context, &mut [u8]) -> Poll<io::Result<usize>>
+    {
+        loop {
+            let wanted = if self.filled < 4 {
+                4
+            } else {
+                let length = LittleEndian::read_u32(&self.data[..4]) as usize;
+                if length > self.max_length {
+                    self.filled = 0;
+                    return Poll::Ready(Err(ErrorKind::FrameTooLarge(length, self.max_length)
+                                               .into()));
+                }
+                4 + length
+            };
+            if self.filled >= 4 && self.filled == wanted {
+                self.filled = 0;
+                return Poll::Ready(decode(&self.data[4..wanted]));
+            }
+            let end = if self.filled < self.data.len() {
+                wanted.min(self.data.len())
+            } else {
+                wanted.min(self.filled + READ_STEP)
+            };
+            if self.data.len() < end {
+                self.data.resize(end, 0);
+            }
+            let count = match read(cx, &mut self.data[self.filled..end]) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Ok(0)) => {
+                    return Poll::Ready(Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()))
+                }
+                Poll::Ready(Ok(count)) => count,
+                Poll::Ready(Err(ref error)) if error.kind() == io::ErrorKind::Interrupted => 0,
+                Poll::Ready(Err(error)) => return Poll::Ready(Err(error.into())),
+            };
+            self.filled += count;
+        }
+    }
+}
+
+impl Default for ReadBuffer {
+    fn default() -> ReadBuffer {
+        ReadBuffer::new()
+    }
+}
+
+fn decode<T>(body: &[u8]) -> Result<T>
+    where T: DeserializeOwned
+{
+    let mut deserializer = Deserializer::new(body, body.len() as u32);
+    let value = T::deserialize(&mut deserializer)?;
+    if !deserializer.is_fully_read() {
+        bail!(ErrorKind::Underflow);
+    }
+    Ok(value)
+}
+
+/// Serialized message, along with how much of it got written.
+#[derive(Debug)]
+struct WriteState {
+    prefix: [u8; 4],
+    body: Vec<u8>,
+    written: usize,
+    error: Option<Error>,
+}
+
+impl WriteState {
+    fn new<T>(value: &T) -> WriteState
+        where T: Serialize + ?Sized
+    {
+        let mut body = Vec::with_capacity(128);
+        let error = value.serialize(&mut Serializer::new(&mut body)).err();
+        let mut prefix = [0; 4];
+        LittleEndian::write_u32(&mut prefix, body.len() as u32);
+        WriteState {
+            prefix,
+            body,
+            written: 0,
+            error,
+        }
+    }
+
+    /// Write the length prefix and the body together, then flush.
+    fn poll_write<W, F, G>(&mut self,
+                           cx: &mut Context,
+                           writer: &mut W,
+                           mut write: F,
+                           flush: G)
+                           -> Poll<Result<()>>
+        where W: ?Sized,
+              F: FnMut(&mut W, &mut Context, &[IoSlice]) -> Poll<io::Result<usize>>,
+              G: FnOnce(&mut W, &mut Context) -> Poll<io::Result<()>>
+    {
+        if let Some(error) = self.error.take() {
+            return Poll::Ready(Err(error));
+        }
+        while self.written < 4 + self.body.len() {
+            let result = if self.written < 4 {
+                write(writer,
+                      cx,
+                      &[IoSlice::new(&self.prefix[self.written..]), IoSlice::new(&self.body)])
+            } else {
+                write(writer, cx, &[IoSlice::new(&self.body[self.written - 4..])])
+            };
+            match result {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Ok(0)) => {
+                    return Poll::Ready(Err(io::Error::from(io::ErrorKind::WriteZero).into()))
+                }
+                Poll::Ready(Ok(count)) => self.written += count,
+                Poll::Ready(Err(ref error)) if error.kind() == io::ErrorKind::Interrupted => {}
+                Poll::Ready(Err(error)) => return Poll::Ready(Err(error.into())),
+            }
+        }
+        flush(writer, cx).map_err(|v| v.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::to_vec;
+    use futures_io;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::Waker;
+    use tokio::io::ReadBuf;
+
+    /// Stream handing out at most `chunk` bytes at a time, and returning
+    /// `Pending` before every chunk.
+    struct Trickle {
+        data: Vec<u8>,
+        position: usize,
+        chunk: usize,
+        ready: bool,
+        writes: usize,
+    }
+
+    impl Trickle {
+        fn new(data: Vec<u8>, chunk: usize) -> Trickle {
+            Trickle {
+                data,
+                position: 0,
+                chunk,
+                ready: false,
+                writes: 0,
+            }
+        }
+
+        fn read(&mut self, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+            self.ready = !self.ready;
+            if !self.ready {
+                return Poll::Pending;
+            }
+            let count = buf.len().min(self.chunk).min(self.data.len() - self.position);
+            buf[..count].copy_from_slice(&self.data[self.position..self.position + count]);
+            self.position += count;
+            Poll::Ready(Ok(count))
+        }
+
+        fn write(&mut self, bufs: &[IoSlice]) -> Poll<io::Result<usize>> {
+            self.ready = !self.ready;
+            if !self.ready {
+                return Poll::Pending;
+            }
+            self.writes += 1;
+            let mut count = 0;
+            for buf in bufs {
+                let taken = buf.len().min(self.chunk - count);
+                self.data.extend_from_slice(&buf[..taken]);
+                count += taken;
+            }
+            Poll::Ready(Ok(count))
+        }
+    }
+
+    impl futures_io::AsyncRead for Trickle {
+        fn poll_read(self: Pin<&mut Self>,
+                     _: &mut Context,
+                     buf: &mut [u8])
+                     -> Poll<io::Result<usize>> {
+            self.get_mut().read(buf)
+        }
+    }
+
+    impl futures_io::AsyncWrite for Trickle {
+        fn poll_write(self: Pin<&mut Self>,
+                      _: &mut Context,
+                      buf: &[u8])
+                      -> Poll<io::Result<usize>> {
+            self.get_mut().write(&[IoSlice::new(buf)])
+        }
+
+        fn poll_write_vectored(self: Pin<&mut Self>,
+                               _: &mut Context,
+                               bufs: &[IoSlice])
+                               -> Poll<io::Result<usize>> {
+            self.get_mut().write(bufs)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl ::tokio::io::AsyncRead for Trickle {
+        fn poll_read(self: Pin<&mut Self>,
+                     _: &mut Context,
+                     buf: &mut ReadBuf)
+                     -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            let result = this.read(buf.initialize_unfilled());
+            result.map_ok(|count| buf.advance(count))
+        }
+    }
+
+    impl ::tokio::io::AsyncWrite for Trickle {
+        fn poll_write(self: Pin<&mut Self>,
+                      _: &mut Context,
+                      buf: &[u8])
+                      -> Poll<io::Result<usize>> {
+            self.get_mut().write(&[IoSlice::new(buf)])
+        }
+
+        fn poll_write_vectored(self: Pin<&mut Self>,
+                               _: &mut Context,
+                               bufs: &[IoSlice])
+                               -> Poll<io::Result<usize>> {
+            self.get_mut().write(bufs)
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            true
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn block_on<F>(mut future: F) -> F::Output
+        where F: Future + Unpin
+    {
+        let mut context = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = Pin::new(&mut future).poll(&mut context) {
+                return output;
+            }
+        }
+    }
+
+    fn messages() -> Vec<u8> {
+        let mut data = to_vec(&String::from("Hello, World!")).unwrap();
+        data.extend(to_vec(&Vec::<u16>::new()).unwrap());
+        data.extend(to_vec(&vec![1u16, 2, 3]).unwrap());
+        data
+    }
+
+    #[test]
+    fn reads_messages_in_chunks() {
+        let mut reader = Trickle::new(messages(), 3);
+        let mut buffer = ReadBuffer::new();
+        assert_eq!(block_on(futures::from_async_reader::<_, String>(&mut reader, &mut buffer))
+                       .unwrap(),
+                   "Hello, World!");
+        assert_eq!(block_on(tokio::from_async_reader::<_, Vec<u16>>(&mut reader, &mut buffer))
+                       .unwrap(),
+                   Vec::<u16>::new());
+        assert_eq!(block_on(futures::from_async_reader::<_, Vec<u16>>(&mut reader, &mut buffer))
+                       .unwrap(),
+                   vec![1, 2, 3]);
+        assert!(block_on(tokio::from_async_reader::<_, Vec<u16>>(&mut reader, &mut buffer))
+                    .is_err());
+    }
+
+    #[test]
+    fn resumes_cancelled_reads() {
+        let mut reader = Trickle::new(messages(), 3);
+        let mut buffer = ReadBuffer::new();
+        let mut context = Context::from_waker(Waker::noop());
+        for _ in 0..3 {
+            let mut future = futures::from_async_reader::<_, String>(&mut reader, &mut buffer);
+            assert!(Pin::new(&mut future).poll(&mut context).is_pending());
+        }
+        assert!(buffer.is_partial());
+        assert_eq!(block_on(tokio::from_async_reader::<_, String>(&mut reader, &mut buffer))
+                       .unwrap(),
+                   "Hello, World!");
+        assert!(!buffer.is_partial());
+        {
+            let mut future = tokio::from_async_reader::<_, Vec<u16>>(&mut reader, &mut buffer);
+            assert!(Pin::new(&mut future).poll(&mut context).is_pending());
+        }
+        assert_eq!(block_on(futures::from_async_reader::<_, Vec<u16>>(&mut reader, &mut buffer))
+                       .unwrap(),
+                   Vec::<u16>::new());
+    }
+
+    #[test]
+    fn enforces_length_limits() {
+        let mut reader = Trickle::new(messages(), 100);
+        let mut buffer = ReadBuffer::with_max_length(8);
+        match *block_on(futures::from_async_reader::<_, String>(&mut reader, &mut buffer))
+                   .unwrap_err()
+                   .kind() {
+            ErrorKind::FrameTooLarge(17, 8) => {}
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn grows_with_arriving_data() {
+        let mut data = vec![0, 0, 0, 0x40];
+        data.extend_from_slice(&[7; 100]);
+        let mut reader = Trickle::new(data, 1000);
+        let mut buffer = ReadBuffer::with_max_length(u32::MAX as usize);
+        let mut context = Context::from_waker(Waker::noop());
+        {
+            let mut future = futures::from_async_reader::<_, Vec<u8>>(&mut reader, &mut buffer);
+            for _ in 0..2 {
+                assert!(Pin::new(&mut future).poll(&mut context).is_pending());
+            }
+        }
+        assert!(buffer.is_partial());
+        assert!(buffer.data.len() <= 4 + READ_STEP);
+
+        let message = "x".repeat(3 * READ_STEP);
+        let mut reader = Trickle::new(to_vec(&message).unwrap(), READ_STEP / 2);
+        let mut buffer = ReadBuffer::new();
+        assert_eq!(block_on(tokio::from_async_reader::<_, String>(&mut reader, &mut buffer))
+                       .unwrap(),
+                   message);
+    }
+
+    #[test]
+    fn writes_messages_with_vectored_io() {
+        let mut writer = Trickle::new(Vec::new(), 100);
+        block_on(futures::to_async_writer(&mut writer, "Hello, World!")).unwrap();
+        block_on(tokio::to_async_writer(&mut writer, &vec![1u16, 2, 3])).unwrap();
+        assert_eq!(writer.writes, 2);
+        let mut expected = to_vec(&"Hello, World!").unwrap();
+        expected.extend(to_vec(&vec![1u16, 2, 3]).unwrap());
+        assert_eq!(writer.data, expected);
+
+        let mut writer = Trickle::new(Vec::new(), 3);
+        block_on(tokio::to_async_writer(&mut writer, "Hello, World!")).unwrap();
+        block_on(futures::to_async_writer(&mut writer, &vec![1u16, 2, 3])).unwrap();
+        assert_eq!(writer.data, expected);
+    }
+}
@@ -0,0 +1,93 @@
+//! Reading and writing with the `tokio::io` traits.
+
+use super::{ReadBuffer, WriteState};
+use super::super::error::Result;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Read a message from an `AsyncRead`, using `buffer` to hold its data.
+///
+/// Dropping the future keeps the progress within the buffer, as described
+/// in the [module documentation](../index.html#cancellation-safety).
+pub fn from_async_reader<'a, R, T>(reader: &'a mut R,
+                                   buffer: &'a mut ReadBuffer)
+                                   -> ReadMessage<'a, R, T>
+    where R: AsyncRead + Unpin + ?Sized,
+          T: DeserializeOwned
+{
+    ReadMessage {
+        reader,
+        buffer,
+        phantom: PhantomData,
+    }
+}
+
+/// Serialize a message and write it to an `AsyncWrite`.
+///
+/// The length prefix and the message get written with vectored writes,
+/// followed by a flush.
+pub fn to_async_writer<'a, W, T>(writer: &'a mut W, value: &T) -> WriteMessage<'a, W>
+    where W: AsyncWrite + Unpin + ?Sized,
+          T: Serialize + ?Sized
+{
+    WriteMessage {
+        writer,
+        state: WriteState::new(value),
+    }
+}
+
+/// Future returned by [`from_async_reader`](fn.from_async_reader.html).
+#[derive(Debug)]
+pub struct ReadMessage<'a, R: 'a + ?Sized, T> {
+    reader: &'a mut R,
+    buffer: &'a mut ReadBuffer,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<'a, R, T> Future for ReadMessage<'a, R, T>
+    where R: AsyncRead + Unpin + ?Sized,
+          T: DeserializeOwned
+{
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<T>> {
+        let this = self.get_mut();
+        let reader = &mut this.reader;
+        this.buffer
+            .poll_message(cx, |cx, buf| {
+                let mut buf = ReadBuf::new(buf);
+                match Pin::new(&mut **reader).poll_read(cx, &mut buf) {
+                    Poll::Ready(Ok(())) => Poll::Ready(Ok(buf.filled().len())),
+                    Poll::Ready(Err(error)) => Poll::Ready(Err(error)),
+                    Poll::Pending => Poll::Pending,
+                }
+            })
+    }
+}
+
+/// Future returned by [`to_async_writer`](fn.to_async_writer.html).
+#[derive(Debug)]
+pub struct WriteMessage<'a, W: 'a + ?Sized> {
+    writer: &'a mut W,
+    state: WriteState,
+}
+
+impl<'a, W> Future for WriteMessage<'a, W>
+    where W: AsyncWrite + Unpin + ?Sized
+{
+    type Output = Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        this.state
+            .poll_write(cx,
+                        &mut *this.writer,
+                        |writer, cx, bufs| Pin::new(writer).poll_write_vectored(cx, bufs),
+                        |writer, cx| Pin::new(writer).poll_flush(cx))
+    }
+}
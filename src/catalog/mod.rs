@@ -0,0 +1,250 @@
+//! Bundled definitions of the standard ROS message packages.
+//!
+//! The catalog embeds the `.msg` sources of `std_msgs`, `geometry_msgs`,
+//! `sensor_msgs`, `nav_msgs`, `actionlib_msgs`, `diagnostic_msgs`,
+//! `rosgraph_msgs`, `tf2_msgs` and `visualization_msgs`, so the
+//! `message_definition` and MD5 sum of those messages are available without
+//! a ROS installation.
+//!
+//! It is only available with the `catalog` feature enabled.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use serde_rosmsg::catalog;
+//! let entry = catalog::find("geometry_msgs/Point").unwrap();
+//! assert_eq!(entry.md5sum, "4a842b65f413084dc2b10fb484ea7f17");
+//! assert_eq!(entry.message_definition,
+//!            "# This contains the position of a point in free space\n\
+//!             float64 x\nfloat64 y\nfloat64 z\n");
+//! ```
+
+use super::schema::Schema;
+
+/// Everything ROS needs to know about a message type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// Full name of the message, like `geometry_msgs/Point`.
+    pub name: &'static str,
+    /// Source of the message's own `.msg` file.
+    pub definition: &'static str,
+    /// Full `message_definition`, including all dependencies.
+    pub message_definition: String,
+    /// MD5 sum of the message.
+    pub md5sum: String,
+}
+
+/// Get the `.msg` source of the message with the given full name.
+pub fn definition(name: &str) -> Option<&'static str> {
+    MESSAGES.iter().find(|&&(key, _)| key == name).map(|&(_, definition)| definition)
+}
+
+/// Iterate over the full names of all messages within the catalog.
+pub fn names() -> impl Iterator<Item = &'static str> {
+    MESSAGES.iter().map(|&(name, _)| name)
+}
+
+/// Get the parsed schema of the message with the given full name.
+pub fn schema(name: &str) -> Option<Schema> {
+    definition(name)?;
+    Schema::build(name, |name| definition(name).map(String::from)).ok()
+}
+
+/// Get the full description of the message with the given full name.
+pub fn find(name: &str) -> Option<Entry> {
+    let name = MESSAGES.iter().find(|&&(key, _)| key == name)?.0;
+    let schema = schema(name)?;
+    Some(Entry {
+             name,
+             definition: definition(name)?,
+             message_definition: schema.message_definition().ok()?,
+             md5sum: schema.md5sum().ok()?,
+         })
+}
+
+static MESSAGES: &[(&str, &str)] = &[
+    ("actionlib_msgs/GoalID", include_str!("actionlib_msgs/GoalID.msg")),
+    ("actionlib_msgs/GoalStatus", include_str!("actionlib_msgs/GoalStatus.msg")),
+    ("actionlib_msgs/GoalStatusArray", include_str!("actionlib_msgs/GoalStatusArray.msg")),
+    ("diagnostic_msgs/DiagnosticArray", include_str!("diagnostic_msgs/DiagnosticArray.msg")),
+    ("diagnostic_msgs/DiagnosticStatus", include_str!("diagnostic_msgs/DiagnosticStatus.msg")),
+    ("diagnostic_msgs/KeyValue", include_str!("diagnostic_msgs/KeyValue.msg")),
+    ("geometry_msgs/Accel", include_str!("geometry_msgs/Accel.msg")),
+    ("geometry_msgs/AccelStamped", include_str!("geometry_msgs/AccelStamped.msg")),
+    ("geometry_msgs/AccelWithCovariance", include_str!("geometry_msgs/AccelWithCovariance.msg")),
+    ("geometry_msgs/AccelWithCovarianceStamped", include_str!("geometry_msgs/AccelWithCovarianceStamped.msg")),
+    ("geometry_msgs/Inertia", include_str!("geometry_msgs/Inertia.msg")),
+    ("geometry_msgs/InertiaStamped", include_str!("geometry_msgs/InertiaStamped.msg")),
+    ("geometry_msgs/Point", include_str!("geometry_msgs/Point.msg")),
+    ("geometry_msgs/Point32", include_str!("geometry_msgs/Point32.msg")),
+    ("geometry_msgs/PointStamped", include_str!("geometry_msgs/PointStamped.msg")),
+    ("geometry_msgs/Polygon", include_str!("geometry_msgs/Polygon.msg")),
+    ("geometry_msgs/PolygonStamped", include_str!("geometry_msgs/PolygonStamped.msg")),
+    ("geometry_msgs/Pose", include_str!("geometry_msgs/Pose.msg")),
+    ("geometry_msgs/Pose2D", include_str!("geometry_msgs/Pose2D.msg")),
+    ("geometry_msgs/PoseArray", include_str!("geometry_msgs/PoseArray.msg")),
+    ("geometry_msgs/PoseStamped", include_str!("geometry_msgs/PoseStamped.msg")),
+    ("geometry_msgs/PoseWithCovariance", include_str!("geometry_msgs/PoseWithCovariance.msg")),
+    ("geometry_msgs/PoseWithCovarianceStamped", include_str!("geometry_msgs/PoseWithCovarianceStamped.msg")),
+    ("geometry_msgs/Quaternion", include_str!("geometry_msgs/Quaternion.msg")),
+    ("geometry_msgs/QuaternionStamped", include_str!("geometry_msgs/QuaternionStamped.msg")),
+    ("geometry_msgs/Transform", include_str!("geometry_msgs/Transform.msg")),
+    ("geometry_msgs/TransformStamped", include_str!("geometry_msgs/TransformStamped.msg")),
+    ("geometry_msgs/Twist", include_str!("geometry_msgs/Twist.msg")),
+    ("geometry_msgs/TwistStamped", include_str!("geometry_msgs/TwistStamped.msg")),
+    ("geometry_msgs/TwistWithCovariance", include_str!("geometry_msgs/TwistWithCovariance.msg")),
+    ("geometry_msgs/TwistWithCovarianceStamped", include_str!("geometry_msgs/TwistWithCovarianceStamped.msg")),
+    ("geometry_msgs/Vector3", include_str!("geometry_msgs/Vector3.msg")),
+    ("geometry_msgs/Vector3Stamped", include_str!("geometry_msgs/Vector3Stamped.msg")),
+    ("geometry_msgs/Wrench", include_str!("geometry_msgs/Wrench.msg")),
+    ("geometry_msgs/WrenchStamped", include_str!("geometry_msgs/WrenchStamped.msg")),
+    ("nav_msgs/GridCells", include_str!("nav_msgs/GridCells.msg")),
+    ("nav_msgs/MapMetaData", include_str!("nav_msgs/MapMetaData.msg")),
+    ("nav_msgs/OccupancyGrid", include_str!("nav_msgs/OccupancyGrid.msg")),
+    ("nav_msgs/Odometry", include_str!("nav_msgs/Odometry.msg")),
+    ("nav_msgs/Path", include_str!("nav_msgs/Path.msg")),
+    ("rosgraph_msgs/Clock", include_str!("rosgraph_msgs/Clock.msg")),
+    ("rosgraph_msgs/Log", include_str!("rosgraph_msgs/Log.msg")),
+    ("rosgraph_msgs/TopicStatistics", include_str!("rosgraph_msgs/TopicStatistics.msg")),
+    ("sensor_msgs/BatteryState", include_str!("sensor_msgs/BatteryState.msg")),
+    ("sensor_msgs/CameraInfo", include_str!("sensor_msgs/CameraInfo.msg")),
+    ("sensor_msgs/ChannelFloat32", include_str!("sensor_msgs/ChannelFloat32.msg")),
+    ("sensor_msgs/CompressedImage", include_str!("sensor_msgs/CompressedImage.msg")),
+    ("sensor_msgs/FluidPressure", include_str!("sensor_msgs/FluidPressure.msg")),
+    ("sensor_msgs/Illuminance", include_str!("sensor_msgs/Illuminance.msg")),
+    ("sensor_msgs/Image", include_str!("sensor_msgs/Image.msg")),
+    ("sensor_msgs/Imu", include_str!("sensor_msgs/Imu.msg")),
+    ("sensor_msgs/JointState", include_str!("sensor_msgs/JointState.msg")),
+    ("sensor_msgs/Joy", include_str!("sensor_msgs/Joy.msg")),
+    ("sensor_msgs/JoyFeedback", include_str!("sensor_msgs/JoyFeedback.msg")),
+    ("sensor_msgs/JoyFeedbackArray", include_str!("sensor_msgs/JoyFeedbackArray.msg")),
+    ("sensor_msgs/LaserEcho", include_str!("sensor_msgs/LaserEcho.msg")),
+    ("sensor_msgs/LaserScan", include_str!("sensor_msgs/LaserScan.msg")),
+    ("sensor_msgs/MagneticField", include_str!("sensor_msgs/MagneticField.msg")),
+    ("sensor_msgs/MultiDOFJointState", include_str!("sensor_msgs/MultiDOFJointState.msg")),
+    ("sensor_msgs/MultiEchoLaserScan", include_str!("sensor_msgs/MultiEchoLaserScan.msg")),
+    ("sensor_msgs/NavSatFix", include_str!("sensor_msgs/NavSatFix.msg")),
+    ("sensor_msgs/NavSatStatus", include_str!("sensor_msgs/NavSatStatus.msg")),
+    ("sensor_msgs/PointCloud", include_str!("sensor_msgs/PointCloud.msg")),
+    ("sensor_msgs/PointCloud2", include_str!("sensor_msgs/PointCloud2.msg")),
+    ("sensor_msgs/PointField", include_str!("sensor_msgs/PointField.msg")),
+    ("sensor_msgs/Range", include_str!("sensor_msgs/Range.msg")),
+    ("sensor_msgs/RegionOfInterest", include_str!("sensor_msgs/RegionOfInterest.msg")),
+    ("sensor_msgs/RelativeHumidity", include_str!("sensor_msgs/RelativeHumidity.msg")),
+    ("sensor_msgs/Temperature", include_str!("sensor_msgs/Temperature.msg")),
+    ("sensor_msgs/TimeReference", include_str!("sensor_msgs/TimeReference.msg")),
+    ("std_msgs/Bool", include_str!("std_msgs/Bool.msg")),
+    ("std_msgs/Byte", include_str!("std_msgs/Byte.msg")),
+    ("std_msgs/ByteMultiArray", include_str!("std_msgs/ByteMultiArray.msg")),
+    ("std_msgs/Char", include_str!("std_msgs/Char.msg")),
+    ("std_msgs/ColorRGBA", include_str!("std_msgs/ColorRGBA.msg")),
+    ("std_msgs/Duration", include_str!("std_msgs/Duration.msg")),
+    ("std_msgs/Empty", include_str!("std_msgs/Empty.msg")),
+    ("std_msgs/Float32", include_str!("std_msgs/Float32.msg")),
+    ("std_msgs/Float32MultiArray", include_str!("std_msgs/Float32MultiArray.msg")),
+    ("std_msgs/Float64", include_str!("std_msgs/Float64.msg")),
+    ("std_msgs/Float64MultiArray", include_str!("std_msgs/Float64MultiArray.msg")),
+    ("std_msgs/Header", include_str!("std_msgs/Header.msg")),
+    ("std_msgs/Int16", include_str!("std_msgs/Int16.msg")),
+    ("std_msgs/Int16MultiArray", include_str!("std_msgs/Int16MultiArray.msg")),
+    ("std_msgs/Int32", include_str!("std_msgs/Int32.msg")),
+    ("std_msgs/Int32MultiArray", include_str!("std_msgs/Int32MultiArray.msg")),
+    ("std_msgs/Int64", include_str!("std_msgs/Int64.msg")),
+    ("std_msgs/Int64MultiArray", include_str!("std_msgs/Int64MultiArray.msg")),
+    ("std_msgs/Int8", include_str!("std_msgs/Int8.msg")),
+    ("std_msgs/Int8MultiArray", include_str!("std_msgs/Int8MultiArray.msg")),
+    ("std_msgs/MultiArrayDimension", include_str!("std_msgs/MultiArrayDimension.msg")),
+    ("std_msgs/MultiArrayLayout", include_str!("std_msgs/MultiArrayLayout.msg")),
+    ("std_msgs/String", include_str!("std_msgs/String.msg")),
+    ("std_msgs/Time", include_str!("std_msgs/Time.msg")),
+    ("std_msgs/UInt16", include_str!("std_msgs/UInt16.msg")),
+    ("std_msgs/UInt16MultiArray", include_str!("std_msgs/UInt16MultiArray.msg")),
+    ("std_msgs/UInt32", include_str!("std_msgs/UInt32.msg")),
+    ("std_msgs/UInt32MultiArray", include_str!("std_msgs/UInt32MultiArray.msg")),
+    ("std_msgs/UInt64", include_str!("std_msgs/UInt64.msg")),
+    ("std_msgs/UInt64MultiArray", include_str!("std_msgs/UInt64MultiArray.msg")),
+    ("std_msgs/UInt8", include_str!("std_msgs/UInt8.msg")),
+    ("std_msgs/UInt8MultiArray", include_str!("std_msgs/UInt8MultiArray.msg")),
+    ("tf2_msgs/TF2Error", include_str!("tf2_msgs/TF2Error.msg")),
+    ("tf2_msgs/TFMessage", include_str!("tf2_msgs/TFMessage.msg")),
+    ("visualization_msgs/ImageMarker", include_str!("visualization_msgs/ImageMarker.msg")),
+    ("visualization_msgs/InteractiveMarker", include_str!("visualization_msgs/InteractiveMarker.msg")),
+    ("visualization_msgs/InteractiveMarkerControl", include_str!("visualization_msgs/InteractiveMarkerControl.msg")),
+    ("visualization_msgs/InteractiveMarkerFeedback", include_str!("visualization_msgs/InteractiveMarkerFeedback.msg")),
+    ("visualization_msgs/InteractiveMarkerInit", include_str!("visualization_msgs/InteractiveMarkerInit.msg")),
+    ("visualization_msgs/InteractiveMarkerPose", include_str!("visualization_msgs/InteractiveMarkerPose.msg")),
+    ("visualization_msgs/InteractiveMarkerUpdate", include_str!("visualization_msgs/InteractiveMarkerUpdate.msg")),
+    ("visualization_msgs/Marker", include_str!("visualization_msgs/Marker.msg")),
+    ("visualization_msgs/MarkerArray", include_str!("visualization_msgs/MarkerArray.msg")),
+    ("visualization_msgs/MenuEntry", include_str!("visualization_msgs/MenuEntry.msg")),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_all_messages() {
+        for name in names() {
+            let schema = schema(name).unwrap_or_else(|| panic!("Failed to parse {}", name));
+            assert_eq!(schema.name(), name);
+            assert!(find(name).is_some());
+        }
+    }
+
+    #[test]
+    fn reports_unknown_messages() {
+        assert_eq!(definition("std_msgs/Nothing"), None);
+        assert_eq!(schema("std_msgs/Nothing"), None);
+        assert_eq!(find("std_msgs/Nothing"), None);
+    }
+
+    #[test]
+    fn matches_known_md5sums() {
+        let known = [("std_msgs/Header", "2176decaecbce78abc3b96ef049fabed"),
+                     ("std_msgs/Empty", "d41d8cd98f00b204e9800998ecf8427e"),
+                     ("std_msgs/Bool", "8b94c1b53db61fb6aed406028ad6332a"),
+                     ("std_msgs/Float64", "fdb28210bfa9d7c91146260178d9a584"),
+                     ("std_msgs/Int32", "da5909fbe378aeaf85e547e830cc1bb7"),
+                     ("std_msgs/String", "992ce8a1687cec8c8bd883ec73ca41d1"),
+                     ("std_msgs/ColorRGBA", "a29a96539573343b1310c73607334b00"),
+                     ("std_msgs/Time", "cd7166c74c552c311fbcc2fe5a7bc289"),
+                     ("geometry_msgs/Point", "4a842b65f413084dc2b10fb484ea7f17"),
+                     ("geometry_msgs/Quaternion", "a779879fadf0160734f906b8c19c7004"),
+                     ("geometry_msgs/Pose", "e45d45a5a1ce597b249e23fb30fc871f"),
+                     ("geometry_msgs/PoseArray", "916c28c5764443f268b296bb671b9d97"),
+                     ("geometry_msgs/PoseWithCovariance", "c23e848cf1b7533a8d7c259073a97e6f"),
+                     ("geometry_msgs/Twist", "9f195f881246fdfa2798d1d3eebca84a"),
+                     ("geometry_msgs/PoseStamped", "d3812c3cbc69362b77dc0b19b345f8f5"),
+                     ("geometry_msgs/TransformStamped", "b5764a33bfeb3588febc2682852579b0"),
+                     ("sensor_msgs/Image", "060021388200f6f0f447d0fcd9c64743"),
+                     ("sensor_msgs/Imu", "6a62c6daae103f4ff57a132d6f95cec2"),
+                     ("sensor_msgs/LaserScan", "90c7ef2dc6895d81024acba2ac42f369"),
+                     ("sensor_msgs/PointCloud2", "1158d486dd51d683ce2f1be655c3c181"),
+                     ("sensor_msgs/CameraInfo", "c9a58c1b0b154e0e6da7578cb991d214"),
+                     ("sensor_msgs/JointState", "3066dcd76a6cfaef579bd0f34173e9fd"),
+                     ("nav_msgs/Odometry", "cd5e73d190d741a2f92e81eda573aca7"),
+                     ("nav_msgs/Path", "6227e2b7e9cce15051f669a5e197bbf7"),
+                     ("nav_msgs/OccupancyGrid", "3381f2d731d4076ec5c71b0759edbe4e"),
+                     ("tf2_msgs/TFMessage", "94810edda583a504dfda3829e70d7eec"),
+                     ("rosgraph_msgs/Log", "acffd30cd6b6de30f120938c17c593fb"),
+                     ("rosgraph_msgs/Clock", "a9c97c1d230cfc112e270351a944ee47"),
+                     ("visualization_msgs/Marker", "4048c9de2a16f4ae8e0538085ebf1b97"),
+                     ("visualization_msgs/MarkerArray", "d155b9ce5188fbaf89745847fd5882d7"),
+                     ("diagnostic_msgs/DiagnosticArray", "60810da900de1dd6ddd437c3503511da"),
+                     ("actionlib_msgs/GoalStatusArray", "8b2b82f13216d0a8ea88bd3af735e619")];
+        for &(name, md5sum) in known.iter() {
+            assert_eq!(find(name).unwrap().md5sum, md5sum, "MD5 sum of {}", name);
+        }
+    }
+
+    #[test]
+    fn matches_recorded_message_definitions() {
+        assert_eq!(find("geometry_msgs/Pose").unwrap().message_definition,
+                   include_str!("../datatests/pose_message_definition.txt"));
+        assert_eq!(find("geometry_msgs/PoseWithCovariance").unwrap().message_definition,
+                   include_str!("../datatests/pose_with_covariance_message_definition.txt"));
+        assert_eq!(find("geometry_msgs/PoseArray").unwrap().message_definition,
+                   include_str!("../datatests/pose_array_message_definition.txt"));
+    }
+}
@@ -0,0 +1,397 @@
+//! Codecs for framing ROSMSG data within asynchronous streams.
+//!
+//! Requires the `async` feature.
+//!
+//! The codecs implement the `tokio_util` `Decoder` and `Encoder` traits, so
+//! they can turn any `AsyncRead` and `AsyncWrite` into a stream and sink of
+//! messages with `FramedRead`, `FramedWrite` or `Framed`. Every frame
+//! starts with its length, which gets checked against a limit before any
+//! memory is reserved for it. Even then, memory is only reserved in steps,
+//! as the data of the frame arrives.
+//!
+//! * [`FrameCodec`](struct.FrameCodec.html) yields the raw bytes of frames.
+//! * [`MessageCodec`](struct.MessageCodec.html) yields messages of a type.
+//! * [`HeaderCodec`](struct.HeaderCodec.html) yields connection headers.
+//!
+//! # Examples
+//!
+//! ```rust
+//! extern crate bytes;
+//! extern crate serde_rosmsg;
+//! extern crate tokio_util;
+//!
+//! use bytes::BytesMut;
+//! use serde_rosmsg::codec::MessageCodec;
+//! use tokio_util::codec::{Decoder, Encoder};
+//!
+//! fn main() {
+//! let mut codec = MessageCodec::<String>::new();
+//! let mut buffer = BytesMut::new();
+//! codec.encode(&String::from("Hello"), &mut buffer).unwrap();
+//! codec.encode(&String::from("World"), &mut buffer).unwrap();
+//! assert_eq!(&buffer[..13], b"\x09\0\0\0\x05\0\0\0Hello");
+//! assert_eq!(codec.decode(&mut buffer).unwrap(), Some(String::from("Hello")));
+//! assert_eq!(codec.decode(&mut buffer).unwrap(), Some(String::from("World")));
+//! assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+//! }
+//! ```
+
+use super::de::Deserializer;
+use super::error::{Error, ErrorKind, Result};
+use super::header::ConnectionHeader;
+use super::ser::Serializer;
+use byteorder::{ByteOrder, LittleEndian};
+use bytes::{BufMut, Bytes, BytesMut};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::marker::PhantomData;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Default limit on the length of frames, of 64 MiB.
+///
+/// That's far below roscpp's limit of 1 GB, since any peer can make a
+/// connection buffer up to the limit. Raise it for bigger messages, like
+/// high resolution images or point clouds.
+pub const DEFAULT_MAX_LENGTH: usize = 64 * 1024 * 1024;
+
+/// Most memory reserved for a frame before more of its data arrives.
+const RESERVE_STEP: usize = 64 * 1024;
+
+/// Codec for raw frames, without the length prefix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameCodec {
+    max_length: usize,
+}
+
+impl FrameCodec {
+    /// Create a codec with the default length limit.
+    pub fn new() -> FrameCodec {
+        FrameCodec::with_max_length(DEFAULT_MAX_LENGTH)
+    }
+
+    /// Create a codec that rejects frames longer than `max_length` bytes.
+    pub fn with_max_length(max_length: usize) -> FrameCodec {
+        FrameCodec { max_length }
+    }
+
+    /// Limit on the length of frames, excluding the length prefix.
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    fn check_length(&self, length: usize) -> Result<()> {
+        if length > self.max_length {
+            bail!(ErrorKind::FrameTooLarge(length, self.max_length));
+        }
+        Ok(())
+    }
+
+    /// Serialize a value as a frame, straight into the buffer.
+    fn encode_value<T>(&self, value: &T, dst: &mut BytesMut) -> Result<()>
+        where T: Serialize + ?Sized
+    {
+        let start = dst.len();
+        dst.put_u32_le(0);
+        let result = value
+            .serialize(&mut Serializer::new(dst.writer()))
+            .and_then(|_| self.check_length(dst.len() - start - 4));
+        if let Err(error) = result {
+            dst.truncate(start);
+            return Err(error);
+        }
+        let length = (dst.len() - start - 4) as u32;
+        LittleEndian::write_u32(&mut dst[start..start + 4], length);
+        Ok(())
+    }
+
+    /// Deserialize a frame, without copying its contents.
+    fn decode_value<T>(&mut self, src: &mut BytesMut) -> Result<Option<T>>
+        where T: DeserializeOwned
+    {
+        let frame = match self.decode(src)? {
+            Some(frame) => frame,
+            None => return Ok(None),
+        };
+        let mut deserializer = Deserializer::new(&frame[..], frame.len() as u32);
+        let value = T::deserialize(&mut deserializer)?;
+        if !deserializer.is_fully_read() {
+            bail!(ErrorKind::Underflow);
+        }
+        Ok(Some(value))
+    }
+}
+
+impl Default for FrameCodec {
+    fn default() -> FrameCodec {
+        FrameCodec::new()
+    }
+}
+
+impl Decoder for FrameCodec {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Bytes>> {
+        if src.len() < 4 {
+            return Ok(None);
+        }
+        let length = LittleEndian::read_u32(&src[..4]) as usize;
+        self.check_length(length)?;
+        if src.len() < 4 + length {
+            src.reserve((4 + length - src.len()).min(RESERVE_STEP));
+            return Ok(None);
+        }
+        let _ = src.split_to(4);
+        Ok(Some(src.split_to(length).freeze()))
+    }
+}
+
+impl<'a> Encoder<&'a [u8]> for FrameCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: &'a [u8], dst: &mut BytesMut) -> Result<()> {
+        self.check_length(item.len())?;
+        dst.reserve(4 + item.len());
+        dst.put_u32_le(item.len() as u32);
+        dst.extend_from_slice(item);
+        Ok(())
+    }
+}
+
+impl Encoder<Bytes> for FrameCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> Result<()> {
+        self.encode(&item[..], dst)
+    }
+}
+
+/// Codec for messages of type `T`.
+pub struct MessageCodec<T> {
+    frames: FrameCodec,
+    phantom: PhantomData<fn(T) -> T>,
+}
+
+impl<T> MessageCodec<T> {
+    /// Create a codec with the default length limit.
+    pub fn new() -> MessageCodec<T> {
+        MessageCodec::with_max_length(DEFAULT_MAX_LENGTH)
+    }
+
+    /// Create a codec that rejects messages longer than `max_length` bytes.
+    pub fn with_max_length(max_length: usize) -> MessageCodec<T> {
+        MessageCodec {
+            frames: FrameCodec::with_max_length(max_length),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Limit on the length of messages, excluding the length prefix.
+    pub fn max_length(&self) -> usize {
+        self.frames.max_length()
+    }
+}
+
+impl<T> Clone for MessageCodec<T> {
+    fn clone(&self) -> MessageCodec<T> {
+        MessageCodec::with_max_length(self.max_length())
+    }
+}
+
+impl<T> Default for MessageCodec<T> {
+    fn default() -> MessageCodec<T> {
+        MessageCodec::new()
+    }
+}
+
+impl<T> fmt::Debug for MessageCodec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MessageCodec")
+            .field("max_length", &self.max_length())
+            .finish()
+    }
+}
+
+impl<T> Decoder for MessageCodec<T>
+    where T: DeserializeOwned
+{
+    type Item = T;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<T>> {
+        self.frames.decode_value(src)
+    }
+}
+
+impl<'a, T> Encoder<&'a T> for MessageCodec<T>
+    where T: Serialize
+{
+    type Error = Error;
+
+    fn encode(&mut self, item: &'a T, dst: &mut BytesMut) -> Result<()> {
+        self.frames.encode_value(item, dst)
+    }
+}
+
+impl<T> Encoder<T> for MessageCodec<T>
+    where T: Serialize
+{
+    type Error = Error;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<()> {
+        self.frames.encode_value(&item, dst)
+    }
+}
+
+/// Codec for connection headers.
+///
+/// Headers only get exchanged at the start of a connection, so once the
+/// handshake is done, the `Framed` stream can be mapped onto a
+/// [`MessageCodec`](struct.MessageCodec.html) with `map_codec`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HeaderCodec {
+    frames: FrameCodec,
+}
+
+impl HeaderCodec {
+    /// Create a codec with the default length limit.
+    pub fn new() -> HeaderCodec {
+        HeaderCodec::default()
+    }
+
+    /// Create a codec that rejects headers longer than `max_length` bytes.
+    pub fn with_max_length(max_length: usize) -> HeaderCodec {
+        HeaderCodec { frames: FrameCodec::with_max_length(max_length) }
+    }
+
+    /// Limit on the length of headers, excluding the length prefix.
+    pub fn max_length(&self) -> usize {
+        self.frames.max_length()
+    }
+}
+
+impl Decoder for HeaderCodec {
+    type Item = ConnectionHeader;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<ConnectionHeader>> {
+        self.frames.decode_value(src)
+    }
+}
+
+impl<'a> Encoder<&'a ConnectionHeader> for HeaderCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: &'a ConnectionHeader, dst: &mut BytesMut) -> Result<()> {
+        self.frames.encode_value(item, dst)
+    }
+}
+
+impl Encoder<ConnectionHeader> for HeaderCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: ConnectionHeader, dst: &mut BytesMut) -> Result<()> {
+        self.frames.encode_value(&item, dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{from_slice, to_vec};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Point {
+        x: f64,
+        y: f64,
+        z: f64,
+    }
+
+    #[test]
+    fn decodes_frames_split_at_any_point() {
+        let mut data = to_vec(&String::from("Hello")).unwrap();
+        data.extend(to_vec(&vec![1u8, 2, 3]).unwrap());
+        for split in 0..data.len() {
+            let mut codec = FrameCodec::new();
+            let mut buffer = BytesMut::from(&data[..split]);
+            let mut frames = Vec::new();
+            while let Some(frame) = codec.decode(&mut buffer).unwrap() {
+                frames.push(frame);
+            }
+            buffer.extend_from_slice(&data[split..]);
+            while let Some(frame) = codec.decode(&mut buffer).unwrap() {
+                frames.push(frame);
+            }
+            assert_eq!(frames,
+                       vec![Bytes::from_static(b"\x05\0\0\0Hello"),
+                            Bytes::from_static(b"\x03\0\0\0\x01\x02\x03")]);
+            assert!(buffer.is_empty());
+        }
+    }
+
+    #[test]
+    fn encodes_frames() {
+        let mut codec = FrameCodec::new();
+        let mut buffer = BytesMut::new();
+        codec.encode(&b"abc"[..], &mut buffer).unwrap();
+        codec.encode(Bytes::from_static(b""), &mut buffer).unwrap();
+        assert_eq!(&buffer[..], b"\x03\0\0\0abc\0\0\0\0");
+    }
+
+    #[test]
+    fn enforces_length_limits() {
+        let mut codec = FrameCodec::with_max_length(4);
+        let mut buffer = BytesMut::from(&b"\x05\0\0\0"[..]);
+        match *codec.decode(&mut buffer).unwrap_err().kind() {
+            ErrorKind::FrameTooLarge(5, 4) => {}
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+        assert!(codec.encode(&b"abcde"[..], &mut buffer).is_err());
+        let mut codec = MessageCodec::<String>::with_max_length(8);
+        let mut buffer = BytesMut::from(&b"ab"[..]);
+        codec.encode(&String::from("abcd"), &mut buffer).unwrap();
+        assert!(codec.encode(&String::from("abcde"), &mut buffer).is_err());
+        assert_eq!(&buffer[..], b"ab\x08\0\0\0\x04\0\0\0abcd");
+    }
+
+    #[test]
+    fn reserves_memory_as_data_arrives() {
+        let mut codec = FrameCodec::with_max_length(u32::MAX as usize);
+        let mut buffer = BytesMut::from(&b"\0\0\0\x40"[..]);
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+        assert!(buffer.capacity() <= 4 + RESERVE_STEP);
+        buffer.extend_from_slice(&[7; 100]);
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+        assert!(buffer.capacity() <= 2 * (4 + 100 + RESERVE_STEP));
+    }
+
+    #[test]
+    fn encodes_and_decodes_messages() {
+        let mut codec = MessageCodec::<Point>::new();
+        let mut buffer = BytesMut::new();
+        let point = Point {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        };
+        codec.encode(&point, &mut buffer).unwrap();
+        assert_eq!(&buffer[..], &to_vec(&point).unwrap()[..]);
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some(point));
+        buffer.extend_from_slice(b"\x04\0\0\0\0\0\0\0");
+        assert!(codec.decode(&mut buffer).is_err());
+    }
+
+    #[test]
+    fn encodes_and_decodes_headers() {
+        let mut header = ConnectionHeader::new();
+        header.insert("callerid", "/node");
+        header.insert("topic", "/chatter");
+        let mut codec = HeaderCodec::new();
+        let mut buffer = BytesMut::new();
+        codec.encode(&header, &mut buffer).unwrap();
+        assert_eq!(from_slice::<ConnectionHeader>(&buffer).unwrap(), header);
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some(header));
+        assert!(buffer.is_empty());
+    }
+}
@@ -0,0 +1,756 @@
+//! Generate Rust types out of ROS message definitions.
+//!
+//! The generator is meant to be used within build scripts. It reads all
+//! `.msg` and `.srv` files within the given directories, and writes a Rust
+//! module for every package, containing a structure for every message, and
+//! a request and a response structure for every service.
+//!
+//! Files are expected to be placed in ROS's usual layout, with each package
+//! having its definitions within `msg` and `srv` directories, like
+//! `my_msgs/msg/Point.msg`. Files placed directly within a package
+//! directory, like `my_msgs/Point.msg`, are accepted too.
+//!
+//! Packages nested within other directories get generated as nested
+//! modules, following the directory layout below the searched directory.
+//! With `msg` searched, `msg/robots/arm_msgs/msg/Joint.msg` becomes
+//! `robots::arm_msgs::Joint`, while references to it keep using the ROS name
+//! `arm_msgs/Joint`. Since ROS identifies packages by name alone, every
+//! package can only be placed in one directory, and every message or
+//! service can only be defined once.
+//!
+//! Generated structures derive `Serialize` and `Deserialize`, so
+//! `serde_derive`'s macros need to be in scope. Field order matches the
+//! definition, which makes the structures usable with the rest of the crate.
+//! Constants are turned into associated constants.
+//!
+//! If the `catalog` feature is enabled, standard messages that are used but
+//! not found within the directories get generated from the bundled
+//! [`catalog`](../catalog/index.html). Definitions found within the
+//! directories always take precedence over the bundled ones.
+//!
+//! # Examples
+//!
+//! Generate the modules from within `build.rs`:
+//!
+//! ```rust,no_run
+//! extern crate serde_rosmsg;
+//!
+//! use std::env;
+//! use std::path::Path;
+//!
+//! fn main() {
+//!     let output = Path::new(&env::var("OUT_DIR").unwrap()).join("msgs.rs");
+//!     serde_rosmsg::codegen::generate_to_file(&["msg"], output).unwrap();
+//!     println!("cargo:rerun-if-changed=msg");
+//! }
+//! ```
+//!
+//! And include them within the crate:
+//!
+//! ```rust,ignore
+//! #[macro_use]
+//! extern crate serde_derive;
+//! extern crate serde_rosmsg;
+//!
+//! pub mod msgs {
+//!     include!(concat!(env!("OUT_DIR"), "/msgs.rs"));
+//! }
+//! ```
+
+use super::error::{ErrorKind, Result};
+use super::schema::{self, BaseType, Constant, Field, FieldType, Msg, Schema};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// Generate the code for all packages found within the given directories.
+///
+/// Directories are searched recursively.
+pub fn generate<P>(paths: &[P]) -> Result<String>
+    where P: AsRef<Path>
+{
+    let mut packages = BTreeMap::new();
+    for path in paths {
+        load_directory(path.as_ref(), path.as_ref(), &mut packages)?;
+    }
+    load_dependencies(&mut packages)?;
+    let mut sources = HashMap::new();
+    for package in packages.values() {
+        let services = package.services.values().flat_map(|(req, res)| vec![req, res]);
+        for msg in package.messages.values().chain(services) {
+            if sources.insert(msg.full_name(), msg.source.clone()).is_some() {
+                bail!(ErrorKind::BadMessageDefinition(format!("{} is defined twice",
+                                                              msg.full_name())));
+            }
+        }
+    }
+    let modules = packages.iter()
+        .map(|(name, package)| {
+                 let mut module = package.path.clone();
+                 module.push(name.clone());
+                 (name.clone(), module)
+             })
+        .collect::<HashMap<_, _>>();
+    let mut order = packages.keys().collect::<Vec<_>>();
+    order.sort_by_key(|name| &modules[*name]);
+    let mut code = String::from("// Generated by serde_rosmsg::codegen, do not edit by hand.\n");
+    let mut open: Vec<String> = Vec::new();
+    for name in order {
+        let module = &modules[name];
+        while !module.starts_with(&open) {
+            open.pop();
+            code.push_str(&indent("}\n", open.len()));
+        }
+        while open.len() < module.len() {
+            let attributes = if open.len() + 1 == module.len() {
+                "#[allow(dead_code, non_camel_case_types, non_snake_case, \
+                 non_upper_case_globals)]\n"
+            } else {
+                ""
+            };
+            let name = module[open.len()].clone();
+            code.push_str(&indent(&format!("\n{}pub mod {} {{", attributes, name), open.len()));
+            open.push(name);
+        }
+        let package = package_code(name, &packages[name], &sources, &modules)?;
+        code.push_str(&indent(&package, open.len() - 1));
+    }
+    while open.pop().is_some() {
+        code.push_str(&indent("}\n", open.len()));
+    }
+    Ok(code)
+}
+
+/// Generate the code for all packages found within the given directories,
+/// and write it into the output file.
+pub fn generate_to_file<P, Q>(paths: &[P], output: Q) -> Result<()>
+    where P: AsRef<Path>,
+          Q: AsRef<Path>
+{
+    let code = generate(paths)?;
+    fs::write(output, code)?;
+    Ok(())
+}
+
+#[derive(Default)]
+struct Package {
+    /// Modules containing the package's module.
+    path: Vec<String>,
+    messages: BTreeMap<String, Msg>,
+    services: BTreeMap<String, (Msg, Msg)>,
+}
+
+fn load_directory(root: &Path,
+                  path: &Path,
+                  packages: &mut BTreeMap<String, Package>)
+                  -> Result<()> {
+    let mut entries = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<::std::io::Result<Vec<_>>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            load_directory(root, &entry, packages)?;
+            continue;
+        }
+        let extension = entry.extension().and_then(|v| v.to_str());
+        if extension != Some("msg") && extension != Some("srv") {
+            continue;
+        }
+        let name = match entry.file_stem().and_then(|v| v.to_str()) {
+            Some(name) => name,
+            None => continue,
+        };
+        let (path, package_name) = package_path(root, &entry)?;
+        let source = fs::read_to_string(&entry)?;
+        let package = packages.entry(package_name.clone()).or_insert_with(|| {
+            Package {
+                path: path.clone(),
+                ..Package::default()
+            }
+        });
+        if package.path != path {
+            bail!(ErrorKind::BadMessageDefinition(format!("package {} is placed in both {:?} \
+                                                           and {:?}",
+                                                          package_name,
+                                                          package.path.join("/"),
+                                                          path.join("/"))));
+        }
+        if package.messages.contains_key(name) || package.services.contains_key(name) {
+            bail!(ErrorKind::BadMessageDefinition(format!("{}/{} is defined twice",
+                                                          package_name,
+                                                          name)));
+        }
+        if extension == Some("msg") {
+            let msg = Msg::new(&format!("{}/{}", package_name, name), &source)?;
+            package.messages.insert(name.into(), msg);
+        } else {
+            let (request, response) = split_service(&source);
+            let request = Msg::new(&format!("{}/{}Request", package_name, name), &request)?;
+            let response = Msg::new(&format!("{}/{}Response", package_name, name), &response)?;
+            package.services.insert(name.into(), (request, response));
+        }
+    }
+    Ok(())
+}
+
+/// Find the package containing the file, along with the modules the
+/// package is nested in.
+fn package_path(root: &Path, file: &Path) -> Result<(Vec<String>, String)> {
+    let mut directory = file.parent();
+    let name = directory.and_then(|v| v.file_name()).and_then(|v| v.to_str());
+    if name == Some("msg") || name == Some("srv") {
+        directory = directory.and_then(|v| v.parent());
+    }
+    let name = match directory.and_then(|v| v.file_name()).and_then(|v| v.to_str()) {
+        Some(name) => name,
+        None => {
+            bail!(ErrorKind::BadMessageDefinition(format!("no package contains {}",
+                                                          file.display())))
+        }
+    };
+    let mut path = Vec::new();
+    let nesting = directory.and_then(|v| v.strip_prefix(root).ok()).and_then(|v| v.parent());
+    for component in nesting.into_iter().flat_map(|v| v.iter()) {
+        path.push(component.to_string_lossy().into_owned());
+    }
+    for module in path.iter().map(|v| v.as_str()).chain(Some(name)) {
+        let valid = module.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') &&
+                    module.starts_with(|c: char| c.is_ascii_alphabetic());
+        if !valid {
+            bail!(ErrorKind::BadMessageDefinition(format!("{:?} is not a valid module name \
+                                                           for {}",
+                                                          module,
+                                                          file.display())));
+        }
+    }
+    Ok((path, name.into()))
+}
+
+/// Indent all lines of the code by the given number of levels.
+fn indent(code: &str, depth: usize) -> String {
+    let prefix = "    ".repeat(depth);
+    code.split('\n')
+        .map(|line| if line.is_empty() {
+                 String::new()
+             } else {
+                 format!("{}{}", prefix, line)
+             })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Split the source of a service into the request and response definitions.
+pub(crate) fn split_service(source: &str) -> (String, String) {
+    let lines = source.split('\n').collect::<Vec<_>>();
+    match lines.iter().position(|line| line.trim_start().starts_with("---")) {
+        Some(index) => (lines[..index].join("\n"), lines[index + 1..].join("\n")),
+        None => (source.into(), String::new()),
+    }
+}
+
+fn load_dependencies(packages: &mut BTreeMap<String, Package>) -> Result<()> {
+    let mut pending = Vec::new();
+    for package in packages.values() {
+        let services = package.services.values().flat_map(|(req, res)| vec![req, res]);
+        for msg in package.messages.values().chain(services) {
+            pending.extend(msg.dependencies().into_iter().map(String::from));
+        }
+    }
+    while let Some(full_name) = pending.pop() {
+        let msg = {
+            let (package_name, name) = split_name(&full_name);
+            let known = packages.get(package_name).map(|v| v.messages.contains_key(name));
+            if known == Some(true) {
+                continue;
+            }
+            match bundled_definition(&full_name) {
+                Some(source) => Msg::new(&full_name, source)?,
+                None => bail!(ErrorKind::UnknownMessageType(full_name)),
+            }
+        };
+        pending.extend(msg.dependencies().into_iter().map(String::from));
+        packages.entry(msg.package.clone()).or_default().messages.insert(msg.name.clone(), msg);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "catalog")]
+fn bundled_definition(name: &str) -> Option<&'static str> {
+    super::catalog::definition(name)
+}
+
+#[cfg(not(feature = "catalog"))]
+fn bundled_definition(_name: &str) -> Option<&'static str> {
+    None
+}
+
+fn split_name(full_name: &str) -> (&str, &str) {
+    let index = full_name.find('/').unwrap_or(0);
+    (&full_name[..index], &full_name[index + 1..])
+}
+
+/// Generate the contents of a package's module.
+fn package_code(name: &str,
+                package: &Package,
+                sources: &HashMap<String, String>,
+                modules: &HashMap<String, Vec<String>>)
+                -> Result<String> {
+    let schema = |name: &str| Schema::build(name, |name| sources.get(name).cloned());
+    let mut code = String::new();
+    for msg in package.messages.values() {
+        code.push_str(&message_code(msg, &schema(&msg.full_name())?, modules)?);
+    }
+    for (service, (request, response)) in &package.services {
+        let request_schema = schema(&request.full_name())?;
+        let response_schema = schema(&response.full_name())?;
+        code.push_str(&format!("
+    /// Generated from `{package}/{service}`.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct {service};
+
+    impl ::serde_rosmsg::RosService for {service} {{
+        const ROS_TYPE: &'static str = \"{package}/{service}\";
+        const MD5SUM: &'static str = \"{md5sum}\";
+        type Request = {request};
+        type Response = {response};
+    }}
+",
+                               package = name,
+                               service = service,
+                               md5sum = schema::service_md5sum(&request_schema,
+                                                               &response_schema)?,
+                               request = request.name,
+                               response = response.name));
+        code.push_str(&message_code(request, &request_schema, modules)?);
+        code.push_str(&message_code(response, &response_schema, modules)?);
+    }
+    Ok(code)
+}
+
+fn message_code(msg: &Msg,
+                schema: &Schema,
+                modules: &HashMap<String, Vec<String>>)
+                -> Result<String> {
+    let mut code = String::new();
+    let derives_default = msg.fields.iter().all(|field| !is_big_array(&field.field_type));
+    code.push_str(&format!("\n    /// Generated from `{}`.\n", msg.full_name()));
+    code.push_str(&format!("    #[derive(Clone, Debug, {}PartialEq, Serialize, Deserialize)]\n",
+                           if derives_default { "Default, " } else { "" }));
+    if msg.fields.is_empty() {
+        code.push_str(&format!("    pub struct {} {{}}\n", msg.name));
+    } else {
+        code.push_str(&format!("    pub struct {} {{\n", msg.name));
+        for field in &msg.fields {
+            code.push_str(&field_code(field, &msg.package, modules));
+        }
+        code.push_str("    }\n");
+    }
+    if !msg.constants.is_empty() {
+        code.push_str(&format!("\n    impl {} {{\n", msg.name));
+        for constant in &msg.constants {
+            code.push_str(&format!("        pub const {}: {} = {};\n",
+                                   constant.name,
+                                   constant_type(&constant.base_type),
+                                   constant_value(constant)?));
+        }
+        code.push_str("    }\n");
+    }
+    code.push_str(&format!("\n    impl ::serde_rosmsg::RosMessage for {} {{\n", msg.name));
+    code.push_str(&format!("        const ROS_TYPE: &'static str = {:?};\n", msg.full_name()));
+    code.push_str(&format!("        const MD5SUM: &'static str = {:?};\n", schema.md5sum()?));
+    code.push_str(&format!("        const DEFINITION: &'static str = {:?};\n",
+                           schema.message_definition()?));
+    code.push_str("    }\n");
+    if !derives_default {
+        code.push_str(&format!("\n    impl ::std::default::Default for {} {{\n", msg.name));
+        code.push_str(&format!("        fn default() -> {} {{\n", msg.name));
+        code.push_str(&format!("            {} {{\n", msg.name));
+        for field in &msg.fields {
+            let value = if is_big_array(&field.field_type) {
+                "::std::array::from_fn(|_| ::std::default::Default::default())"
+            } else {
+                "::std::default::Default::default()"
+            };
+            code.push_str(&format!("                {}: {},\n", field_name(&field.name), value));
+        }
+        code.push_str("            }\n        }\n    }\n");
+    }
+    Ok(code)
+}
+
+fn field_code(field: &Field, package: &str, modules: &HashMap<String, Vec<String>>) -> String {
+    let mut code = String::new();
+    let name = field_name(&field.name);
+    if name != field.name {
+        code.push_str(&format!("        #[serde(rename = \"{}\")]\n", field.name));
+    }
+    match field.field_type {
+        FieldType::Array(BaseType::UInt8) |
+        FieldType::Array(BaseType::Char) => {
+            code.push_str("        #[serde(with = \"::serde_rosmsg::types::bytes\")]\n");
+        }
+        ref field_type if is_big_array(field_type) => {
+            code.push_str("        #[serde(with = \"::serde_rosmsg::types::big_array\")]\n");
+        }
+        _ => {}
+    }
+    let rust_type = match field.field_type {
+        FieldType::Single(ref base) => base_type(base, package, modules),
+        FieldType::Array(ref base) => {
+            format!("::std::vec::Vec<{}>", base_type(base, package, modules))
+        }
+        FieldType::FixedArray(ref base, size) => {
+            format!("[{}; {}]", base_type(base, package, modules), size)
+        }
+    };
+    code.push_str(&format!("        pub {}: {},\n", name, rust_type));
+    code
+}
+
+fn is_big_array(field_type: &FieldType) -> bool {
+    match *field_type {
+        FieldType::FixedArray(_, size) => size > 32,
+        _ => false,
+    }
+}
+
+fn base_type(base: &BaseType, package: &str, modules: &HashMap<String, Vec<String>>) -> String {
+    match *base {
+        BaseType::String => "::std::string::String".into(),
+        BaseType::Time => "::serde_rosmsg::types::Time".into(),
+        BaseType::Duration => "::serde_rosmsg::types::Duration".into(),
+        BaseType::Message(ref full_name) => {
+            let (message_package, name) = split_name(full_name);
+            if message_package == package {
+                name.into()
+            } else {
+                format!("{}{}::{}",
+                        "super::".repeat(modules[package].len()),
+                        modules[message_package].join("::"),
+                        name)
+            }
+        }
+        ref base => constant_type(base).into(),
+    }
+}
+
+fn constant_type(base: &BaseType) -> &'static str {
+    match *base {
+        BaseType::Bool => "bool",
+        BaseType::Int8 | BaseType::Byte => "i8",
+        BaseType::UInt8 | BaseType::Char => "u8",
+        BaseType::Int16 => "i16",
+        BaseType::UInt16 => "u16",
+        BaseType::Int32 => "i32",
+        BaseType::UInt32 => "u32",
+        BaseType::Int64 => "i64",
+        BaseType::UInt64 => "u64",
+        BaseType::Float32 => "f32",
+        BaseType::Float64 => "f64",
+        BaseType::String => "&'static str",
+        BaseType::Time | BaseType::Duration | BaseType::Message(..) => "()",
+    }
+}
+
+fn constant_value(constant: &Constant) -> Result<String> {
+    let value = constant.value.as_str();
+    let bad_value = || {
+        ErrorKind::BadMessageDefinition(format!("bad value of constant {}: {:?}",
+                                                constant.name,
+                                                value))
+    };
+    Ok(match constant.base_type {
+           BaseType::Bool => {
+               match value {
+                   "True" | "true" | "1" => "true".into(),
+                   "False" | "false" | "0" => "false".into(),
+                   _ => bail!(bad_value()),
+               }
+           }
+           BaseType::Float32 | BaseType::Float64 => {
+               match value.parse::<f64>() {
+                   Ok(value) if value.is_finite() => format!("{:?}", value),
+                   _ => bail!(bad_value()),
+               }
+           }
+           BaseType::String => format!("{:?}", value),
+           ref base => {
+               let (min, max) = base.integer_range().ok_or_else(bad_value)?;
+               match value.parse::<i128>() {
+                   Ok(value) if value >= min && value <= max => value.to_string(),
+                   _ => bail!(bad_value()),
+               }
+           }
+       })
+}
+
+fn field_name(name: &str) -> String {
+    const KEYWORDS: &[&str] = &["abstract", "as", "async", "await", "become", "box", "break",
+                                "const", "continue", "crate", "do", "dyn", "else", "enum",
+                                "extern", "false", "final", "fn", "for", "if", "impl", "in",
+                                "let", "loop", "macro", "match", "mod", "move", "mut",
+                                "override", "priv", "pub", "ref", "return", "self", "static",
+                                "struct", "super", "trait", "true", "try", "type", "typeof",
+                                "unsafe", "unsized", "use", "virtual", "where", "while",
+                                "yield"];
+    if KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{from_slice, to_vec, RosMessage, RosService};
+    use std::env;
+    use std::path::PathBuf;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Code generated from `FIXTURE_FILES`.
+    mod generated {
+        include!("datatests/codegen_generated.rs");
+    }
+
+    const FIXTURE_FILES: &[(&str, &str)] =
+        &[("my_msgs/msg/Point.msg", "float64 x\nfloat64 y\nfloat64 z\n"),
+          ("my_msgs/msg/Shape.msg",
+           "uint8 TRIANGLE=3\nstring NAME=shape\nstring name\nPoint[] points\n\
+            uint8[] data\nfloat64[36] covariance\nrobots/Joint[2] joints\n"),
+          ("my_msgs/srv/AddTwoInts.srv", "int64 a\nint64 b\n---\nint64 sum\n"),
+          ("nested/robots/msg/Joint.msg",
+           "time stamp\nduration type\nmy_msgs/Point position\n")];
+
+    static PACKAGES: AtomicUsize = AtomicUsize::new(0);
+
+    /// Temporary directory with packages, removed once dropped.
+    struct Packages(PathBuf);
+
+    impl AsRef<Path> for Packages {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for Packages {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn create_packages(files: &[(&str, &str)]) -> Packages {
+        let root = env::temp_dir().join(format!("serde_rosmsg_codegen_{}_{}",
+                                                process::id(),
+                                                PACKAGES.fetch_add(1, Ordering::SeqCst)));
+        let packages = Packages(root);
+        for &(path, source) in files {
+            let path = packages.0.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, source).unwrap();
+        }
+        packages
+    }
+
+    #[test]
+    fn generates_messages() {
+        let root = create_packages(&[("my_msgs/msg/Point.msg",
+                                      "uint8 ORIGIN=0 # Comment\nfloat64 x\nfloat64 y\n"),
+                                     ("my_msgs/msg/Path.msg",
+                                      "string NAME=path # Not a comment\n\
+                                       string name\nPoint[] points\nuint8[] data\nfloat64[36] covariance\n\
+                                       other_msgs/Info[2] info\n"),
+                                     ("other_msgs/Info.msg", "time stamp\nduration type\n")]);
+        let code = generate(&[root]).unwrap();
+        assert!(code.contains("\npub mod my_msgs {\n"));
+        assert!(code.contains("\npub mod other_msgs {\n"));
+        assert!(code.contains("
+    /// Generated from `my_msgs/Point`.
+    #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+    pub struct Point {
+        pub x: f64,
+        pub y: f64,
+    }
+
+    impl Point {
+        pub const ORIGIN: u8 = 0;
+    }
+
+    impl ::serde_rosmsg::RosMessage for Point {
+        const ROS_TYPE: &'static str = \"my_msgs/Point\";
+        const MD5SUM: &'static str = \"0f0ab690180e630d6e70d345cac9bfcc\";
+        const DEFINITION: &'static str = \"uint8 ORIGIN=0 # Comment\\nfloat64 x\\nfloat64 y\\n\";
+    }
+"));
+        assert!(code.contains("
+    /// Generated from `my_msgs/Path`.
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Path {
+        pub name: ::std::string::String,
+        pub points: ::std::vec::Vec<Point>,
+        #[serde(with = \"::serde_rosmsg::types::bytes\")]
+        pub data: ::std::vec::Vec<u8>,
+        #[serde(with = \"::serde_rosmsg::types::big_array\")]
+        pub covariance: [f64; 36],
+        pub info: [super::other_msgs::Info; 2],
+    }
+
+    impl Path {
+        pub const NAME: &'static str = \"path # Not a comment\";
+    }
+"));
+        assert!(code.contains("
+    impl ::std::default::Default for Path {
+        fn default() -> Path {
+            Path {
+                name: ::std::default::Default::default(),
+                points: ::std::default::Default::default(),
+                data: ::std::default::Default::default(),
+                covariance: ::std::array::from_fn(|_| ::std::default::Default::default()),
+                info: ::std::default::Default::default(),
+            }
+        }
+    }
+"));
+        assert!(code.contains("
+    /// Generated from `other_msgs/Info`.
+    #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+    pub struct Info {
+        pub stamp: ::serde_rosmsg::types::Time,
+        #[serde(rename = \"type\")]
+        pub type_: ::serde_rosmsg::types::Duration,
+    }
+"));
+    }
+
+    #[test]
+    fn generates_services() {
+        let root = create_packages(&[("my_msgs/srv/AddTwoInts.srv",
+                                      "int64 a\nint64 b\n---\nint64 sum\n"),
+                                     ("my_msgs/srv/Trigger.srv", "---\nbool success\n")]);
+        let code = generate(&[root]).unwrap();
+        assert!(code.contains("
+    /// Generated from `my_msgs/AddTwoIntsRequest`.
+    #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+    pub struct AddTwoIntsRequest {
+        pub a: i64,
+        pub b: i64,
+    }
+"));
+        assert!(code.contains("pub struct AddTwoIntsResponse {\n        pub sum: i64,\n"));
+        assert!(code.contains("
+    /// Generated from `my_msgs/AddTwoInts`.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct AddTwoInts;
+
+    impl ::serde_rosmsg::RosService for AddTwoInts {
+        const ROS_TYPE: &'static str = \"my_msgs/AddTwoInts\";
+        const MD5SUM: &'static str = \"6a2e34150c00229791cc89ff309fff21\";
+        type Request = AddTwoIntsRequest;
+        type Response = AddTwoIntsResponse;
+    }
+"));
+        assert!(code.contains("pub struct TriggerRequest {}\n"));
+        assert!(code.contains("pub struct TriggerResponse {\n        pub success: bool,\n"));
+    }
+
+    #[test]
+    fn reports_missing_dependencies() {
+        let root = create_packages(&[("my_msgs/msg/A.msg", "other_msgs/B b\n")]);
+        assert!(generate(&[root]).is_err());
+    }
+
+    #[test]
+    fn reports_bad_constants() {
+        let root = create_packages(&[("my_msgs/msg/A.msg", "uint8 A=256\n")]);
+        assert!(generate(&[root]).is_err());
+        let root = create_packages(&[("my_msgs/msg/A.msg", "bool A=yes\n")]);
+        assert!(generate(&[root]).is_err());
+    }
+
+    #[cfg(feature = "catalog")]
+    #[test]
+    fn generates_dependencies_from_catalog() {
+        let root = create_packages(&[("my_msgs/msg/Stamped.msg", "Header header\nint32 data\n")]);
+        let code = generate(&[root]).unwrap();
+        assert!(code.contains("pub header: super::std_msgs::Header,\n"));
+        assert!(code.contains("\npub mod std_msgs {\n"));
+        assert!(code.contains("pub struct Header {\n"));
+    }
+
+    #[test]
+    fn reports_duplicate_definitions() {
+        let first = create_packages(&[("my_msgs/msg/A.msg", "int32 a\n")]);
+        let second = create_packages(&[("my_msgs/msg/A.msg", "int64 a\n")]);
+        assert!(generate(&[&first, &second]).is_err());
+        let root = create_packages(&[("my_msgs/msg/A.msg", "int32 a\n"),
+                                     ("my_msgs/srv/A.srv", "---\n")]);
+        assert!(generate(&[root]).is_err());
+        let root = create_packages(&[("my_msgs/msg/ARequest.msg", "int32 a\n"),
+                                     ("my_msgs/srv/A.srv", "---\n")]);
+        assert!(generate(&[root]).is_err());
+        let root = create_packages(&[("a/my_msgs/msg/A.msg", "int32 a\n"),
+                                     ("b/my_msgs/msg/B.msg", "int32 b\n")]);
+        assert!(generate(&[root]).is_err());
+        let root = create_packages(&[("my-dir/my_msgs/msg/A.msg", "int32 a\n")]);
+        assert!(generate(&[root]).is_err());
+    }
+
+    #[test]
+    fn nests_packages() {
+        let code = include_str!("datatests/codegen_generated.rs");
+        assert!(code.contains("\npub mod nested {\n"));
+        assert!(code.contains("\n    pub mod robots {\n"));
+        assert!(code.contains("pub joints: [super::nested::robots::Joint; 2],\n"));
+        assert!(code.contains("pub position: super::super::my_msgs::Point,\n"));
+    }
+
+    #[test]
+    fn matches_generated_fixture() {
+        let root = create_packages(FIXTURE_FILES);
+        assert!(generate(&[root]).unwrap() == include_str!("datatests/codegen_generated.rs"),
+                "src/datatests/codegen_generated.rs needs to be regenerated");
+    }
+
+    #[test]
+    fn round_trips_generated_messages() {
+        use self::generated::my_msgs::{AddTwoInts, AddTwoIntsRequest, Point, Shape};
+        use self::generated::nested::robots::Joint;
+        use super::super::types::{Duration, Time};
+
+        assert_eq!(Point::MD5SUM, "4a842b65f413084dc2b10fb484ea7f17");
+        assert_eq!(AddTwoInts::MD5SUM, "6a2e34150c00229791cc89ff309fff21");
+        assert_eq!(Shape::TRIANGLE, 3);
+        assert_eq!(Shape::NAME, "shape");
+
+        let point = Point {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        };
+        assert_eq!(to_vec(&point).unwrap(), to_vec(&(1.0f64, 2.0f64, 3.0f64)).unwrap());
+        let request = AddTwoIntsRequest { a: 5, b: -7 };
+        assert_eq!(to_vec(&request).unwrap(), to_vec(&(5i64, -7i64)).unwrap());
+
+        let joint = Joint {
+            stamp: Time { sec: 1, nsec: 2 },
+            type_: Duration { sec: -3, nsec: 4 },
+            position: point.clone(),
+        };
+        let mut shape = Shape {
+            name: "square".into(),
+            points: vec![point.clone(), Point::default()],
+            data: vec![1, 2, 3],
+            joints: [joint.clone(), Joint::default()],
+            ..Shape::default()
+        };
+        shape.covariance[35] = 0.5;
+        let data = to_vec(&shape).unwrap();
+        assert_eq!(data.len(), 4 + 10 + 4 + 48 + 4 + 3 + 36 * 8 + 2 * 40);
+        assert_eq!(from_slice::<Shape>(&data).unwrap(), shape);
+        let joint_data = to_vec(&joint).unwrap();
+        assert_eq!(&data[data.len() - 80..data.len() - 40], &joint_data[4..]);
+    }
+}
@@ -0,0 +1,468 @@
+//! Check whether changes to message definitions keep them compatible.
+//!
+//! ROSMSG data carries no field names or types, so whether data written
+//! with one version of a message can be read with another depends only on
+//! their wire layouts. Comparing two schemas walks both layouts side by
+//! side, recursing into nested messages, and reports every change together
+//! with the path of the field it affects.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use serde_rosmsg::compat::{compare, Compatibility};
+//! # use serde_rosmsg::schema::Schema;
+//! let old = Schema::from_definition("my_msgs/Reading",
+//!                                   "string sensor\nfloat32 value\n").unwrap();
+//!
+//! let new = Schema::from_definition("my_msgs/Reading",
+//!                                   "string source\nfloat32 value\n").unwrap();
+//! let report = compare(&old, &new).unwrap();
+//! assert_eq!(report.compatibility(), Compatibility::Renamed);
+//! assert_eq!(report.to_string(), "source: field renamed from sensor\n");
+//!
+//! let new = Schema::from_definition("my_msgs/Reading",
+//!                                   "string sensor\nfloat64 value\n").unwrap();
+//! let report = compare(&old, &new).unwrap();
+//! assert_eq!(report.compatibility(), Compatibility::Widened);
+//! assert!(!report.is_wire_compatible());
+//! ```
+
+use super::error::Result;
+use super::schema::{BaseType, Constant, FieldType, Schema};
+use std::fmt;
+
+/// How well data of the old message fits the new one.
+///
+/// Levels are ordered from the most to the least compatible.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Compatibility {
+    /// Nothing changed.
+    Identical,
+    /// The wire layout and field names are the same, but the MD5 sum or the
+    /// type names changed, like with new constants or aliased types.
+    Compatible,
+    /// The wire layout is the same, but some fields got renamed.
+    Renamed,
+    /// Some values need converting into types that represent all of their
+    /// old values, like `int32` into `int64`.
+    Widened,
+    /// The data cannot be read using the new message.
+    Incompatible,
+}
+
+impl fmt::Display for Compatibility {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+                        Compatibility::Identical => "identical",
+                        Compatibility::Compatible => "compatible",
+                        Compatibility::Renamed => "renamed",
+                        Compatibility::Widened => "widened",
+                        Compatibility::Incompatible => "incompatible",
+                    })
+    }
+}
+
+/// A single difference between two messages.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change {
+    /// Path of the affected field or constant, like `poses[].position.x`,
+    /// following the new message. Removed fields follow the old one, and
+    /// changes to the root message itself have an empty path.
+    pub path: String,
+    /// What changed.
+    pub kind: ChangeKind,
+}
+
+impl Change {
+    /// How compatible the change is.
+    pub fn compatibility(&self) -> Compatibility {
+        self.kind.compatibility()
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.kind)
+        } else {
+            write!(f, "{}: {}", self.path, self.kind)
+        }
+    }
+}
+
+/// The kinds of differences between two messages.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// The field had the given name before.
+    RenamedField(String),
+    /// The message type had the given name before.
+    RenamedType(String),
+    /// The type got replaced with its alias, like `byte` with `int8`.
+    AliasedType(FieldType, FieldType),
+    /// A constant got added, removed or given a different value.
+    ChangedConstant(Option<String>, Option<String>),
+    /// The type got replaced with one holding all of its values.
+    WidenedType(FieldType, FieldType),
+    /// The type got replaced with an unrelated one.
+    ChangedType(FieldType, FieldType),
+    /// A field of the given type got added.
+    AddedField(FieldType),
+    /// A field of the given type got removed.
+    RemovedField(FieldType),
+}
+
+impl ChangeKind {
+    /// How compatible the change is.
+    pub fn compatibility(&self) -> Compatibility {
+        match *self {
+            ChangeKind::RenamedType(..) |
+            ChangeKind::AliasedType(..) |
+            ChangeKind::ChangedConstant(..) => Compatibility::Compatible,
+            ChangeKind::RenamedField(..) => Compatibility::Renamed,
+            ChangeKind::WidenedType(..) => Compatibility::Widened,
+            ChangeKind::ChangedType(..) |
+            ChangeKind::AddedField(..) |
+            ChangeKind::RemovedField(..) => Compatibility::Incompatible,
+        }
+    }
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ChangeKind::RenamedField(ref old) => write!(f, "field renamed from {}", old),
+            ChangeKind::RenamedType(ref old) => write!(f, "type renamed from {}", old),
+            ChangeKind::AliasedType(ref old, ref new) => {
+                write!(f, "type changed from {} to its alias {}", old, new)
+            }
+            ChangeKind::ChangedConstant(None, Some(ref new)) => {
+                write!(f, "constant added with value {}", new)
+            }
+            ChangeKind::ChangedConstant(Some(ref old), None) => {
+                write!(f, "constant with value {} removed", old)
+            }
+            ChangeKind::ChangedConstant(ref old, ref new) => {
+                write!(f,
+                       "constant changed from {} to {}",
+                       old.as_ref().map(String::as_str).unwrap_or_default(),
+                       new.as_ref().map(String::as_str).unwrap_or_default())
+            }
+            ChangeKind::WidenedType(ref old, ref new) => {
+                write!(f, "type widened from {} to {}", old, new)
+            }
+            ChangeKind::ChangedType(ref old, ref new) => {
+                write!(f, "type changed from {} to {}", old, new)
+            }
+            ChangeKind::AddedField(ref new) => write!(f, "field of type {} added", new),
+            ChangeKind::RemovedField(ref old) => write!(f, "field of type {} removed", old),
+        }
+    }
+}
+
+/// Differences between two versions of a message, as found by `compare`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    /// MD5 sum of the old message.
+    pub old_md5sum: String,
+    /// MD5 sum of the new message.
+    pub new_md5sum: String,
+    /// All changes, in the order of the fields they affect.
+    pub changes: Vec<Change>,
+}
+
+impl Report {
+    /// How compatible the least compatible change is.
+    pub fn compatibility(&self) -> Compatibility {
+        self.changes
+            .iter()
+            .map(Change::compatibility)
+            .max()
+            .unwrap_or(Compatibility::Identical)
+    }
+
+    /// Whether data of the old message can be read as is using the new one.
+    pub fn is_wire_compatible(&self) -> bool {
+        self.compatibility() <= Compatibility::Renamed
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare two versions of a message.
+///
+/// Fields are matched by their position, since that is how they are laid
+/// out on the wire. Fails only if either schema is missing a message.
+pub fn compare(old: &Schema, new: &Schema) -> Result<Report> {
+    let mut comparison = Comparison {
+        old,
+        new,
+        changes: Vec::new(),
+        stack: Vec::new(),
+    };
+    comparison.message(old.name(), new.name(), "")?;
+    Ok(Report {
+           old_md5sum: old.md5sum()?,
+           new_md5sum: new.md5sum()?,
+           changes: comparison.changes,
+       })
+}
+
+struct Comparison<'a> {
+    old: &'a Schema,
+    new: &'a Schema,
+    changes: Vec<Change>,
+    stack: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Comparison<'a> {
+    fn change(&mut self, path: &str, kind: ChangeKind) {
+        self.changes.push(Change {
+                              path: path.into(),
+                              kind,
+                          });
+    }
+
+    fn message(&mut self, old_name: &'a str, new_name: &'a str, path: &str) -> Result<()> {
+        // Definitions cannot really be recursive, but the text could claim so
+        if self.stack.contains(&(old_name, new_name)) {
+            return Ok(());
+        }
+        let old = self.old.message(old_name)?;
+        let new = self.new.message(new_name)?;
+        if old_name != new_name {
+            self.change(path, ChangeKind::RenamedType(old_name.into()));
+        }
+        self.constants(&old.constants, &new.constants, path);
+        self.stack.push((old_name, new_name));
+        for (old_field, new_field) in old.fields.iter().zip(&new.fields) {
+            let field_path = child(path, &new_field.name);
+            if old_field.name != new_field.name {
+                self.change(&field_path, ChangeKind::RenamedField(old_field.name.clone()));
+            }
+            self.field(&old_field.field_type, &new_field.field_type, &field_path)?;
+        }
+        for field in old.fields.iter().skip(new.fields.len()) {
+            let kind = ChangeKind::RemovedField(field.field_type.clone());
+            self.change(&child(path, &field.name), kind);
+        }
+        for field in new.fields.iter().skip(old.fields.len()) {
+            let kind = ChangeKind::AddedField(field.field_type.clone());
+            self.change(&child(path, &field.name), kind);
+        }
+        self.stack.pop();
+        Ok(())
+    }
+
+    fn constants(&mut self, old: &[Constant], new: &[Constant], path: &str) {
+        let find = |constants: &[Constant], name: &str| -> Option<(BaseType, String)> {
+            constants
+                .iter()
+                .find(|constant| constant.name == name)
+                .map(|constant| (constant.base_type.clone(), constant.value.clone()))
+        };
+        let names = old.iter()
+            .chain(new.iter().filter(|constant| find(old, &constant.name).is_none()))
+            .map(|constant| constant.name.as_str());
+        for name in names {
+            let (old, new) = (find(old, name), find(new, name));
+            if old != new {
+                let kind = ChangeKind::ChangedConstant(old.map(|v| v.1), new.map(|v| v.1));
+                self.change(&child(path, name), kind);
+            }
+        }
+    }
+
+    fn field(&mut self, old: &'a FieldType, new: &'a FieldType, path: &str) -> Result<()> {
+        let same_shape = match (old, new) {
+            (FieldType::Single(..), FieldType::Single(..)) |
+            (FieldType::Array(..), FieldType::Array(..)) => true,
+            (FieldType::FixedArray(_, old), FieldType::FixedArray(_, new)) => old == new,
+            _ => false,
+        };
+        let (old_base, new_base) = (old.base(), new.base());
+        if !same_shape {
+            self.change(path, ChangeKind::ChangedType(old.clone(), new.clone()));
+            return Ok(());
+        }
+        let path = match *new {
+            FieldType::Single(..) => path.to_owned(),
+            _ => format!("{}[]", path),
+        };
+        let kind = match (old_base, new_base) {
+            (BaseType::Message(old), BaseType::Message(new)) => {
+                return self.message(old, new, &path);
+            }
+            _ if old_base == new_base => return Ok(()),
+            _ if is_alias(old_base, new_base) => {
+                ChangeKind::AliasedType(old.clone(), new.clone())
+            }
+            _ if is_widening(old_base, new_base) => {
+                ChangeKind::WidenedType(old.clone(), new.clone())
+            }
+            _ => ChangeKind::ChangedType(old.clone(), new.clone()),
+        };
+        // Type changes apply to the whole field, rather than its items
+        let path = path.trim_end_matches("[]");
+        self.change(path, kind);
+        Ok(())
+    }
+}
+
+fn child(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.into()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+fn is_alias(old: &BaseType, new: &BaseType) -> bool {
+    matches!((old, new),
+             (BaseType::Byte, BaseType::Int8) |
+             (BaseType::Int8, BaseType::Byte) |
+             (BaseType::Char, BaseType::UInt8) |
+             (BaseType::UInt8, BaseType::Char))
+}
+
+/// Whether every value of the old type can be represented by the new one.
+fn is_widening(old: &BaseType, new: &BaseType) -> bool {
+    let (old_min, old_max) = match (old, old.integer_range()) {
+        (BaseType::Float32, _) => return *new == BaseType::Float64,
+        (_, Some(range)) => range,
+        _ => return false,
+    };
+    // Floats represent integers exactly up to the size of their mantissa
+    let (new_min, new_max) = match (new, new.integer_range()) {
+        (BaseType::Float32, _) => (-(1 << 24), 1 << 24),
+        (BaseType::Float64, _) => (-(1 << 53), 1 << 53),
+        (_, Some(range)) => range,
+        _ => return false,
+    };
+    new_min <= old_min && old_max <= new_max
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema(definition: &str) -> Schema {
+        Schema::from_definition("my_msgs/Data", definition).unwrap()
+    }
+
+    fn changes(old: &str, new: &str) -> Vec<String> {
+        let report = compare(&schema(old), &schema(new)).unwrap();
+        report.changes.iter().map(|change| change.to_string()).collect()
+    }
+
+    const POINT: &str = "\n================================================\
+                         ================================\n\
+                         MSG: my_msgs/Point\n";
+
+    #[test]
+    fn finds_identical_messages() {
+        let old = schema("# Comment\nint32 a\nstring b\n");
+        let new = schema("int32   a # Comment\nstring b\n");
+        let report = compare(&old, &new).unwrap();
+        assert_eq!(report.compatibility(), Compatibility::Identical);
+        assert_eq!(report.old_md5sum, report.new_md5sum);
+        assert!(report.changes.is_empty());
+    }
+
+    #[test]
+    fn classifies_compatible_changes() {
+        let old = schema("int32 A=1\nint32 B=2\nbyte a\nchar[] b\n");
+        let new = schema("int32 A=1\nint32 B=3\nint32 C=4\nint8 a\nuint8[] b\n");
+        let report = compare(&old, &new).unwrap();
+        assert_eq!(report.compatibility(), Compatibility::Compatible);
+        assert!(report.is_wire_compatible());
+        assert_ne!(report.old_md5sum, report.new_md5sum);
+        assert_eq!(report.to_string(),
+                   "B: constant changed from 2 to 3\n\
+                    C: constant added with value 4\n\
+                    a: type changed from byte to its alias int8\n\
+                    b: type changed from char[] to its alias uint8[]\n");
+    }
+
+    #[test]
+    fn recurses_into_nested_messages() {
+        let old = format!("Point[] points\nPoint last\n{}int32 x\nint32 y\n", POINT);
+        let new = format!("Point[] points\nPoint last\n{}int32 x\nint64 y\nint8 z\n", POINT);
+        assert_eq!(changes(&old, &new),
+                   vec!["points[].y: type widened from int32 to int64",
+                        "points[].z: field of type int8 added",
+                        "last.y: type widened from int32 to int64",
+                        "last.z: field of type int8 added"]);
+
+        let new = "geometry_msgs/Point32[] points\ngeometry_msgs/Point32 last\n\
+                   ================================================\
+                   ================================\n\
+                   MSG: geometry_msgs/Point32\nfloat32 x\nfloat32 y\nfloat32 z\n";
+        let old = format!("Point[] points\nPoint last\n{}float32 x\nfloat32 y\nfloat32 z\n",
+                          POINT);
+        assert_eq!(changes(&old, new),
+                   vec!["points[]: type renamed from my_msgs/Point",
+                        "last: type renamed from my_msgs/Point"]);
+    }
+
+    #[test]
+    fn classifies_widened_types() {
+        let widened = [("uint8", "int16"),
+                       ("int16", "int32"),
+                       ("uint32", "int64"),
+                       ("uint32", "uint64"),
+                       ("float32", "float64"),
+                       ("int16", "float32"),
+                       ("uint32", "float64")];
+        for &(old, new) in &widened {
+            let report = compare(&schema(&format!("{} a\n", old)),
+                                 &schema(&format!("{} a\n", new)))
+                .unwrap();
+            assert_eq!(report.compatibility(), Compatibility::Widened);
+        }
+        let narrowed = [("int16", "uint16"),
+                        ("int64", "int32"),
+                        ("uint64", "int64"),
+                        ("float64", "float32"),
+                        ("int32", "float32"),
+                        ("int64", "float64"),
+                        ("bool", "uint8"),
+                        ("time", "duration")];
+        for &(old, new) in &narrowed {
+            let report = compare(&schema(&format!("{} a\n", old)),
+                                 &schema(&format!("{} a\n", new)))
+                .unwrap();
+            assert_eq!(report.compatibility(), Compatibility::Incompatible);
+        }
+    }
+
+    #[test]
+    fn classifies_incompatible_changes() {
+        assert_eq!(changes("int32 a\nint32[] b\nint32[2] c\nint32 d\n",
+                           "int32[] a\nint32[2] b\nint32[3] c\n"),
+                   vec!["a: type changed from int32 to int32[]",
+                        "b: type changed from int32[] to int32[2]",
+                        "c: type changed from int32[2] to int32[3]",
+                        "d: field of type int32 removed"]);
+        let report = compare(&schema("int32 a\n"), &schema("int32 b\nstring c\n")).unwrap();
+        assert_eq!(report.compatibility(), Compatibility::Incompatible);
+        assert_eq!(report.to_string(),
+                   "b: field renamed from a\nc: field of type string added\n");
+    }
+
+    #[test]
+    fn reports_renamed_root_types() {
+        let old = schema("int32 a\n");
+        let new = Schema::from_definition("other_msgs/Data", "int32 a\n").unwrap();
+        let report = compare(&old, &new).unwrap();
+        assert_eq!(report.compatibility(), Compatibility::Compatible);
+        assert_eq!(report.old_md5sum, report.new_md5sum);
+        assert_eq!(report.to_string(), "type renamed from my_msgs/Data\n");
+    }
+}
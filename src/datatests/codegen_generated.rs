@@ -0,0 +1,109 @@
+// Generated by serde_rosmsg::codegen, do not edit by hand.
+
+#[allow(dead_code, non_camel_case_types, non_snake_case, non_upper_case_globals)]
+pub mod my_msgs {
+    /// Generated from `my_msgs/Point`.
+    #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+    pub struct Point {
+        pub x: f64,
+        pub y: f64,
+        pub z: f64,
+    }
+
+    impl ::serde_rosmsg::RosMessage for Point {
+        const ROS_TYPE: &'static str = "my_msgs/Point";
+        const MD5SUM: &'static str = "4a842b65f413084dc2b10fb484ea7f17";
+        const DEFINITION: &'static str = "float64 x\nfloat64 y\nfloat64 z\n";
+    }
+
+    /// Generated from `my_msgs/Shape`.
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Shape {
+        pub name: ::std::string::String,
+        pub points: ::std::vec::Vec<Point>,
+        #[serde(with = "::serde_rosmsg::types::bytes")]
+        pub data: ::std::vec::Vec<u8>,
+        #[serde(with = "::serde_rosmsg::types::big_array")]
+        pub covariance: [f64; 36],
+        pub joints: [super::nested::robots::Joint; 2],
+    }
+
+    impl Shape {
+        pub const TRIANGLE: u8 = 3;
+        pub const NAME: &'static str = "shape";
+    }
+
+    impl ::serde_rosmsg::RosMessage for Shape {
+        const ROS_TYPE: &'static str = "my_msgs/Shape";
+        const MD5SUM: &'static str = "c24649f8e4e79ce897b26443d4e26ecc";
+        const DEFINITION: &'static str = "uint8 TRIANGLE=3\nstring NAME=shape\nstring name\nPoint[] points\nuint8[] data\nfloat64[36] covariance\nrobots/Joint[2] joints\n\n================================================================================\nMSG: my_msgs/Point\nfloat64 x\nfloat64 y\nfloat64 z\n\n================================================================================\nMSG: robots/Joint\ntime stamp\nduration type\nmy_msgs/Point position\n";
+    }
+
+    impl ::std::default::Default for Shape {
+        fn default() -> Shape {
+            Shape {
+                name: ::std::default::Default::default(),
+                points: ::std::default::Default::default(),
+                data: ::std::default::Default::default(),
+                covariance: ::std::array::from_fn(|_| ::std::default::Default::default()),
+                joints: ::std::default::Default::default(),
+            }
+        }
+    }
+
+    /// Generated from `my_msgs/AddTwoInts`.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct AddTwoInts;
+
+    impl ::serde_rosmsg::RosService for AddTwoInts {
+        const ROS_TYPE: &'static str = "my_msgs/AddTwoInts";
+        const MD5SUM: &'static str = "6a2e34150c00229791cc89ff309fff21";
+        type Request = AddTwoIntsRequest;
+        type Response = AddTwoIntsResponse;
+    }
+
+    /// Generated from `my_msgs/AddTwoIntsRequest`.
+    #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+    pub struct AddTwoIntsRequest {
+        pub a: i64,
+        pub b: i64,
+    }
+
+    impl ::serde_rosmsg::RosMessage for AddTwoIntsRequest {
+        const ROS_TYPE: &'static str = "my_msgs/AddTwoIntsRequest";
+        const MD5SUM: &'static str = "36d09b846be0b371c5f190354dd3153e";
+        const DEFINITION: &'static str = "int64 a\nint64 b";
+    }
+
+    /// Generated from `my_msgs/AddTwoIntsResponse`.
+    #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+    pub struct AddTwoIntsResponse {
+        pub sum: i64,
+    }
+
+    impl ::serde_rosmsg::RosMessage for AddTwoIntsResponse {
+        const ROS_TYPE: &'static str = "my_msgs/AddTwoIntsResponse";
+        const MD5SUM: &'static str = "b88405221c77b1878a3cbbfff53428d7";
+        const DEFINITION: &'static str = "int64 sum\n";
+    }
+}
+
+pub mod nested {
+    #[allow(dead_code, non_camel_case_types, non_snake_case, non_upper_case_globals)]
+    pub mod robots {
+        /// Generated from `robots/Joint`.
+        #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+        pub struct Joint {
+            pub stamp: ::serde_rosmsg::types::Time,
+            #[serde(rename = "type")]
+            pub type_: ::serde_rosmsg::types::Duration,
+            pub position: super::super::my_msgs::Point,
+        }
+
+        impl ::serde_rosmsg::RosMessage for Joint {
+            const ROS_TYPE: &'static str = "robots/Joint";
+            const MD5SUM: &'static str = "3734037bd536b366df870870d24b7387";
+            const DEFINITION: &'static str = "time stamp\nduration type\nmy_msgs/Point position\n\n================================================================================\nMSG: my_msgs/Point\nfloat64 x\nfloat64 y\nfloat64 z\n";
+        }
+    }
+}
@@ -11,8 +11,13 @@
 //! the data does not contain any type information.
 
 use byteorder::{LittleEndian, ReadBytesExt};
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
 use serde::de;
 use super::error::{Error, ErrorKind, Result, ResultExt};
+use super::raw;
+#[cfg(feature = "bytes")]
+use super::types::shared_bytes;
 use std::io;
 
 /// A structure for deserializing ROSMSG into Rust values.
@@ -24,6 +29,11 @@ use std::io;
 pub struct Deserializer<R> {
     reader: R,
     length: u32,
+    #[cfg(feature = "bytes")]
+    expected_length: u32,
+    /// The whole input, including the length prefix, if it can be shared.
+    #[cfg(feature = "bytes")]
+    shared: Option<Bytes>,
 }
 
 impl<R> Deserializer<R>
@@ -55,6 +65,10 @@ impl<R> Deserializer<R>
         Deserializer {
             reader: reader,
             length: expected_length,
+            #[cfg(feature = "bytes")]
+            expected_length,
+            #[cfg(feature = "bytes")]
+            shared: None,
         }
     }
 
@@ -120,7 +134,7 @@ impl<R> Deserializer<R>
     }
 
     #[inline]
-    fn pop_length(&mut self) -> Result<u32> {
+    pub(crate) fn pop_length(&mut self) -> Result<u32> {
         self.reserve_bytes(4)?;
         self.reader
             .read_u32::<LittleEndian>()
@@ -128,13 +142,30 @@ impl<R> Deserializer<R>
     }
 
     #[inline]
-    fn get_string(&mut self) -> Result<String> {
-        let length = self.pop_length()?;
+    pub(crate) fn get_bytes(&mut self, length: u32) -> Result<Vec<u8>> {
         self.reserve_bytes(length)?;
         let mut buffer = vec![0; length as usize];
         self.reader
             .read_exact(&mut buffer)
             .chain_err(|| ErrorKind::EndOfBuffer)?;
+        Ok(buffer)
+    }
+
+    #[inline]
+    pub(crate) fn skip_bytes(&mut self, length: u32) -> Result<()> {
+        self.reserve_bytes(length)?;
+        let skipped = io::copy(&mut io::Read::take(&mut self.reader, length as u64),
+                               &mut io::sink())?;
+        if skipped < length as u64 {
+            bail!(ErrorKind::EndOfBuffer);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn get_string(&mut self) -> Result<String> {
+        let length = self.pop_length()?;
+        let buffer = self.get_bytes(length)?;
         String::from_utf8(buffer).chain_err(|| ErrorKind::BadStringData)
     }
 }
@@ -270,9 +301,28 @@ impl<'de, 'a, R: io::Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
     }
 
     #[inline]
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
         where V: de::Visitor<'de>
     {
+        if name == raw::TOKEN {
+            let length = self.length;
+            return visitor.visit_byte_buf(self.get_bytes(length)?);
+        }
+        #[cfg(feature = "bytes")]
+        {
+            if name == shared_bytes::TOKEN {
+                let length = self.pop_length()?;
+                let value = match self.shared.clone() {
+                    Some(shared) => {
+                        let start = 4 + (self.expected_length - self.length) as usize;
+                        self.skip_bytes(length)?;
+                        shared.slice(start..start + length as usize)
+                    }
+                    None => Bytes::from(self.get_bytes(length)?),
+                };
+                return shared_bytes::hand_over(value, || visitor.visit_unit());
+            }
+        }
         visitor.visit_newtype_struct(self)
     }
 
@@ -455,6 +505,11 @@ impl<'de, 'a, R: io::Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
     {
         bail!(ErrorKind::UnsupportedDeserializerMethod("deserialize_ignored_any".into()))
     }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        false
+    }
 }
 
 impl de::Error for Error {
@@ -526,6 +581,54 @@ pub fn from_slice<'de, T>(bytes: &[u8]) -> Result<T>
     from_reader(io::Cursor::new(bytes))
 }
 
+/// Deserialize an instance of type `T` from a shared buffer of ROSMSG data.
+///
+/// Requires the `bytes` feature.
+///
+/// Works like [`from_slice`](fn.from_slice.html), except that `Bytes`
+/// fields using [`shared_bytes`](../types/shared_bytes/index.html) are
+/// sliced out of `data`, rather than copied. All decoded messages keep
+/// their part of `data` alive, so it can be shared between them.
+///
+/// # Examples
+///
+/// ```rust
+/// extern crate bytes;
+/// extern crate serde_rosmsg;
+/// #[macro_use]
+/// extern crate serde_derive;
+/// use bytes::Bytes;
+/// use serde_rosmsg::de::from_bytes;
+///
+/// fn main() {
+/// #[derive(Deserialize)]
+/// struct Blob {
+///     name: String,
+///     #[serde(with = "serde_rosmsg::types::shared_bytes")]
+///     data: Bytes,
+/// }
+///
+/// let data = Bytes::from_static(b"\x0f\0\0\0\x01\0\0\0a\x06\0\0\0abcdef");
+/// let blob: Blob = from_bytes(data.clone()).unwrap();
+/// assert_eq!(blob.name, "a");
+/// assert_eq!(blob.data, &b"abcdef"[..]);
+/// assert_eq!(blob.data.as_ptr(), data[13..].as_ptr());
+/// }
+/// ```
+#[cfg(feature = "bytes")]
+pub fn from_bytes<T>(data: Bytes) -> Result<T>
+    where T: de::DeserializeOwned
+{
+    let length = (&data[..]).read_u32::<LittleEndian>()?;
+    let mut deserializer = Deserializer::new(io::Cursor::new(data.slice(4..)), length);
+    deserializer.shared = Some(data);
+    let value = T::deserialize(&mut deserializer)?;
+    if !deserializer.is_fully_read() {
+        bail!(ErrorKind::Underflow);
+    }
+    Ok(value)
+}
+
 /// Deserialize an instance of type `T` from a string of ROSMSG data.
 ///
 /// This conversion can fail if the passed stream of bytes does not match the
@@ -556,75 +659,75 @@ mod tests {
     #[test]
     fn reads_u8() {
         let data = vec![1, 0, 0, 0, 150];
-        assert_eq!(150u8, from_slice(&data).unwrap());
+        assert_eq!(150u8, from_slice::<u8>(&data).unwrap());
     }
 
     #[test]
     fn reads_u16() {
         let data = vec![2, 0, 0, 0, 0x34, 0xA2];
-        assert_eq!(0xA234u16, from_slice(&data).unwrap());
+        assert_eq!(0xA234u16, from_slice::<u16>(&data).unwrap());
     }
 
     #[test]
     fn reads_u32() {
         let data = vec![4, 0, 0, 0, 0x45, 0x23, 1, 0xCD];
-        assert_eq!(0xCD012345u32, from_slice(&data).unwrap());
+        assert_eq!(0xCD012345u32, from_slice::<u32>(&data).unwrap());
     }
 
     #[test]
     fn reads_u64() {
         let data = vec![8, 0, 0, 0, 0xBB, 0xAA, 0x10, 0x32, 0x54, 0x76, 0x98, 0xAB];
-        assert_eq!(0xAB9876543210AABBu64, from_slice(&data).unwrap());
+        assert_eq!(0xAB9876543210AABBu64, from_slice::<u64>(&data).unwrap());
     }
 
     #[test]
     fn reads_i8() {
         let data = vec![1, 0, 0, 0, 156];
-        assert_eq!(-100i8, from_slice(&data).unwrap());
+        assert_eq!(-100i8, from_slice::<i8>(&data).unwrap());
     }
 
     #[test]
     fn reads_i16() {
         let data = vec![2, 0, 0, 0, 0xD0, 0x8A];
-        assert_eq!(-30000i16, from_slice(&data).unwrap());
+        assert_eq!(-30000i16, from_slice::<i16>(&data).unwrap());
     }
 
     #[test]
     fn reads_i32() {
         let data = vec![4, 0, 0, 0, 0x00, 0x6C, 0xCA, 0x88];
-        assert_eq!(-2000000000i32, from_slice(&data).unwrap());
+        assert_eq!(-2000000000i32, from_slice::<i32>(&data).unwrap());
     }
 
     #[test]
     fn reads_i64() {
         let data = vec![8, 0, 0, 0, 0x00, 0x00, 0x7c, 0x1d, 0xaf, 0x93, 0x19, 0x83];
-        assert_eq!(-9000000000000000000i64, from_slice(&data).unwrap());
+        assert_eq!(-9000000000000000000i64, from_slice::<i64>(&data).unwrap());
     }
 
     #[test]
     fn reads_f32() {
         let data = vec![4, 0, 0, 0, 0x00, 0x70, 0x7b, 0x44];
-        assert_eq!(1005.75f32, from_slice(&data).unwrap());
+        assert_eq!(1005.75f32, from_slice::<f32>(&data).unwrap());
     }
 
     #[test]
     fn reads_f64() {
         let data = vec![8, 0, 0, 0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x6e, 0x8f, 0x40];
-        assert_eq!(1005.75f64, from_slice(&data).unwrap());
+        assert_eq!(1005.75f64, from_slice::<f64>(&data).unwrap());
     }
 
     #[test]
     fn reads_bool() {
         let data = vec![1, 0, 0, 0, 1];
-        assert_eq!(true, from_slice(&data).unwrap());
+        assert_eq!(true, from_slice::<bool>(&data).unwrap());
         let data = vec![1, 0, 0, 0, 0];
-        assert_eq!(false, from_slice(&data).unwrap());
+        assert_eq!(false, from_slice::<bool>(&data).unwrap());
     }
 
     #[test]
     fn reads_bool_from_string() {
-        assert_eq!(true, from_str("\x01\0\0\0\x01").unwrap());
-        assert_eq!(false, from_str("\x01\0\0\0\x00").unwrap());
+        assert_eq!(true, from_str::<bool>("\x01\0\0\0\x01").unwrap());
+        assert_eq!(false, from_str::<bool>("\x01\0\0\0\x00").unwrap());
     }
 
     #[test]
@@ -820,4 +923,71 @@ mod tests {
         let data = vec![12, 0, 0, 0, 5, 0, 0, 0, 7, 0, 1, 4, 33, 0, 57, 0];
         from_slice::<Vec<i16>>(&data).unwrap_err();
     }
+
+    #[cfg(feature = "bytes")]
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Blob {
+        name: String,
+        #[serde(with = "::types::shared_bytes")]
+        data: Bytes,
+        plain: Bytes,
+    }
+
+    #[cfg(feature = "bytes")]
+    fn blobs() -> Vec<Blob> {
+        vec![Blob {
+                 name: "first".into(),
+                 data: Bytes::from_static(b"abcdef"),
+                 plain: Bytes::from_static(b"xy"),
+             },
+             Blob {
+                 name: "second".into(),
+                 data: Bytes::new(),
+                 plain: Bytes::new(),
+             }]
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn shares_bytes_fields() {
+        let data = Bytes::from(::to_vec(&blobs()).unwrap());
+        let value: Vec<Blob> = from_bytes(data.clone()).unwrap();
+        assert_eq!(value, blobs());
+        assert_eq!(value[0].data.as_ptr(), data[21..].as_ptr());
+        assert!(value[0].plain.as_ptr() < data.as_ptr() ||
+                value[0].plain.as_ptr() >= data[data.len()..].as_ptr());
+        assert_eq!(from_slice::<Vec<Blob>>(&data).unwrap(), blobs());
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn reads_bytes_fields_from_any_reader() {
+        struct Strict<'a>(&'a [u8]);
+
+        impl<'a> io::Read for Strict<'a> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                assert!(!buf.is_empty(), "Empty reads are not expected");
+                self.0.read(buf)
+            }
+        }
+
+        let data = ::to_vec(&blobs()).unwrap();
+        assert_eq!(from_reader::<_, Vec<Blob>>(Strict(&data)).unwrap(), blobs());
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn fails_on_truncated_bytes() {
+        let data = ::to_vec(&blobs()).unwrap();
+        let mut truncated = data[..24].to_vec();
+        truncated[0] = 20;
+        assert!(from_bytes::<Vec<Blob>>(Bytes::from(truncated.clone())).is_err());
+        assert!(from_slice::<Vec<Blob>>(&truncated).is_err());
+        let mut lying = data.clone();
+        lying[17] = 0xFF;
+        match *from_bytes::<Vec<Blob>>(Bytes::from(lying)).unwrap_err().kind() {
+            ErrorKind::Overflow => {}
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+    }
 }
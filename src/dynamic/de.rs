@@ -0,0 +1,198 @@
+use byteorder::{LittleEndian, ReadBytesExt};
+use serde::de::Deserialize;
+use super::Value;
+use super::super::de::Deserializer;
+use super::super::error::{ErrorKind, Result};
+use super::super::schema::{BaseType, FieldType, Schema};
+use super::super::types::{Duration, Time};
+use std::convert::TryFrom;
+use std::io;
+
+/// Decode bytes of ROSMSG data into a value, following the schema.
+///
+/// Just like with `from_slice`, the data needs to start with the length
+/// of the message.
+pub fn decode(schema: &Schema, bytes: &[u8]) -> Result<Value> {
+    decode_reader(schema, io::Cursor::new(bytes))
+}
+
+/// Decode a message from an IO stream of ROSMSG data, following the schema.
+///
+/// Just like with `from_reader`, the stream needs to start with the length
+/// of the message.
+pub fn decode_reader<R>(schema: &Schema, mut reader: R) -> Result<Value>
+    where R: io::Read
+{
+    let length = reader.read_u32::<LittleEndian>()?;
+    let mut deserializer = Deserializer::new(reader, length);
+    let value = decode_message(schema, schema.name(), &mut deserializer)?;
+    if !deserializer.is_fully_read() {
+        bail!(ErrorKind::Underflow);
+    }
+    Ok(value)
+}
+
+pub(crate) fn decode_message<R>(schema: &Schema,
+                                name: &str,
+                                deserializer: &mut Deserializer<R>)
+                                -> Result<Value>
+    where R: io::Read
+{
+    let msg = schema.message(name)?;
+    let mut fields = Vec::with_capacity(msg.fields.len());
+    for field in &msg.fields {
+        let value = decode_field(schema, &field.field_type, deserializer)?;
+        fields.push((field.name.clone(), value));
+    }
+    Ok(Value::Message(fields))
+}
+
+pub(crate) fn decode_field<R>(schema: &Schema,
+                              field_type: &FieldType,
+                              deserializer: &mut Deserializer<R>)
+                              -> Result<Value>
+    where R: io::Read
+{
+    let (base, length) = match *field_type {
+        FieldType::Single(ref base) => return decode_base(schema, base, deserializer),
+        FieldType::Array(ref base) => (base, deserializer.pop_length()?),
+        FieldType::FixedArray(ref base, length) => {
+            (base, u32::try_from(length).map_err(|_| ErrorKind::Overflow)?)
+        }
+    };
+    match *base {
+        BaseType::UInt8 | BaseType::Char => deserializer.get_bytes(length).map(Value::Bytes),
+        _ => {
+            // Avoid trusting the length with preallocation, as it comes from the data
+            let mut items = Vec::new();
+            for _ in 0..length {
+                items.push(decode_base(schema, base, deserializer)?);
+            }
+            Ok(Value::Array(items))
+        }
+    }
+}
+
+pub(crate) fn decode_base<R>(schema: &Schema,
+                             base: &BaseType,
+                             deserializer: &mut Deserializer<R>)
+                             -> Result<Value>
+    where R: io::Read
+{
+    Ok(match *base {
+           BaseType::Bool => Value::Bool(bool::deserialize(deserializer)?),
+           BaseType::Int8 | BaseType::Byte => Value::I8(i8::deserialize(deserializer)?),
+           BaseType::UInt8 | BaseType::Char => Value::U8(u8::deserialize(deserializer)?),
+           BaseType::Int16 => Value::I16(i16::deserialize(deserializer)?),
+           BaseType::UInt16 => Value::U16(u16::deserialize(deserializer)?),
+           BaseType::Int32 => Value::I32(i32::deserialize(deserializer)?),
+           BaseType::UInt32 => Value::U32(u32::deserialize(deserializer)?),
+           BaseType::Int64 => Value::I64(i64::deserialize(deserializer)?),
+           BaseType::UInt64 => Value::U64(u64::deserialize(deserializer)?),
+           BaseType::Float32 => Value::F32(f32::deserialize(deserializer)?),
+           BaseType::Float64 => Value::F64(f64::deserialize(deserializer)?),
+           BaseType::String => Value::String(String::deserialize(deserializer)?),
+           BaseType::Time => Value::Time(Time::deserialize(deserializer)?),
+           BaseType::Duration => Value::Duration(Duration::deserialize(deserializer)?),
+           BaseType::Message(ref name) => decode_message(schema, name, deserializer)?,
+       })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::schema::Msg;
+    use super::super::super::to_vec;
+
+    fn pose(base: f64) -> Value {
+        Value::Message(vec![("position".into(),
+                             Value::Message(vec![("x".into(), Value::F64(base)),
+                                                 ("y".into(), Value::F64(base + 1.0)),
+                                                 ("z".into(), Value::F64(base + 2.0))])),
+                            ("orientation".into(),
+                             Value::Message(vec![("x".into(), Value::F64(base + 3.0)),
+                                                 ("y".into(), Value::F64(base + 4.0)),
+                                                 ("z".into(), Value::F64(base + 5.0)),
+                                                 ("w".into(), Value::F64(base + 6.0))]))])
+    }
+
+    #[test]
+    fn decodes_recorded_messages() {
+        let schema = Schema::from_definition("geometry_msgs/PoseArray",
+                                             include_str!("../datatests/\
+                                                           pose_array_message_definition.txt"))
+            .unwrap();
+        let value = decode(&schema, include_bytes!("../datatests/pose_array_msg.bin")).unwrap();
+        let header = Value::Message(vec![("seq".into(), Value::U32(1)),
+                                         ("stamp".into(), Value::Time(Time { sec: 0, nsec: 0 })),
+                                         ("frame_id".into(), Value::String("ABC".into()))]);
+        assert_eq!(value,
+                   Value::Message(vec![("header".into(), header),
+                                       ("poses".into(), Value::Array(vec![pose(1.0); 5]))]));
+    }
+
+    #[test]
+    fn decodes_all_types() {
+        let schema = Schema::from_definition("my_msgs/All",
+                                             "bool a\nint8 b\nbyte c\nuint8 d\nchar e\n\
+                                              int16 f\nuint16 g\nint32 h\nuint32 i\n\
+                                              int64 j\nuint64 k\nfloat32 l\nfloat64 m\n\
+                                              string n\ntime o\nduration p\n\
+                                              uint8[] q\nchar[2] r\nint16[2] s\n")
+            .unwrap();
+        let data = to_vec(&(true,
+                            (-1i8, -2i8, 3u8, 4u8),
+                            (-5i16, 6u16, -7i32, 8u32, -9i64, 10u64),
+                            (11.5f32, 12.5f64, "text"),
+                            (13u32, 14u32, -15i32, 16i32),
+                            vec![17u8, 18],
+                            [19u8, 20],
+                            [-21i16, 22]))
+            .unwrap();
+        let value = decode(&schema, &data).unwrap();
+        let values = value.as_fields().unwrap().iter().map(|v| v.1.clone()).collect::<Vec<_>>();
+        assert_eq!(values,
+                   vec![Value::Bool(true),
+                        Value::I8(-1),
+                        Value::I8(-2),
+                        Value::U8(3),
+                        Value::U8(4),
+                        Value::I16(-5),
+                        Value::U16(6),
+                        Value::I32(-7),
+                        Value::U32(8),
+                        Value::I64(-9),
+                        Value::U64(10),
+                        Value::F32(11.5),
+                        Value::F64(12.5),
+                        Value::String("text".into()),
+                        Value::Time(Time { sec: 13, nsec: 14 }),
+                        Value::Duration(Duration { sec: -15, nsec: 16 }),
+                        Value::Bytes(vec![17, 18]),
+                        Value::Bytes(vec![19, 20]),
+                        Value::Array(vec![Value::I16(-21), Value::I16(22)])]);
+    }
+
+    #[test]
+    fn fails_on_wrong_lengths() {
+        let schema = Schema::from_definition("my_msgs/Pair", "uint16 a\nuint16 b\n").unwrap();
+        assert_eq!(decode(&schema, &[4, 0, 0, 0, 1, 0, 2, 0]).unwrap(),
+                   Value::Message(vec![("a".into(), Value::U16(1)), ("b".into(), Value::U16(2))]));
+        assert!(decode(&schema, &[3, 0, 0, 0, 1, 0, 2, 0]).is_err());
+        assert!(decode(&schema, &[5, 0, 0, 0, 1, 0, 2, 0, 3]).is_err());
+        assert!(decode(&schema, &[4, 0, 0, 0, 1, 0, 2]).is_err());
+        let schema = Schema::from_definition("my_msgs/Data", "uint32[] data\n").unwrap();
+        assert!(decode(&schema, &[8, 0, 0, 0, 255, 255, 255, 255, 1, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn fails_on_oversized_fixed_arrays() {
+        let mut msg = Msg::new("my_msgs/Data", "int8[1] a\n").unwrap();
+        msg.fields[0].field_type = FieldType::FixedArray(BaseType::Int8, u32::MAX as usize + 1);
+        let schema = Schema::new(msg, Vec::new()).unwrap();
+        match *decode(&schema, &[0, 0, 0, 0]).unwrap_err().kind() {
+            ErrorKind::Overflow => {}
+            ref kind => panic!("Unexpected error: {}", kind),
+        }
+    }
+}
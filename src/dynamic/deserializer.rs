@@ -0,0 +1,980 @@
+use byteorder::{LittleEndian, ReadBytesExt};
+use serde::de::{self, IntoDeserializer};
+use super::super::de::Deserializer;
+use super::super::error::{Error, ErrorKind, Result};
+use super::super::schema::{BaseType, FieldType, Field, Schema};
+use std::convert::TryFrom;
+use std::io;
+
+/// A structure for deserializing ROSMSG data guided by a message schema.
+///
+/// Unlike the plain [`Deserializer`](../de/struct.Deserializer.html), this
+/// one knows the layout of the data, which makes it self-describing.
+/// Messages are offered as maps from field names to values, so any serde
+/// consumer can receive them, including `serde_json::Value`, `HashMap`,
+/// untagged enums and structs with flattened fields.
+///
+/// `time` and `duration` values are offered as maps with `secs` and `nsecs`
+/// fields, like `rospy` names them.
+///
+/// Since fields are matched by name, structs can declare just the fields
+/// they need, in any order. All other fields get skipped without decoding,
+/// using the schema to find out how many bytes they take.
+///
+/// Prefer using `from_reader` and `from_slice`.
+pub struct SchemaDeserializer<'s, R> {
+    deserializer: Deserializer<R>,
+    schema: &'s Schema,
+}
+
+impl<'s, R> SchemaDeserializer<'s, R>
+    where R: io::Read
+{
+    /// Create a new deserializer for the root message of the schema.
+    ///
+    /// The object size prefix must already be consumed from the reader, and
+    /// passed as the expected length.
+    pub fn new(reader: R, expected_length: u32, schema: &'s Schema) -> Self {
+        SchemaDeserializer {
+            deserializer: Deserializer::new(reader, expected_length),
+            schema,
+        }
+    }
+
+    /// Unwrap the `Reader` from the `SchemaDeserializer`.
+    pub fn into_inner(self) -> R {
+        self.deserializer.into_inner()
+    }
+
+    /// Check if the deserializer is fully read.
+    pub fn is_fully_read(&self) -> bool {
+        self.deserializer.is_fully_read()
+    }
+
+    fn root(&mut self) -> ValueDeserializer<'_, 's, R> {
+        ValueDeserializer {
+            deserializer: &mut self.deserializer,
+            schema: self.schema,
+            kind: Kind::Message(self.schema.name()),
+        }
+    }
+}
+
+macro_rules! forward_to_root {
+    ($($method:ident($($arg:ident: $ty:ty),*))*) => {
+        $(
+            #[inline]
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value>
+                where V: de::Visitor<'de>
+            {
+                self.root().$method($($arg,)* visitor)
+            }
+        )*
+    }
+}
+
+impl<'de, 'a, 's, R: io::Read> de::Deserializer<'de> for &'a mut SchemaDeserializer<'s, R> {
+    type Error = Error;
+
+    forward_to_root! {
+        deserialize_any()
+        deserialize_bool()
+        deserialize_i8()
+        deserialize_i16()
+        deserialize_i32()
+        deserialize_i64()
+        deserialize_u8()
+        deserialize_u16()
+        deserialize_u32()
+        deserialize_u64()
+        deserialize_f32()
+        deserialize_f64()
+        deserialize_char()
+        deserialize_str()
+        deserialize_string()
+        deserialize_bytes()
+        deserialize_byte_buf()
+        deserialize_option()
+        deserialize_unit()
+        deserialize_unit_struct(name: &'static str)
+        deserialize_newtype_struct(name: &'static str)
+        deserialize_seq()
+        deserialize_tuple(len: usize)
+        deserialize_tuple_struct(name: &'static str, len: usize)
+        deserialize_map()
+        deserialize_struct(name: &'static str, fields: &'static [&'static str])
+        deserialize_enum(name: &'static str, variants: &'static [&'static str])
+        deserialize_identifier()
+        deserialize_ignored_any()
+    }
+}
+
+static UINT32: BaseType = BaseType::UInt32;
+static INT32: BaseType = BaseType::Int32;
+
+/// Type of the value that comes next in the data.
+#[derive(Clone, Copy)]
+pub(super) enum Kind<'s> {
+    Message(&'s str),
+    Field(&'s FieldType),
+    Base(&'s BaseType),
+}
+
+impl<'s> Kind<'s> {
+    fn single(self) -> Option<&'s BaseType> {
+        match self {
+            Kind::Field(FieldType::Single(base)) => Some(base),
+            Kind::Base(base) => Some(base),
+            _ => None,
+        }
+    }
+}
+
+struct ValueDeserializer<'a, 's, R: 'a> {
+    deserializer: &'a mut Deserializer<R>,
+    schema: &'s Schema,
+    kind: Kind<'s>,
+}
+
+impl<'a, 's, R: io::Read> ValueDeserializer<'a, 's, R> {
+    fn fields(&self, name: &str) -> Result<&'s [Field]> {
+        self.schema.message(name).map(|msg| &msg.fields[..])
+    }
+
+    fn byte_array_length(&mut self) -> Result<Option<u32>> {
+        match self.kind {
+            Kind::Field(FieldType::Array(BaseType::UInt8)) |
+            Kind::Field(FieldType::Array(BaseType::Char)) => {
+                self.deserializer.pop_length().map(Some)
+            }
+            Kind::Field(FieldType::FixedArray(BaseType::UInt8, length)) |
+            Kind::Field(FieldType::FixedArray(BaseType::Char, length)) => {
+                fixed_length(*length).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn time_type(base: &BaseType) -> &'static BaseType {
+        match *base {
+            BaseType::Duration => &INT32,
+            _ => &UINT32,
+        }
+    }
+}
+
+macro_rules! forward_to_any {
+    ($($method:ident($($arg:ident: $ty:ty),*))*) => {
+        $(
+            #[inline]
+            fn $method<V>(self, $(_: $ty,)* visitor: V) -> Result<V::Value>
+                where V: de::Visitor<'de>
+            {
+                self.deserialize_any(visitor)
+            }
+        )*
+    }
+}
+
+impl<'de, 'a, 's, R: io::Read> de::Deserializer<'de> for ValueDeserializer<'a, 's, R> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+        where V: de::Visitor<'de>
+    {
+        let base = match self.kind {
+            Kind::Message(name) => {
+                let fields = self.fields(name)?
+                    .iter()
+                    .map(|field| (field.name.as_str(), Kind::Field(&field.field_type)));
+                return visitor.visit_map(Fields::new(self.deserializer, self.schema, fields));
+            }
+            Kind::Field(FieldType::Single(base)) => base,
+            Kind::Field(FieldType::Array(base)) => {
+                let length = self.deserializer.pop_length()?;
+                return visitor.visit_seq(Items::new(self.deserializer, self.schema, base, length));
+            }
+            Kind::Field(FieldType::FixedArray(base, length)) => {
+                let length = fixed_length(*length)?;
+                return visitor.visit_seq(Items::new(self.deserializer, self.schema, base, length));
+            }
+            Kind::Base(base) => base,
+        };
+        let deserializer = self.deserializer;
+        match *base {
+            BaseType::Bool => de::Deserializer::deserialize_bool(deserializer, visitor),
+            BaseType::Int8 | BaseType::Byte => {
+                de::Deserializer::deserialize_i8(deserializer, visitor)
+            }
+            BaseType::UInt8 | BaseType::Char => {
+                de::Deserializer::deserialize_u8(deserializer, visitor)
+            }
+            BaseType::Int16 => de::Deserializer::deserialize_i16(deserializer, visitor),
+            BaseType::UInt16 => de::Deserializer::deserialize_u16(deserializer, visitor),
+            BaseType::Int32 => de::Deserializer::deserialize_i32(deserializer, visitor),
+            BaseType::UInt32 => de::Deserializer::deserialize_u32(deserializer, visitor),
+            BaseType::Int64 => de::Deserializer::deserialize_i64(deserializer, visitor),
+            BaseType::UInt64 => de::Deserializer::deserialize_u64(deserializer, visitor),
+            BaseType::Float32 => de::Deserializer::deserialize_f32(deserializer, visitor),
+            BaseType::Float64 => de::Deserializer::deserialize_f64(deserializer, visitor),
+            BaseType::String => de::Deserializer::deserialize_string(deserializer, visitor),
+            BaseType::Time | BaseType::Duration => {
+                let kind = Kind::Base(Self::time_type(base));
+                let fields = [("secs", kind), ("nsecs", kind)];
+                visitor.visit_map(Fields::new(deserializer, self.schema, fields.iter().cloned()))
+            }
+            BaseType::Message(ref name) => {
+                ValueDeserializer {
+                        deserializer,
+                        schema: self.schema,
+                        kind: Kind::Message(name),
+                    }
+                    .deserialize_any(visitor)
+            }
+        }
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+        where V: de::Visitor<'de>
+    {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V>(mut self, visitor: V) -> Result<V::Value>
+        where V: de::Visitor<'de>
+    {
+        match self.byte_array_length()? {
+            Some(length) => visitor.visit_byte_buf(self.deserializer.get_bytes(length)?),
+            None => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+        where V: de::Visitor<'de>
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+        where V: de::Visitor<'de>
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+        where V: de::Visitor<'de>
+    {
+        // Structures can be read positionally too, just like without a schema
+        let base = match self.kind {
+            Kind::Message(name) => Some(name),
+            _ => {
+                match self.kind.single() {
+                    Some(BaseType::Message(name)) => Some(name.as_str()),
+                    _ => None,
+                }
+            }
+        };
+        if let Some(name) = base {
+            let fields = self.fields(name)?
+                .iter()
+                .map(|field| (field.name.as_str(), Kind::Field(&field.field_type)));
+            return visitor.visit_seq(Fields::new(self.deserializer, self.schema, fields));
+        }
+        match self.kind.single() {
+            Some(base @ &BaseType::Time) |
+            Some(base @ &BaseType::Duration) => {
+                let kind = Kind::Base(Self::time_type(base));
+                let fields = [("secs", kind), ("nsecs", kind)];
+                let fields = fields.iter().cloned();
+                visitor.visit_seq(Fields::new(self.deserializer, self.schema, fields))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+        where V: de::Visitor<'de>
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(self,
+                                   _name: &'static str,
+                                   _len: usize,
+                                   visitor: V)
+                                   -> Result<V::Value>
+        where V: de::Visitor<'de>
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V>(self,
+                           _name: &'static str,
+                           _variants: &'static [&'static str],
+                           _visitor: V)
+                           -> Result<V::Value>
+        where V: de::Visitor<'de>
+    {
+        bail!(ErrorKind::UnsupportedEnumType)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+        where V: de::Visitor<'de>
+    {
+        skip(self.deserializer, self.schema, self.kind)?;
+        visitor.visit_unit()
+    }
+
+    forward_to_any! {
+        deserialize_bool()
+        deserialize_i8()
+        deserialize_i16()
+        deserialize_i32()
+        deserialize_i64()
+        deserialize_u8()
+        deserialize_u16()
+        deserialize_u32()
+        deserialize_u64()
+        deserialize_f32()
+        deserialize_f64()
+        deserialize_char()
+        deserialize_str()
+        deserialize_string()
+        deserialize_unit()
+        deserialize_unit_struct(name: &'static str)
+        deserialize_map()
+        deserialize_struct(name: &'static str, fields: &'static [&'static str])
+        deserialize_identifier()
+    }
+}
+
+/// Length of a fixed size array, which needs to fit the wire format.
+fn fixed_length(length: usize) -> Result<u32> {
+    u32::try_from(length).map_err(|_| ErrorKind::Overflow.into())
+}
+
+/// Skip over a value, without decoding it.
+fn skip<R: io::Read>(deserializer: &mut Deserializer<R>,
+                     schema: &Schema,
+                     kind: Kind)
+                     -> Result<()> {
+    let (base, length) = match kind {
+        Kind::Message(name) => {
+            if let Some(size) = schema.size_of_message(name) {
+                let size = u32::try_from(size).map_err(|_| ErrorKind::Overflow)?;
+                return deserializer.skip_bytes(size);
+            }
+            for field in &schema.message(name)?.fields {
+                skip(deserializer, schema, Kind::Field(&field.field_type))?;
+            }
+            return Ok(());
+        }
+        Kind::Field(FieldType::Single(base)) => (base, 1),
+        Kind::Base(base) => (base, 1),
+        Kind::Field(FieldType::Array(base)) => (base, deserializer.pop_length()?),
+        Kind::Field(FieldType::FixedArray(base, length)) => (base, fixed_length(*length)?),
+    };
+    // Arrays of constant size items get skipped in one go
+    if let Some(size) = schema.size_of(base) {
+        let size = u32::try_from(size)
+            .ok()
+            .and_then(|size| size.checked_mul(length))
+            .ok_or(ErrorKind::Overflow)?;
+        return deserializer.skip_bytes(size);
+    }
+    for _ in 0..length {
+        match *base {
+            BaseType::Message(ref name) => skip(deserializer, schema, Kind::Message(name))?,
+            _ => {
+                let size = deserializer.pop_length()?;
+                deserializer.skip_bytes(size)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Named values, read one after another.
+struct Fields<'a, 's, R: 'a, I> {
+    deserializer: &'a mut Deserializer<R>,
+    schema: &'s Schema,
+    fields: I,
+    next: Option<Kind<'s>>,
+}
+
+impl<'a, 's, R, I> Fields<'a, 's, R, I>
+    where R: io::Read,
+          I: Iterator<Item = (&'s str, Kind<'s>)>
+{
+    fn new(deserializer: &'a mut Deserializer<R>, schema: &'s Schema, fields: I) -> Self {
+        Fields {
+            deserializer,
+            schema,
+            fields,
+            next: None,
+        }
+    }
+
+    fn value(&mut self, kind: Kind<'s>) -> ValueDeserializer<'_, 's, R> {
+        ValueDeserializer {
+            deserializer: self.deserializer,
+            schema: self.schema,
+            kind,
+        }
+    }
+}
+
+impl<'de, 'a, 's, R, I> de::MapAccess<'de> for Fields<'a, 's, R, I>
+    where R: io::Read,
+          I: Iterator<Item = (&'s str, Kind<'s>)>
+{
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+        where K: de::DeserializeSeed<'de>
+    {
+        match self.fields.next() {
+            Some((name, kind)) => {
+                self.next = Some(kind);
+                seed.deserialize(name.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+        where V: de::DeserializeSeed<'de>
+    {
+        match self.next.take() {
+            Some(kind) => seed.deserialize(self.value(kind)),
+            None => bail!(ErrorKind::UnexpectedType("value without a key".into())),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.fields.size_hint().1
+    }
+}
+
+impl<'de, 'a, 's, R, I> de::SeqAccess<'de> for Fields<'a, 's, R, I>
+    where R: io::Read,
+          I: Iterator<Item = (&'s str, Kind<'s>)>
+{
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+        where T: de::DeserializeSeed<'de>
+    {
+        match self.fields.next() {
+            Some((_, kind)) => seed.deserialize(self.value(kind)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.fields.size_hint().1
+    }
+}
+
+/// Array items, all of the same type.
+struct Items<'a, 's, R: 'a> {
+    deserializer: &'a mut Deserializer<R>,
+    schema: &'s Schema,
+    base: &'s BaseType,
+    remaining: u32,
+}
+
+impl<'a, 's, R: io::Read> Items<'a, 's, R> {
+    fn new(deserializer: &'a mut Deserializer<R>,
+           schema: &'s Schema,
+           base: &'s BaseType,
+           remaining: u32)
+           -> Self {
+        Items {
+            deserializer,
+            schema,
+            base,
+            remaining,
+        }
+    }
+}
+
+impl<'de, 'a, 's, R: io::Read> de::SeqAccess<'de> for Items<'a, 's, R> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+        where T: de::DeserializeSeed<'de>
+    {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(ValueDeserializer {
+                             deserializer: self.deserializer,
+                             schema: self.schema,
+                             kind: Kind::Base(self.base),
+                         })
+            .map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        // Capped, as the length comes from the data
+        Some(::std::cmp::min(self.remaining as usize, 4096))
+    }
+}
+
+/// Deserialize an instance of type `T` from an IO stream of ROSMSG data,
+/// guided by the schema.
+///
+/// The schema makes the data self-describing, so `T` can be any type that
+/// serde can deserialize from the contents of the message, even without
+/// knowing its structure upfront.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serde_rosmsg;
+/// # use serde_rosmsg::dynamic::from_reader;
+/// # use serde_rosmsg::schema::Schema;
+/// # use std::collections::HashMap;
+/// # fn main() {
+/// let schema = Schema::from_definition("my_msgs/Pair", "uint16 a\nuint16 b\n").unwrap();
+/// let mut cursor = std::io::Cursor::new([4, 0, 0, 0, 1, 0, 2, 0]);
+/// let value: HashMap<String, u16> = from_reader(&schema, &mut cursor).unwrap();
+/// assert_eq!(value["a"], 1);
+/// assert_eq!(value["b"], 2);
+/// # }
+/// ```
+pub fn from_reader<'de, R, T>(schema: &Schema, mut reader: R) -> Result<T>
+    where R: io::Read,
+          T: de::Deserialize<'de>
+{
+    let length = reader.read_u32::<LittleEndian>()?;
+    let mut deserializer = SchemaDeserializer::new(reader, length, schema);
+    let value = T::deserialize(&mut deserializer)?;
+    if !deserializer.is_fully_read() {
+        bail!(ErrorKind::Underflow);
+    }
+    Ok(value)
+}
+
+/// Deserialize an instance of type `T` from bytes of ROSMSG data, guided by
+/// the schema.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[macro_use]
+/// # extern crate serde_json;
+/// # extern crate serde_rosmsg;
+/// # use serde_rosmsg::dynamic::from_slice;
+/// # use serde_rosmsg::schema::Schema;
+/// # fn main() {
+/// let schema = Schema::from_definition("my_msgs/Reading",
+///                                      "string sensor\ntime stamp\nfloat32[] values\n")
+///     .unwrap();
+/// let data = [27, 0, 0, 0,
+///             3, 0, 0, 0, 73, 77, 85,
+///             1, 0, 0, 0, 2, 0, 0, 0,
+///             2, 0, 0, 0, 0, 0, 128, 63, 0, 0, 0, 64];
+/// let value: serde_json::Value = from_slice(&schema, &data).unwrap();
+/// assert_eq!(value,
+///            json!({
+///                "sensor": "IMU",
+///                "stamp": {"secs": 1, "nsecs": 2},
+///                "values": [1.0, 2.0],
+///            }));
+/// # }
+/// ```
+pub fn from_slice<'de, T>(schema: &Schema, bytes: &[u8]) -> Result<T>
+    where T: de::Deserialize<'de>
+{
+    from_reader(schema, io::Cursor::new(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeMap, HashMap};
+    use super::super::super::schema::Msg;
+    use super::super::super::to_vec;
+    use super::super::super::types::{Duration, Time};
+    #[cfg(feature = "catalog")]
+    use super::super::{encode, Value};
+
+    /// Minimal self-describing value, relying only on `deserialize_any`.
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(untagged)]
+    enum Any {
+        Bool(bool),
+        Int(i64),
+        Float(f64),
+        Text(String),
+        List(Vec<Any>),
+        Map(BTreeMap<String, Any>),
+    }
+
+    fn map(items: Vec<(&str, Any)>) -> Any {
+        Any::Map(items.into_iter().map(|(key, value)| (key.into(), value)).collect())
+    }
+
+    fn pose_array_schema() -> Schema {
+        Schema::from_definition("geometry_msgs/PoseArray",
+                                include_str!("../datatests/pose_array_message_definition.txt"))
+            .unwrap()
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Header {
+        frame_id: String,
+        stamp: Time,
+        seq: u32,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Point {
+        z: f64,
+        y: f64,
+        x: f64,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Pose {
+        orientation: HashMap<String, f64>,
+        position: Point,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct PoseArray {
+        poses: Vec<Pose>,
+        header: Header,
+    }
+
+    #[test]
+    fn reads_any_values() {
+        let schema = Schema::from_definition("my_msgs/All",
+                                             "bool a\nbyte b\nchar c\nint64 d\nfloat32 e\n\
+                                              duration f\nuint8[] g\nchar[2] h\nstring[] i\n")
+            .unwrap();
+        let data = to_vec(&(true,
+                            -1i8,
+                            2u8,
+                            -3i64,
+                            0.5f32,
+                            (-4i32, 5i32),
+                            vec![6u8, 7],
+                            [8u8, 9],
+                            vec!["x", "yz"]))
+            .unwrap();
+        let value: Any = from_slice(&schema, &data).unwrap();
+        assert_eq!(value,
+                   map(vec![("a", Any::Bool(true)),
+                            ("b", Any::Int(-1)),
+                            ("c", Any::Int(2)),
+                            ("d", Any::Int(-3)),
+                            ("e", Any::Float(0.5)),
+                            ("f", map(vec![("secs", Any::Int(-4)), ("nsecs", Any::Int(5))])),
+                            ("g", Any::List(vec![Any::Int(6), Any::Int(7)])),
+                            ("h", Any::List(vec![Any::Int(8), Any::Int(9)])),
+                            ("i",
+                             Any::List(vec![Any::Text("x".into()), Any::Text("yz".into())]))]));
+    }
+
+    #[test]
+    fn reads_into_maps() {
+        let schema = Schema::from_definition("geometry_msgs/Point",
+                                             "float64 x\nfloat64 y\nfloat64 z\n")
+            .unwrap();
+        let data = to_vec(&(1.0f64, 2.0f64, 3.0f64)).unwrap();
+        let value: HashMap<String, f64> = from_slice(&schema, &data).unwrap();
+        assert_eq!(value.len(), 3);
+        assert_eq!(value["x"], 1.0);
+        assert_eq!(value["y"], 2.0);
+        assert_eq!(value["z"], 3.0);
+    }
+
+    #[test]
+    fn reads_structs_by_name() {
+        let value: PoseArray = from_slice(&pose_array_schema(),
+                                          include_bytes!("../datatests/pose_array_msg.bin"))
+            .unwrap();
+        assert_eq!(value.header,
+                   Header {
+                       frame_id: "ABC".into(),
+                       stamp: Time { sec: 0, nsec: 0 },
+                       seq: 1,
+                   });
+        assert_eq!(value.poses.len(), 5);
+        for pose in value.poses {
+            assert_eq!(pose.position,
+                       Point {
+                           x: 1.0,
+                           y: 2.0,
+                           z: 3.0,
+                       });
+            assert_eq!(pose.orientation.len(), 4);
+            assert_eq!(pose.orientation["w"], 7.0);
+        }
+    }
+
+    #[test]
+    fn reads_structs_by_position() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Stamped(u32, (u32, u32), String);
+
+        type Poses = Vec<([f64; 3], [f64; 4])>;
+
+        let value: (Stamped, Poses) = from_slice(&pose_array_schema(),
+                                                 include_bytes!("../datatests/pose_array_msg.bin"))
+            .unwrap();
+        assert_eq!(value.0, Stamped(1, (0, 0), "ABC".into()));
+        assert_eq!(value.1, vec![([1.0, 2.0, 3.0], [4.0, 5.0, 6.0, 7.0]); 5]);
+    }
+
+    #[test]
+    fn reads_time_types() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Times {
+            stamp: Time,
+            timeout: Duration,
+        }
+
+        let schema = Schema::from_definition("my_msgs/Times", "time stamp\nduration timeout\n")
+            .unwrap();
+        let data = to_vec(&(1u32, 2u32, -3i32, 4i32)).unwrap();
+        assert_eq!(from_slice::<Times>(&schema, &data).unwrap(),
+                   Times {
+                       stamp: Time { sec: 1, nsec: 2 },
+                       timeout: Duration { sec: -3, nsec: 4 },
+                   });
+    }
+
+    #[test]
+    fn reads_flattened_fields() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Tagged {
+            name: String,
+            #[serde(flatten)]
+            rest: HashMap<String, i64>,
+        }
+
+        let schema = Schema::from_definition("my_msgs/Tagged",
+                                             "string name\nint32 value\nuint8 count\n")
+            .unwrap();
+        let data = to_vec(&("abc", -2i32, 7u8)).unwrap();
+        let value: Tagged = from_slice(&schema, &data).unwrap();
+        assert_eq!(value.name, "abc");
+        assert_eq!(value.rest.len(), 2);
+        assert_eq!(value.rest["value"], -2);
+        assert_eq!(value.rest["count"], 7);
+    }
+
+    #[test]
+    fn reads_untagged_enums() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        #[serde(untagged)]
+        enum Shape {
+            Circle { radius: f64 },
+            Rectangle { width: f64, height: f64 },
+        }
+
+        let circle = Schema::from_definition("my_msgs/Circle", "float64 radius\n").unwrap();
+        let rectangle = Schema::from_definition("my_msgs/Rectangle",
+                                                "float64 width\nfloat64 height\n")
+            .unwrap();
+        let data = to_vec(&2.0f64).unwrap();
+        assert_eq!(from_slice::<Shape>(&circle, &data).unwrap(),
+                   Shape::Circle { radius: 2.0 });
+        let data = to_vec(&(3.0f64, 4.0f64)).unwrap();
+        assert_eq!(from_slice::<Shape>(&rectangle, &data).unwrap(),
+                   Shape::Rectangle {
+                       width: 3.0,
+                       height: 4.0,
+                   });
+    }
+
+    #[test]
+    fn reads_bytes_in_bulk() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Blob {
+            #[serde(with = "::types::bytes")]
+            data: Vec<u8>,
+            fixed: Vec<u8>,
+        }
+
+        let schema = Schema::from_definition("my_msgs/Blob", "uint8[] data\nchar[2] fixed\n")
+            .unwrap();
+        let data = to_vec(&(vec![1u8, 2, 3], [4u8, 5])).unwrap();
+        let value: Blob = from_slice(&schema, &data).unwrap();
+        assert_eq!(value,
+                   Blob {
+                       data: vec![1, 2, 3],
+                       fixed: vec![4, 5],
+                   });
+    }
+
+    #[test]
+    fn skips_ignored_values() {
+        let data = include_bytes!("../datatests/pose_array_msg.bin");
+        let mut cursor = io::Cursor::new(&data[..]);
+        from_reader::<_, de::IgnoredAny>(&pose_array_schema(), &mut cursor).unwrap();
+        assert_eq!(cursor.position(), data.len() as u64);
+        assert!(from_slice::<de::IgnoredAny>(&pose_array_schema(), &data[..data.len() - 1])
+                    .is_err());
+    }
+
+    #[cfg(feature = "catalog")]
+    fn vector(x: f64, y: f64, z: f64) -> Value {
+        Value::Message(vec![("x".into(), Value::F64(x)),
+                            ("y".into(), Value::F64(y)),
+                            ("z".into(), Value::F64(z))])
+    }
+
+    #[cfg(feature = "catalog")]
+    fn header(frame_id: &str) -> Value {
+        Value::Message(vec![("seq".into(), Value::U32(3)),
+                            ("stamp".into(), Value::Time(Time { sec: 4, nsec: 5 })),
+                            ("frame_id".into(), Value::String(frame_id.into()))])
+    }
+
+    #[cfg(feature = "catalog")]
+    fn floats(values: &[f64]) -> Value {
+        Value::Array(values.iter().cloned().map(Value::F64).collect())
+    }
+
+    #[cfg(feature = "catalog")]
+    #[test]
+    fn reads_subsets_of_camera_info() {
+        #[allow(non_snake_case)]
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct CameraInfo {
+            header: Header,
+            K: [f64; 9],
+        }
+
+        let schema = ::catalog::schema("sensor_msgs/CameraInfo").unwrap();
+        let roi = Value::Message(vec![("x_offset".into(), Value::U32(1)),
+                                      ("y_offset".into(), Value::U32(2)),
+                                      ("height".into(), Value::U32(3)),
+                                      ("width".into(), Value::U32(4)),
+                                      ("do_rectify".into(), Value::Bool(true))]);
+        let value = Value::Message(vec![("header".into(), header("camera")),
+                                        ("height".into(), Value::U32(480)),
+                                        ("width".into(), Value::U32(640)),
+                                        ("distortion_model".into(),
+                                         Value::String("plumb_bob".into())),
+                                        ("D".into(), floats(&[0.1, 0.2, 0.3, 0.4, 0.5])),
+                                        ("K".into(), floats(&[1.0; 9])),
+                                        ("R".into(), floats(&[2.0; 9])),
+                                        ("P".into(), floats(&[3.0; 12])),
+                                        ("binning_x".into(), Value::U32(0)),
+                                        ("binning_y".into(), Value::U32(0)),
+                                        ("roi".into(), roi)]);
+        let data = encode(&schema, &value).unwrap();
+        let mut cursor = io::Cursor::new(&data);
+        let info: CameraInfo = from_reader(&schema, &mut cursor).unwrap();
+        assert_eq!(cursor.position(), data.len() as u64);
+        assert_eq!(info,
+                   CameraInfo {
+                       header: Header {
+                           frame_id: "camera".into(),
+                           stamp: Time { sec: 4, nsec: 5 },
+                           seq: 3,
+                       },
+                       K: [1.0; 9],
+                   });
+    }
+
+    #[cfg(feature = "catalog")]
+    #[test]
+    fn reads_subsets_of_odometry() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct PoseWithCovariance {
+            pose: Pose,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Odometry {
+            pose: PoseWithCovariance,
+        }
+
+        let schema = ::catalog::schema("nav_msgs/Odometry").unwrap();
+        let pose = Value::Message(vec![("position".into(), vector(1.0, 2.0, 3.0)),
+                                       ("orientation".into(),
+                                        Value::Message(vec![("x".into(), Value::F64(0.0)),
+                                                            ("y".into(), Value::F64(0.0)),
+                                                            ("z".into(), Value::F64(0.0)),
+                                                            ("w".into(), Value::F64(1.0))]))]);
+        let twist = Value::Message(vec![("linear".into(), vector(4.0, 5.0, 6.0)),
+                                        ("angular".into(), vector(7.0, 8.0, 9.0))]);
+        let value = Value::Message(vec![("header".into(), header("odom")),
+                                        ("child_frame_id".into(),
+                                         Value::String("base_link".into())),
+                                        ("pose".into(),
+                                         Value::Message(vec![("pose".into(), pose),
+                                                             ("covariance".into(),
+                                                              floats(&[0.5; 36]))])),
+                                        ("twist".into(),
+                                         Value::Message(vec![("twist".into(), twist),
+                                                             ("covariance".into(),
+                                                              floats(&[0.25; 36]))]))]);
+        let data = encode(&schema, &value).unwrap();
+        let odometry: Odometry = from_slice(&schema, &data).unwrap();
+        assert_eq!(odometry.pose.pose.position,
+                   Point {
+                       x: 1.0,
+                       y: 2.0,
+                       z: 3.0,
+                   });
+        assert_eq!(odometry.pose.pose.orientation["w"], 1.0);
+        assert!(from_slice::<Odometry>(&schema, &data[..data.len() - 8]).is_err());
+    }
+
+    #[test]
+    fn skips_variable_length_fields() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Last {
+            last: u8,
+        }
+
+        let schema = Schema::from_definition("my_msgs/Mixed",
+                                             "string[] names\nuint8[] data\nPoint[] points\n\
+                                              Named[] named\nuint8 last\n\
+                                              ================================================\
+                                              ================================\n\
+                                              MSG: my_msgs/Point\nint16 x\nint16 y\n\
+                                              ================================================\
+                                              ================================\n\
+                                              MSG: my_msgs/Named\nstring name\nPoint point\n")
+            .unwrap();
+        let data = to_vec(&(vec!["a", "bc"],
+                            vec![1u8, 2, 3],
+                            vec![(4i16, 5i16), (6, 7)],
+                            vec![("d", (8i16, 9i16))],
+                            10u8))
+            .unwrap();
+        assert_eq!(from_slice::<Last>(&schema, &data).unwrap(), Last { last: 10 });
+        assert!(from_slice::<Last>(&schema, &data[..data.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn fails_to_skip_oversized_fields() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Only {
+            b: i8,
+        }
+
+        let mut msg = Msg::new("my_msgs/Data", "uint8[1] a\nint8 b\n").unwrap();
+        msg.fields[0].field_type = FieldType::FixedArray(BaseType::UInt8, u32::MAX as usize + 2);
+        let schema = Schema::new(msg, Vec::new()).unwrap();
+        assert!(from_slice::<Only>(&schema, &[2, 0, 0, 0, 9, 7]).is_err());
+
+        let schema = Schema::from_definition("my_msgs/Data",
+                                             "Big[2] a\nint8 b\n\
+                                              ================================================\
+                                              ================================\n\
+                                              MSG: my_msgs/Big\ntime[4294967295] c\n")
+            .unwrap();
+        assert!(from_slice::<Only>(&schema, &[2, 0, 0, 0, 9, 7]).is_err());
+    }
+}
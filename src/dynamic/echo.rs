@@ -0,0 +1,315 @@
+use super::Value;
+use super::super::types::{Duration, Time};
+use std::io;
+
+/// Renders dynamic messages as YAML-like text, just like `rostopic echo`.
+///
+/// The output matches what `rospy` produces, including its quirks: nested
+/// messages are indented by two spaces, with their parent field's name
+/// followed by a trailing space, arrays of builtin types are listed inline
+/// in Python's syntax, while arrays of messages are listed with `-`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use serde_rosmsg::dynamic::{decode, Echo};
+/// # use serde_rosmsg::schema::Schema;
+/// let schema = Schema::from_definition("my_msgs/Reading",
+///                                      "string sensor\nfloat32[] values\n").unwrap();
+/// let data = [19, 0, 0, 0,
+///             3, 0, 0, 0, 73, 77, 85,
+///             2, 0, 0, 0, 0, 0, 128, 63, 0, 0, 0, 64];
+/// let value = decode(&schema, &data).unwrap();
+/// assert_eq!(Echo::new().render(&value), "sensor: IMU\nvalues: [1.0, 2.0]");
+/// assert_eq!(Echo::new().noarr(true).render(&value), "sensor: IMU");
+///
+/// let mut output = Vec::new();
+/// Echo::new().write(&mut output, &value).unwrap();
+/// assert_eq!(output, b"sensor: IMU\nvalues: [1.0, 2.0]\n---\n".to_vec());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Echo {
+    noarr: bool,
+    float_precision: Option<usize>,
+}
+
+impl Echo {
+    /// Create a renderer with the default settings of `rostopic echo`.
+    pub fn new() -> Echo {
+        Echo::default()
+    }
+
+    /// Leave out fields containing arrays, like `rostopic echo --noarr`.
+    pub fn noarr(mut self, noarr: bool) -> Echo {
+        self.noarr = noarr;
+        self
+    }
+
+    /// Print floating point numbers with a fixed number of decimals.
+    ///
+    /// By default, they are printed with as many decimals as needed to
+    /// represent them exactly, like Python does.
+    pub fn float_precision(mut self, precision: Option<usize>) -> Echo {
+        self.float_precision = precision;
+        self
+    }
+
+    /// Render the message, without the trailing separator.
+    pub fn render(&self, value: &Value) -> String {
+        let mut output = String::new();
+        self.value(&mut output, value, "");
+        output
+    }
+
+    /// Write the message into the stream, followed by a `---` separator.
+    pub fn write<W: io::Write>(&self, writer: &mut W, value: &Value) -> io::Result<()> {
+        writeln!(writer, "{}\n---", self.render(value))
+    }
+
+    fn value(&self, output: &mut String, value: &Value, indent: &str) {
+        match *value {
+            Value::Bool(v) => output.push_str(if v { "True" } else { "False" }),
+            Value::F32(v) => output.push_str(&self.float(v as f64)),
+            Value::F64(v) => output.push_str(&self.float(v)),
+            Value::String(ref v) if v.is_empty() => output.push_str("''"),
+            Value::String(ref v) => output.push_str(v),
+            Value::Time(Time { sec, nsec }) => time(output, sec as i64, nsec as i64, indent),
+            Value::Duration(Duration { sec, nsec }) => {
+                time(output, sec as i64, nsec as i64, indent)
+            }
+            Value::Bytes(ref v) => {
+                let items = v.iter().map(|item| item.to_string()).collect::<Vec<_>>();
+                inline(output, &items);
+            }
+            Value::Array(ref v) => self.array(output, v, indent),
+            Value::Message(ref fields) => self.message(output, fields, indent),
+            Value::U64(v) => output.push_str(&v.to_string()),
+            ref v => output.push_str(&v.as_i64().unwrap_or_default().to_string()),
+        }
+    }
+
+    fn array(&self, output: &mut String, items: &[Value], indent: &str) {
+        let nested = match items.first() {
+            None => return output.push_str("[]"),
+            Some(&Value::Time(..)) |
+            Some(&Value::Duration(..)) |
+            Some(&Value::Bytes(..)) |
+            Some(&Value::Array(..)) |
+            Some(&Value::Message(..)) => true,
+            Some(..) => false,
+        };
+        if !nested {
+            let items = items
+                .iter()
+                .map(|item| match *item {
+                         Value::String(ref v) => python_repr(v),
+                         ref v => {
+                             let mut text = String::new();
+                             self.value(&mut text, v, indent);
+                             text
+                         }
+                     })
+                .collect::<Vec<_>>();
+            return inline(output, &items);
+        }
+        let prefix = format!("{}- ", indent);
+        let indent = format!("{}  ", indent);
+        for item in items {
+            output.push('\n');
+            output.push_str(&prefix);
+            self.value(output, item, &indent);
+        }
+    }
+
+    fn message(&self, output: &mut String, fields: &[(String, Value)], indent: &str) {
+        let nested = format!("{}  ", indent);
+        let mut first = true;
+        for (name, value) in fields {
+            if self.noarr && matches!(*value, Value::Bytes(..) | Value::Array(..)) {
+                continue;
+            }
+            if !first || !indent.is_empty() {
+                output.push('\n');
+            }
+            first = false;
+            output.push_str(indent);
+            output.push_str(name);
+            output.push_str(": ");
+            self.value(output, value, &nested);
+        }
+    }
+
+    fn float(&self, value: f64) -> String {
+        if value.is_nan() {
+            return "nan".into();
+        }
+        if value.is_infinite() {
+            return if value > 0.0 { "inf" } else { "-inf" }.into();
+        }
+        match self.float_precision {
+            Some(precision) => format!("{:.*}", precision, value),
+            None => python_float(value),
+        }
+    }
+}
+
+fn time(output: &mut String, sec: i64, nsec: i64, indent: &str) {
+    output.push_str(&format!("\n{}secs: {}\n{}nsecs: {}", indent, sec, indent, nsec));
+}
+
+fn inline(output: &mut String, items: &[String]) {
+    output.push('[');
+    output.push_str(&items.join(", "));
+    output.push(']');
+}
+
+/// Format the number like Python's `repr` does.
+///
+/// Both use the shortest representation that reads back exactly, but
+/// Python switches to the exponent notation at different thresholds.
+fn python_float(value: f64) -> String {
+    let scientific = format!("{:e}", value);
+    let (mantissa, exponent) = match scientific.find('e') {
+        Some(index) => (&scientific[..index], &scientific[index + 1..]),
+        None => return scientific,
+    };
+    let exponent = exponent.parse::<i32>().unwrap_or(0);
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => ("-", mantissa),
+        None => ("", mantissa),
+    };
+    let digits = mantissa.replace('.', "");
+    if !(-4..16).contains(&exponent) {
+        let mantissa = if digits.len() > 1 {
+            format!("{}.{}", &digits[..1], &digits[1..])
+        } else {
+            digits
+        };
+        let exponent_sign = if exponent < 0 { '-' } else { '+' };
+        return format!("{}{}e{}{:02}", sign, mantissa, exponent_sign, exponent.abs());
+    }
+    let point = exponent + 1;
+    if point <= 0 {
+        let zeros = "0".repeat(-point as usize);
+        format!("{}0.{}{}", sign, zeros, digits)
+    } else if point as usize >= digits.len() {
+        let zeros = "0".repeat(point as usize - digits.len());
+        format!("{}{}{}.0", sign, digits, zeros)
+    } else {
+        let (whole, fraction) = digits.split_at(point as usize);
+        format!("{}{}.{}", sign, whole, fraction)
+    }
+}
+
+/// Quote the string like Python's `repr` does.
+fn python_repr(value: &str) -> String {
+    let quote = if value.contains('\'') && !value.contains('"') {
+        '"'
+    } else {
+        '\''
+    };
+    let mut output = String::with_capacity(value.len() + 2);
+    output.push(quote);
+    for c in value.chars() {
+        match c {
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if c == quote => {
+                output.push('\\');
+                output.push(c);
+            }
+            c if (c as u32) < 0x20 || c as u32 == 0x7f => {
+                output.push_str(&format!("\\x{:02x}", c as u32))
+            }
+            c => output.push(c),
+        }
+    }
+    output.push(quote);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::decode;
+    use super::super::super::schema::Schema;
+
+    fn pose_array() -> Value {
+        let schema = Schema::from_definition("geometry_msgs/PoseArray",
+                                             include_str!("../datatests/\
+                                                           pose_array_message_definition.txt"))
+            .unwrap();
+        decode(&schema, include_bytes!("../datatests/pose_array_msg.bin")).unwrap()
+    }
+
+    #[test]
+    fn renders_nested_messages() {
+        let pose = "  - \n    position: \n      x: 1.0\n      y: 2.0\n      z: 3.0\n    \
+                    orientation: \n      x: 4.0\n      y: 5.0\n      z: 6.0\n      w: 7.0";
+        let expected = format!("header: \n  seq: 1\n  stamp: \n    secs: 0\n    nsecs: 0\n  \
+                                frame_id: ABC\nposes: \n{}",
+                               [pose; 5].join("\n"));
+        assert_eq!(Echo::new().render(&pose_array()), expected);
+    }
+
+    #[test]
+    fn leaves_out_arrays() {
+        assert_eq!(Echo::new().noarr(true).render(&pose_array()),
+                   "header: \n  seq: 1\n  stamp: \n    secs: 0\n    nsecs: 0\n  frame_id: ABC");
+    }
+
+    #[test]
+    fn renders_builtin_types() {
+        let value = Value::Message(vec![("flag".into(), Value::Bool(true)),
+                                        ("small".into(), Value::I8(-3)),
+                                        ("big".into(), Value::U64(u64::MAX)),
+                                        ("ratio".into(), Value::F32(0.1)),
+                                        ("empty".into(), Value::String(String::new())),
+                                        ("timeout".into(),
+                                         Value::Duration(Duration { sec: -1, nsec: 5 })),
+                                        ("data".into(), Value::Bytes(vec![1, 2, 3])),
+                                        ("flags".into(),
+                                         Value::Array(vec![Value::Bool(true),
+                                                           Value::Bool(false)])),
+                                        ("names".into(),
+                                         Value::Array(vec![Value::String("a".into()),
+                                                           Value::String("it's".into())])),
+                                        ("nothing".into(), Value::Array(vec![]))]);
+        assert_eq!(Echo::new().render(&value),
+                   "flag: True\nsmall: -3\nbig: 18446744073709551615\n\
+                    ratio: 0.10000000149011612\nempty: ''\n\
+                    timeout: \n  secs: -1\n  nsecs: 5\ndata: [1, 2, 3]\n\
+                    flags: [True, False]\nnames: ['a', \"it's\"]\nnothing: []");
+    }
+
+    #[test]
+    fn formats_floats_like_python() {
+        let echo = Echo::new();
+        assert_eq!(echo.float(0.0), "0.0");
+        assert_eq!(echo.float(-2.5), "-2.5");
+        assert_eq!(echo.float(100.0), "100.0");
+        assert_eq!(echo.float(0.0001), "0.0001");
+        assert_eq!(echo.float(0.00001), "1e-05");
+        assert_eq!(echo.float(-1.5e-7), "-1.5e-07");
+        assert_eq!(echo.float(1e15), "1000000000000000.0");
+        assert_eq!(echo.float(1e16), "1e+16");
+        assert_eq!(echo.float(1.2345e100), "1.2345e+100");
+        assert_eq!(echo.float(f64::NAN), "nan");
+        assert_eq!(echo.float(f64::NEG_INFINITY), "-inf");
+        let echo = echo.float_precision(Some(3));
+        assert_eq!(echo.float(1.0), "1.000");
+        assert_eq!(echo.float(2.0 / 3.0), "0.667");
+    }
+
+    #[test]
+    fn writes_separators() {
+        let value = Value::Message(vec![("data".into(), Value::I32(5))]);
+        let mut output = Vec::new();
+        let echo = Echo::new();
+        echo.write(&mut output, &value).unwrap();
+        echo.write(&mut output, &value).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "data: 5\n---\ndata: 5\n---\n");
+    }
+}
@@ -0,0 +1,471 @@
+use super::Value;
+use super::de::decode_base;
+use super::path::{advance, message_bytes, read_length, skip_base};
+use super::super::de::Deserializer;
+use super::super::error::{Error, ErrorKind};
+use super::super::schema::{BaseType, FieldType, Schema};
+use std::fmt;
+use std::ops::Range;
+
+/// Map the bytes of ROSMSG data onto the fields of the message.
+///
+/// Every builtin value within the message gets listed with its path, the
+/// range of bytes it takes, and its decoded value, along with the length
+/// prefixes of the message and its variable size arrays. Arrays of `uint8`
+/// and `char` are listed as a whole.
+///
+/// Instead of failing, the map stops at the field that could not be
+/// decoded, and records the reason. Bytes left after the last field are
+/// recorded too.
+///
+/// The `Display` implementation renders an annotated hex dump, with long
+/// strings and byte arrays truncated.
+///
+/// # Examples
+///
+/// ```rust
+/// # use serde_rosmsg::dynamic::{layout, Content, Value};
+/// # use serde_rosmsg::schema::Schema;
+/// let schema = Schema::from_definition("my_msgs/Reading",
+///                                      "string sensor\nfloat32[] values\n").unwrap();
+/// let data = [19, 0, 0, 0,
+///             3, 0, 0, 0, 73, 77, 85,
+///             2, 0, 0, 0, 0, 0, 128, 63, 0, 0, 0, 64];
+/// let map = layout(&schema, &data);
+/// assert_eq!(map.spans[1].path, "sensor");
+/// assert_eq!(map.spans[1].range, 4..11);
+/// assert_eq!(map.spans[1].hex, "03 00 00 00 49 4d 55");
+/// assert_eq!(map.spans[1].content, Content::Value(Value::String("IMU".into())));
+/// assert!(map.failure.is_none() && map.trailing.is_none());
+///
+/// let map = layout(&schema, &data[..21]);
+/// assert_eq!(map.to_string(),
+///            "    0..4       13 00 00 00                message length = 19\n\
+///             \x20   4..11      03 00 00 00 49 4d 55       sensor = \"IMU\"\n\
+///             \x20  11..15      02 00 00 00                values length = 2\n\
+///             \x20  15..19      00 00 80 3f                values[0] = 1.0\n\
+///             \x20  19..        !! values[1]: Reached end of memory buffer while reading data\n");
+/// ```
+pub fn layout(schema: &Schema, bytes: &[u8]) -> Layout {
+    let mut walker = Walker {
+        schema,
+        bytes,
+        spans: Vec::new(),
+    };
+    let result = read_length(bytes, 0)
+        .map_err(|err| Failure::new("", 0, err))
+        .and_then(|length| {
+            walker.push("", 0..4, Content::Length(length as u32));
+            walker.bytes = message_bytes(bytes).unwrap_or(bytes);
+            walker.message(schema.name(), "", 4)
+        });
+    let (failure, trailing) = match result {
+        Ok(end) if end < bytes.len() => (None, Some(end..bytes.len())),
+        Ok(_) => (None, None),
+        Err(mut failure) => {
+            // The data goes on beyond the length of the message
+            if let ErrorKind::EndOfBuffer = *failure.error.kind() {
+                if walker.bytes.len() < bytes.len() {
+                    failure.error = ErrorKind::Overflow.into();
+                }
+            }
+            (Some(*failure), None)
+        }
+    };
+    Layout {
+        spans: walker.spans,
+        failure,
+        trailing_hex: trailing_hex(bytes, trailing.as_ref()),
+        trailing,
+        length: bytes.len(),
+    }
+}
+
+/// Fields of ROSMSG data, as mapped by `layout`.
+#[derive(Debug)]
+pub struct Layout {
+    /// Values within the data, in order.
+    pub spans: Vec<Span>,
+    /// The value that failed to decode, if any.
+    pub failure: Option<Failure>,
+    /// Bytes left after the last field, if any.
+    pub trailing: Option<Range<usize>>,
+    trailing_hex: String,
+    length: usize,
+}
+
+/// A single value within ROSMSG data.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    /// Path of the field, like `poses[3].orientation.w`, which is empty for
+    /// the length of the whole message.
+    pub path: String,
+    /// Range of bytes, relative to the start of the data.
+    pub range: Range<usize>,
+    /// The bytes in hexadecimal, separated by spaces.
+    ///
+    /// Only the first 32 bytes are kept, followed by `..` if there are more.
+    pub hex: String,
+    /// What the bytes mean.
+    pub content: Content,
+}
+
+/// The meaning of a span of bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Content {
+    /// Length of the message, or number of items in an array.
+    Length(u32),
+    /// Value of a field.
+    Value(Value),
+}
+
+/// A value that could not be decoded.
+#[derive(Debug)]
+pub struct Failure {
+    /// Path of the field.
+    pub path: String,
+    /// Offset of the field's first byte.
+    pub position: usize,
+    /// Why decoding the field failed.
+    pub error: Error,
+}
+
+impl Failure {
+    fn new(path: &str, position: usize, error: Error) -> Box<Failure> {
+        Box::new(Failure {
+                     path: path.into(),
+                     position,
+                     error,
+                 })
+    }
+}
+
+impl Layout {
+    /// Whether the data matches the schema exactly.
+    pub fn is_complete(&self) -> bool {
+        self.failure.is_none() && self.trailing.is_none()
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for span in &self.spans {
+            write!(f,
+                   "{:>5}..{:<7} {:<26} ",
+                   span.range.start,
+                   span.range.end,
+                   shorten(&span.hex))?;
+            match span.content {
+                Content::Length(length) if span.path.is_empty() => {
+                    writeln!(f, "message length = {}", length)?
+                }
+                Content::Length(length) => writeln!(f, "{} length = {}", span.path, length)?,
+                Content::Value(ref value) => writeln!(f, "{} = {}", span.path, Describe(value))?,
+            }
+        }
+        if let Some(ref failure) = self.failure {
+            let path = if failure.path.is_empty() {
+                "message length"
+            } else {
+                &failure.path
+            };
+            writeln!(f, "{:>5}..        !! {}: {}", failure.position, path, failure.error)?;
+        }
+        if let Some(ref trailing) = self.trailing {
+            let within = match self.spans.first() {
+                Some(&Span { content: Content::Length(length), .. }) => length as usize + 4,
+                _ => self.length,
+            };
+            let reason = if trailing.start < within {
+                "not covered by any field"
+            } else {
+                "past the message length"
+            };
+            writeln!(f,
+                     "{:>5}..{:<7} {:<26} !! {} trailing bytes, {}",
+                     trailing.start,
+                     trailing.end,
+                     shorten(&self.trailing_hex),
+                     trailing.len(),
+                     reason)?;
+        }
+        Ok(())
+    }
+}
+
+type Walk = ::std::result::Result<usize, Box<Failure>>;
+
+struct Walker<'a> {
+    schema: &'a Schema,
+    bytes: &'a [u8],
+    spans: Vec<Span>,
+}
+
+impl<'a> Walker<'a> {
+    fn push(&mut self, path: &str, range: Range<usize>, content: Content) {
+        let hex = hex(&self.bytes[range.clone()]);
+        self.spans.push(Span {
+                            path: path.into(),
+                            range,
+                            hex,
+                            content,
+                        });
+    }
+
+    fn message(&mut self, name: &str, path: &str, mut position: usize) -> Walk {
+        let schema = self.schema;
+        let msg = schema.message(name).map_err(|err| Failure::new(path, position, err))?;
+        for field in &msg.fields {
+            let path = if path.is_empty() {
+                field.name.clone()
+            } else {
+                format!("{}.{}", path, field.name)
+            };
+            position = self.field(&field.field_type, &path, position)?;
+        }
+        Ok(position)
+    }
+
+    fn field(&mut self, field_type: &FieldType, path: &str, position: usize) -> Walk {
+        let fail = |err| Failure::new(path, position, err);
+        let (base, length, mut position) = match *field_type {
+            FieldType::Single(ref base) => return self.base(base, path, position),
+            FieldType::FixedArray(ref base, length) => (base, length, position),
+            FieldType::Array(ref base) => {
+                let length = read_length(self.bytes, position).map_err(fail)?;
+                self.push(path, position..position + 4, Content::Length(length as u32));
+                (base, length, position + 4)
+            }
+        };
+        if let BaseType::UInt8 | BaseType::Char = *base {
+            let end = advance(self.bytes, position, length)
+                .map_err(|err| Failure::new(path, position, err))?;
+            let value = Value::Bytes(self.bytes[position..end].to_vec());
+            self.push(path, position..end, Content::Value(value));
+            return Ok(end);
+        }
+        for index in 0..length {
+            position = self.base(base, &format!("{}[{}]", path, index), position)?;
+        }
+        Ok(position)
+    }
+
+    fn base(&mut self, base: &BaseType, path: &str, position: usize) -> Walk {
+        if let BaseType::Message(ref name) = *base {
+            return self.message(name, path, position);
+        }
+        let schema = self.schema;
+        let value = skip_base(schema, base, self.bytes, position).and_then(|end| {
+            let mut deserializer = Deserializer::new(&self.bytes[position..end],
+                                                     (end - position) as u32);
+            decode_base(schema, base, &mut deserializer).map(|value| (value, end))
+        });
+        let (value, end) = value.map_err(|err| Failure::new(path, position, err))?;
+        self.push(path, position..end, Content::Value(value));
+        Ok(end)
+    }
+}
+
+/// Number of bytes kept in the hex dumps of spans.
+const HEX_LIMIT: usize = 32;
+
+/// Number of bytes or characters shown in descriptions of values.
+const DESCRIBE_LIMIT: usize = 16;
+
+fn hex(bytes: &[u8]) -> String {
+    let shown = &bytes[..bytes.len().min(HEX_LIMIT)];
+    let mut hex = shown.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(" ");
+    if shown.len() < bytes.len() {
+        hex.push_str(" ..");
+    }
+    hex
+}
+
+fn trailing_hex(bytes: &[u8], trailing: Option<&Range<usize>>) -> String {
+    trailing.map(|range| hex(&bytes[range.clone()])).unwrap_or_default()
+}
+
+/// Keep long dumps from pushing the values off the screen.
+fn shorten(hex: &str) -> String {
+    if hex.len() <= 26 {
+        hex.into()
+    } else {
+        format!("{}..", &hex[..23])
+    }
+}
+
+/// Short, single line description of a builtin value.
+struct Describe<'a>(&'a Value);
+
+impl<'a> fmt::Display for Describe<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self.0 {
+            Value::Bool(v) => write!(f, "{}", v),
+            Value::I8(v) => write!(f, "{}", v),
+            Value::U8(v) => write!(f, "{}", v),
+            Value::I16(v) => write!(f, "{}", v),
+            Value::U16(v) => write!(f, "{}", v),
+            Value::I32(v) => write!(f, "{}", v),
+            Value::U32(v) => write!(f, "{}", v),
+            Value::I64(v) => write!(f, "{}", v),
+            Value::U64(v) => write!(f, "{}", v),
+            Value::F32(v) => write!(f, "{:?}", v),
+            Value::F64(v) => write!(f, "{:?}", v),
+            Value::String(ref v) => {
+                match v.char_indices().nth(DESCRIBE_LIMIT) {
+                    Some((end, _)) => write!(f, "{:?}.. ({} bytes)", &v[..end], v.len()),
+                    None => write!(f, "{:?}", v),
+                }
+            }
+            Value::Time(v) => write!(f, "{} secs, {} nsecs", v.sec, v.nsec),
+            Value::Duration(v) => write!(f, "{} secs, {} nsecs", v.sec, v.nsec),
+            Value::Bytes(ref v) if v.len() > DESCRIBE_LIMIT => {
+                write!(f, "[")?;
+                for byte in &v[..DESCRIBE_LIMIT] {
+                    write!(f, "{}, ", byte)?;
+                }
+                write!(f, "..] ({} bytes)", v.len())
+            }
+            Value::Bytes(ref v) => write!(f, "{:?}", v),
+            Value::Array(ref v) => write!(f, "{} items", v.len()),
+            Value::Message(ref v) => write!(f, "{} fields", v.len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::to_vec;
+
+    fn pose_array_schema() -> Schema {
+        Schema::from_definition("geometry_msgs/PoseArray",
+                                include_str!("../datatests/pose_array_message_definition.txt"))
+            .unwrap()
+    }
+
+    fn paths(layout: &Layout) -> Vec<&str> {
+        layout.spans.iter().map(|span| span.path.as_str()).collect()
+    }
+
+    #[test]
+    fn maps_recorded_messages() {
+        let schema = pose_array_schema();
+        let data = include_bytes!("../datatests/pose_array_msg.bin");
+        let map = layout(&schema, data);
+        assert!(map.is_complete());
+        assert_eq!(map.spans.len(), 1 + 3 + 1 + 5 * 7);
+        assert_eq!(&paths(&map)[..7],
+                   &["",
+                     "header.seq",
+                     "header.stamp",
+                     "header.frame_id",
+                     "poses",
+                     "poses[0].position.x",
+                     "poses[0].position.y"]);
+        assert_eq!(map.spans[4].content, Content::Length(5));
+        let last = map.spans.last().unwrap();
+        assert_eq!(last.path, "poses[4].orientation.w");
+        assert_eq!(last.range, data.len() - 8..data.len());
+        assert_eq!(last.content, Content::Value(Value::F64(7.0)));
+        for pair in map.spans.windows(2) {
+            assert_eq!(pair[0].range.end, pair[1].range.start);
+        }
+    }
+
+    #[test]
+    fn marks_failures() {
+        let schema = pose_array_schema();
+        let data = include_bytes!("../datatests/pose_array_msg.bin");
+        let map = layout(&schema, &data[..data.len() - 10]);
+        let failure = map.failure.unwrap();
+        assert_eq!(failure.path, "poses[4].orientation.z");
+        assert_eq!(failure.position, data.len() - 16);
+        match *failure.error.kind() {
+            ErrorKind::EndOfBuffer => {}
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+        assert_eq!(map.spans.last().unwrap().path, "poses[4].orientation.y");
+
+        let map = layout(&schema, &data[..2]);
+        assert!(map.spans.is_empty());
+        assert_eq!(map.failure.unwrap().path, "");
+
+        let schema = Schema::from_definition("my_msgs/Text", "string text\n").unwrap();
+        let map = layout(&schema, &[6, 0, 0, 0, 2, 0, 0, 0, 0xc3, 0x28]);
+        match *map.failure.unwrap().error.kind() {
+            ErrorKind::BadStringData => {}
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn marks_trailing_bytes() {
+        let schema = Schema::from_definition("my_msgs/Pair", "uint16 a\nint8[] b\n").unwrap();
+        let data = to_vec(&(258u16, vec![-1i8])).unwrap();
+
+        let mut longer = data.clone();
+        longer[0] += 2;
+        longer.extend(&[7, 8]);
+        let map = layout(&schema, &longer);
+        assert_eq!(map.trailing, Some(data.len()..data.len() + 2));
+        assert_eq!(map.to_string(),
+                   "    0..4       09 00 00 00                message length = 9\n\
+                    \x20   4..6       02 01                      a = 258\n\
+                    \x20   6..10      01 00 00 00                b length = 1\n\
+                    \x20  10..11      ff                         b[0] = -1\n\
+                    \x20  11..13      07 08                      \
+                    !! 2 trailing bytes, not covered by any field\n");
+
+        let mut longer = data.clone();
+        longer.push(7);
+        let map = layout(&schema, &longer);
+        assert_eq!(map.trailing, Some(data.len()..data.len() + 1));
+        assert!(map.to_string().ends_with("past the message length\n"));
+    }
+
+    #[test]
+    fn tells_overflows_apart() {
+        let schema = Schema::from_definition("my_msgs/Pair", "uint16 a\nint8[] b\n").unwrap();
+        let mut data = to_vec(&(258u16, vec![-1i8])).unwrap();
+        data[0] -= 1;
+        let map = layout(&schema, &data);
+        assert!(map.trailing.is_none());
+        let failure = map.failure.unwrap();
+        assert_eq!(failure.path, "b[0]");
+        match *failure.error.kind() {
+            ErrorKind::Overflow => {}
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn shortens_long_dumps() {
+        let schema = Schema::from_definition("my_msgs/Blob", "uint8[] data\ntime stamp\n")
+            .unwrap();
+        let data = to_vec(&((0..20).collect::<Vec<u8>>(), (1u32, 2u32))).unwrap();
+        assert_eq!(layout(&schema, &data).to_string(),
+                   "    0..4       20 00 00 00                message length = 32\n\
+                    \x20   4..8       14 00 00 00                data length = 20\n\
+                    \x20   8..28      00 01 02 03 04 05 06 07..  data = [0, 1, 2, 3, 4, 5, 6, \
+                    7, 8, 9, 10, 11, 12, 13, 14, 15, ..] (20 bytes)\n\
+                    \x20  28..36      01 00 00 00 02 00 00 00    stamp = 1 secs, 2 nsecs\n");
+    }
+
+    #[test]
+    fn truncates_big_values() {
+        let schema = Schema::from_definition("my_msgs/Blob", "string name\nuint8[] data\n")
+            .unwrap();
+        let name = "é".repeat(20);
+        let data = to_vec(&(&name, vec![7u8; 1 << 20])).unwrap();
+        let map = layout(&schema, &data);
+        assert!(map.is_complete());
+        assert_eq!(map.spans[1].hex.len(), HEX_LIMIT * 3 + 2);
+        assert!(map.spans[3].hex.ends_with("07 07 .."));
+        let dump = map.to_string();
+        assert!(dump.len() < 500);
+        assert!(dump.contains(&format!("name = {:?}.. (40 bytes)\n", "é".repeat(16))));
+        assert!(dump.contains("data = [7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, ..] \
+                               (1048576 bytes)\n"));
+    }
+}
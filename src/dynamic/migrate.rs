@@ -0,0 +1,516 @@
+use super::{decode, encode, Value};
+use super::super::de::from_slice;
+use super::super::error::{ErrorKind, Result};
+use super::super::message::RosMessage;
+use super::super::schema::{BaseType, FieldType, Schema};
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+
+type Conversion = Box<dyn Fn(Value) -> Result<Value> + Send + Sync>;
+
+/// Rule for migrating values of one version of a message into the next.
+///
+/// Rules are keyed by the MD5 sum of the old message, just like `rosbag`'s
+/// migration rules. They are made out of steps, applied in order to the
+/// decoded old value. Fields are addressed by paths like `pose.position.x`,
+/// where `[]` applies the rest of the path to all items of an array, like
+/// in `poses[].position.x`.
+///
+/// After all steps are applied, fields missing from the new message get
+/// dropped, and the remaining ones get ordered like in the new message.
+///
+/// # Examples
+///
+/// ```rust
+/// # use serde_rosmsg::dynamic::{Rule, Value};
+/// # use serde_rosmsg::schema::Schema;
+/// let old = Schema::from_definition("my_msgs/Reading", "float32 temp\nstring id\n").unwrap();
+/// let new = Schema::from_definition("my_msgs/Reading",
+///                                   "uint8 quality\nfloat64 kelvin\n").unwrap();
+/// let rule = Rule::new(old, new)
+///     .unwrap()
+///     .rename("temp", "kelvin")
+///     .scale("kelvin", 1.0, 273.15)
+///     .default("quality", Value::U8(100));
+/// let value = Value::Message(vec![("temp".into(), Value::F32(20.0)),
+///                                 ("id".into(), Value::String("A".into()))]);
+/// assert_eq!(rule.apply(value).unwrap(),
+///            Value::Message(vec![("quality".into(), Value::U8(100)),
+///                                ("kelvin".into(), Value::F64(293.15))]));
+/// ```
+pub struct Rule {
+    old: Schema,
+    new: Schema,
+    old_md5sum: String,
+    new_md5sum: String,
+    steps: Vec<Step>,
+}
+
+enum Step {
+    Rename(String, String),
+    Default(String, Value),
+    Scale(String, f64, f64),
+    Convert(String, Conversion),
+}
+
+impl Rule {
+    /// Create a rule migrating values of the old message into the new one.
+    ///
+    /// Fails if the MD5 sum of either message cannot be calculated.
+    pub fn new(old: Schema, new: Schema) -> Result<Rule> {
+        Ok(Rule {
+               old_md5sum: old.md5sum()?,
+               new_md5sum: new.md5sum()?,
+               old,
+               new,
+               steps: Vec::new(),
+           })
+    }
+
+    /// The message that values get migrated from.
+    pub fn old_schema(&self) -> &Schema {
+        &self.old
+    }
+
+    /// The message that values get migrated into.
+    pub fn new_schema(&self) -> &Schema {
+        &self.new
+    }
+
+    /// Give the field at the path a new name.
+    pub fn rename(mut self, path: &str, name: &str) -> Rule {
+        self.steps.push(Step::Rename(path.into(), name.into()));
+        self
+    }
+
+    /// Add the field at the path with the given value, unless it exists.
+    pub fn default(mut self, path: &str, value: Value) -> Rule {
+        self.steps.push(Step::Default(path.into(), value));
+        self
+    }
+
+    /// Convert the number at the path between units, by multiplying it by
+    /// the factor and adding the offset.
+    ///
+    /// The result is a `float64`, which gets narrowed down when encoded.
+    pub fn scale(mut self, path: &str, factor: f64, offset: f64) -> Rule {
+        self.steps.push(Step::Scale(path.into(), factor, offset));
+        self
+    }
+
+    /// Replace the value at the path with the result of the closure.
+    ///
+    /// An empty path passes the whole message.
+    pub fn convert<F>(mut self, path: &str, conversion: F) -> Rule
+        where F: Fn(Value) -> Result<Value> + Send + Sync + 'static
+    {
+        self.steps.push(Step::Convert(path.into(), Box::new(conversion)));
+        self
+    }
+
+    /// Migrate a value of the old message into a value of the new one.
+    pub fn apply(&self, mut value: Value) -> Result<Value> {
+        for step in &self.steps {
+            match *step {
+                Step::Rename(ref path, ref name) => {
+                    let (parent, field) = split_path(path);
+                    visit(&mut value, &parent, "", &mut |parent, prefix| {
+                        match *parent {
+                            Value::Message(ref mut fields) => {
+                                match fields.iter_mut().find(|item| item.0 == field) {
+                                    Some(item) => item.0 = name.clone(),
+                                    None => bail!(ErrorKind::MissingField(child(prefix, field))),
+                                }
+                                Ok(())
+                            }
+                            ref value => bail!(not_message(prefix, value)),
+                        }
+                    })?
+                }
+                Step::Default(ref path, ref default) => {
+                    let (parent, field) = split_path(path);
+                    visit(&mut value, &parent, "", &mut |parent, prefix| {
+                        match *parent {
+                            Value::Message(ref mut fields) => {
+                                if !fields.iter().any(|item| item.0 == field) {
+                                    fields.push((field.into(), default.clone()));
+                                }
+                                Ok(())
+                            }
+                            ref value => bail!(not_message(prefix, value)),
+                        }
+                    })?
+                }
+                Step::Scale(ref path, factor, offset) => {
+                    visit(&mut value, &segments(path), "", &mut |value, prefix| {
+                        match value.as_f64() {
+                            Some(number) => *value = Value::F64(number * factor + offset),
+                            None => {
+                                bail!(ErrorKind::MismatchedValueType(prefix.into(),
+                                                                     "number".into(),
+                                                                     value.type_name().into()))
+                            }
+                        }
+                        Ok(())
+                    })?
+                }
+                Step::Convert(ref path, ref conversion) => {
+                    visit(&mut value, &segments(path), "", &mut |value, _| {
+                        let old = mem::replace(value, Value::Bool(false));
+                        *value = conversion(old)?;
+                        Ok(())
+                    })?
+                }
+            }
+        }
+        conform_message(&self.new, self.new.name(), value, "")
+    }
+}
+
+impl fmt::Debug for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Rule")
+            .field("old", &self.old.name())
+            .field("old_md5sum", &self.old_md5sum)
+            .field("new", &self.new.name())
+            .field("new_md5sum", &self.new_md5sum)
+            .field("steps", &self.steps.len())
+            .finish()
+    }
+}
+
+/// Migrates values of old messages, chaining rules over multiple versions.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[macro_use]
+/// # extern crate serde_derive;
+/// # extern crate serde_rosmsg;
+/// # use serde_rosmsg::RosMessage;
+/// # use serde_rosmsg::dynamic::{Migrator, Rule};
+/// # use serde_rosmsg::schema::Schema;
+/// # fn main() {
+/// #[derive(Debug, Serialize, Deserialize, PartialEq)]
+/// struct Reading {
+///     id: String,
+///     value: f64,
+/// }
+///
+/// impl RosMessage for Reading {
+///     const ROS_TYPE: &'static str = "my_msgs/Reading";
+///     const MD5SUM: &'static str = "8e2cadbbc2a90b873e389b0eeec7e9a3";
+///     const DEFINITION: &'static str = "string id\nfloat64 value\n";
+/// }
+///
+/// let v1 = Schema::from_definition("my_msgs/Reading", "string name\nfloat32 value\n").unwrap();
+/// let v2 = Schema::from_definition("my_msgs/Reading", "string id\nfloat32 value\n").unwrap();
+/// let v3 = Schema::from_definition("my_msgs/Reading", Reading::DEFINITION).unwrap();
+/// let v1_md5sum = v1.md5sum().unwrap();
+/// let migrator = Migrator::new()
+///     .rule(Rule::new(v1, v2.clone()).unwrap().rename("name", "id"))
+///     .rule(Rule::new(v2, v3).unwrap());
+///
+/// let data = [9, 0, 0, 0, 1, 0, 0, 0, 65, 0, 0, 192, 63];
+/// let reading: Reading = migrator.decode(&v1_md5sum, &data).unwrap();
+/// assert_eq!(reading, Reading { id: "A".into(), value: 1.5 });
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct Migrator {
+    rules: HashMap<String, Rule>,
+}
+
+impl Migrator {
+    /// Create a migrator without any rules.
+    pub fn new() -> Migrator {
+        Migrator::default()
+    }
+
+    /// Add a rule, replacing any rule for the same old MD5 sum.
+    pub fn rule(mut self, rule: Rule) -> Migrator {
+        self.add(rule);
+        self
+    }
+
+    /// Add a rule, replacing any rule for the same old MD5 sum.
+    pub fn add(&mut self, rule: Rule) {
+        self.rules.insert(rule.old_md5sum.clone(), rule);
+    }
+
+    /// Migrate a value with one MD5 sum into a value with another.
+    pub fn migrate(&self, value: Value, from: &str, to: &str) -> Result<Value> {
+        self.chain(from, to)?
+            .into_iter()
+            .try_fold(value, |value, rule| rule.apply(value))
+    }
+
+    /// Decode ROSMSG data of any known version of the message.
+    ///
+    /// Data that already matches the message's MD5 sum is decoded directly.
+    pub fn decode<T: RosMessage>(&self, md5sum: &str, bytes: &[u8]) -> Result<T> {
+        let chain = self.chain(md5sum, T::MD5SUM)?;
+        let (first, last) = match (chain.first(), chain.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return from_slice(bytes),
+        };
+        let value = chain
+            .iter()
+            .try_fold(decode(&first.old, bytes)?, |value, rule| rule.apply(value))?;
+        from_slice(&encode(&last.new, &value)?)
+    }
+
+    fn chain(&self, from: &str, to: &str) -> Result<Vec<&Rule>> {
+        let mut chain = Vec::new();
+        let mut md5sum = from;
+        while md5sum != to {
+            // Rules leading in circles never reach the target
+            match self.rules.get(md5sum) {
+                Some(rule) if chain.len() < self.rules.len() => {
+                    chain.push(rule);
+                    md5sum = &rule.new_md5sum;
+                }
+                _ => bail!(ErrorKind::MissingMigration(from.into(), to.into())),
+            }
+        }
+        Ok(chain)
+    }
+}
+
+fn child(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.into()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+fn not_message(path: &str, value: &Value) -> ErrorKind {
+    ErrorKind::MismatchedValueType(path.into(), "message".into(), value.type_name().into())
+}
+
+fn segments(path: &str) -> Vec<&str> {
+    path.split('.').filter(|segment| !segment.is_empty()).collect()
+}
+
+fn split_path(path: &str) -> (Vec<&str>, &str) {
+    let mut segments = segments(path);
+    let field = segments.pop().unwrap_or_default();
+    (segments, field)
+}
+
+/// Call the closure on all values at the path, passing their full paths.
+fn visit<F>(value: &mut Value, segments: &[&str], prefix: &str, visitor: &mut F) -> Result<()>
+    where F: FnMut(&mut Value, &str) -> Result<()>
+{
+    let (segment, rest) = match segments.split_first() {
+        Some(split) => split,
+        None => return visitor(value, prefix),
+    };
+    let (name, all) = match segment.strip_suffix("[]") {
+        Some(name) => (name, true),
+        None => (*segment, false),
+    };
+    let prefix = child(prefix, name);
+    let field = match value.get_mut(name) {
+        Some(field) => field,
+        None => bail!(ErrorKind::MissingField(prefix)),
+    };
+    if !all {
+        return visit(field, rest, &prefix, visitor);
+    }
+    match *field {
+        Value::Array(ref mut items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                visit(item, rest, &format!("{}[{}]", prefix, index), visitor)?;
+            }
+            Ok(())
+        }
+        ref field => {
+            bail!(ErrorKind::MismatchedValueType(prefix,
+                                                 "array".into(),
+                                                 field.type_name().into()))
+        }
+    }
+}
+
+fn conform_message(schema: &Schema, name: &str, value: Value, path: &str) -> Result<Value> {
+    let mut fields = match value {
+        Value::Message(fields) => fields,
+        value => return Ok(value),
+    };
+    let msg = schema.message(name)?;
+    let mut output = Vec::with_capacity(msg.fields.len());
+    for field in &msg.fields {
+        let path = child(path, &field.name);
+        let value = match fields.iter().position(|item| item.0 == field.name) {
+            Some(index) => fields.swap_remove(index).1,
+            None => bail!(ErrorKind::MissingField(path)),
+        };
+        let value = conform_field(schema, &field.field_type, value, &path)?;
+        output.push((field.name.clone(), value));
+    }
+    Ok(Value::Message(output))
+}
+
+fn conform_field(schema: &Schema,
+                 field_type: &FieldType,
+                 value: Value,
+                 path: &str)
+                 -> Result<Value> {
+    let base = match *field_type {
+        FieldType::Single(BaseType::Message(ref name)) => {
+            return conform_message(schema, name, value, path)
+        }
+        FieldType::Single(..) => return Ok(value),
+        FieldType::Array(ref base) |
+        FieldType::FixedArray(ref base, _) => base,
+    };
+    match (base, value) {
+        // Byte arrays widened into other integers need their items split up
+        (BaseType::UInt8, value) |
+        (BaseType::Char, value) => Ok(value),
+        (_, Value::Bytes(items)) => Ok(Value::Array(items.into_iter().map(Value::U8).collect())),
+        (BaseType::Message(name), Value::Array(items)) => {
+            items
+                .into_iter()
+                .enumerate()
+                .map(|(index, item)| {
+                         conform_message(schema, name, item, &format!("{}[{}]", path, index))
+                     })
+                .collect::<Result<Vec<_>>>()
+                .map(Value::Array)
+        }
+        (_, value) => Ok(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::to_vec;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Reading {
+        celsius: f64,
+        label: String,
+        quality: u8,
+        tags: Vec<String>,
+    }
+
+    impl RosMessage for Reading {
+        const ROS_TYPE: &'static str = "my_msgs/Reading";
+        const MD5SUM: &'static str = "87dd11d8da0c74e3ed5281573390c6d3";
+        const DEFINITION: &'static str = "float64 celsius\nstring label\nuint8 quality\n\
+                                          string[] tags\n";
+    }
+
+    fn reading(definition: &str) -> Schema {
+        Schema::from_definition("my_msgs/Reading", definition).unwrap()
+    }
+
+    fn migrator() -> Migrator {
+        let v1 = reading("float32 fahrenheit\nstring name\n");
+        let v2 = reading("float64 celsius\nstring name\nuint8 quality\n");
+        let v3 = reading(Reading::DEFINITION);
+        Migrator::new()
+            .rule(Rule::new(v1, v2.clone())
+                      .unwrap()
+                      .rename("fahrenheit", "celsius")
+                      .scale("celsius", 5.0 / 9.0, -160.0 / 9.0)
+                      .default("quality", Value::U8(100)))
+            .rule(Rule::new(v2, v3)
+                      .unwrap()
+                      .rename("name", "label")
+                      .convert("", |mut value| {
+                let tags = value
+                    .get("label")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .split('/')
+                    .map(|tag| Value::String(tag.into()))
+                    .collect();
+                if let Value::Message(ref mut fields) = value {
+                    fields.push(("tags".into(), Value::Array(tags)));
+                }
+                Ok(value)
+            }))
+    }
+
+    #[test]
+    fn chains_rules_over_versions() {
+        assert_eq!(reading(Reading::DEFINITION).md5sum().unwrap(), Reading::MD5SUM);
+        let migrator = migrator();
+        let v1 = reading("float32 fahrenheit\nstring name\n").md5sum().unwrap();
+        let v2 = reading("float64 celsius\nstring name\nuint8 quality\n").md5sum().unwrap();
+        let expected = Reading {
+            celsius: 100.0,
+            label: "lab/a".into(),
+            quality: 100,
+            tags: vec!["lab".into(), "a".into()],
+        };
+        let data = to_vec(&(212.0f32, "lab/a")).unwrap();
+        assert_eq!(migrator.decode::<Reading>(&v1, &data).unwrap(), expected);
+        let data = to_vec(&(100.0f64, "lab/a", 100u8)).unwrap();
+        assert_eq!(migrator.decode::<Reading>(&v2, &data).unwrap(), expected);
+        let data = to_vec(&expected).unwrap();
+        assert_eq!(migrator.decode::<Reading>(Reading::MD5SUM, &data).unwrap(),
+                   expected);
+
+        let value = Value::Message(vec![("name".into(), Value::String("x".into())),
+                                        ("fahrenheit".into(), Value::F32(32.0))]);
+        assert_eq!(migrator.migrate(value, &v1, &v2).unwrap(),
+                   Value::Message(vec![("celsius".into(), Value::F64(0.0)),
+                                       ("name".into(), Value::String("x".into())),
+                                       ("quality".into(), Value::U8(100))]));
+    }
+
+    #[test]
+    fn applies_steps_to_nested_fields() {
+        let point = "\n================================================\
+                     ================================\n\
+                     MSG: my_msgs/Point\n";
+        let old = Schema::from_definition("my_msgs/Path",
+                                          &format!("Point[] points\n{}int16 x\nint16 y\n",
+                                                   point))
+            .unwrap();
+        let new = Schema::from_definition("my_msgs/Path",
+                                          &format!("Point[] points\n{}float64 x\n\
+                                                    float64 y\nuint8[] z\n",
+                                                   point))
+            .unwrap();
+        let rule = Rule::new(old.clone(), new.clone())
+            .unwrap()
+            .scale("points[].x", 0.5, 0.0)
+            .default("points[].z", Value::Bytes(vec![1]));
+        let value = decode(&old, &to_vec(&vec![(1i16, 2i16), (3, 4)]).unwrap()).unwrap();
+        let value = rule.apply(value).unwrap();
+        assert_eq!(encode(&new, &value).unwrap(),
+                   to_vec(&vec![(0.5f64, 2.0f64, vec![1u8]), (1.5, 4.0, vec![1])]).unwrap());
+    }
+
+    #[test]
+    fn reports_failed_migrations() {
+        let migrator = migrator();
+        let unknown = reading("int8 other\n").md5sum().unwrap();
+        assert_eq!(migrator.decode::<Reading>(&unknown, &[1, 0, 0, 0, 1]).unwrap_err().to_string(),
+                   format!("No migration leads from MD5 sum {} to {}", unknown, Reading::MD5SUM));
+
+        let old = reading("int8 other\n");
+        let rule = Rule::new(old.clone(), reading("int8 other\nint8 more\n")).unwrap();
+        let value = Value::Message(vec![("other".into(), Value::I8(1))]);
+        assert_eq!(rule.apply(value.clone()).unwrap_err().to_string(),
+                   "Message is missing the field more");
+        let rule = Rule::new(old.clone(), old.clone()).unwrap().rename("another", "more");
+        assert_eq!(rule.apply(value.clone()).unwrap_err().to_string(),
+                   "Message is missing the field another");
+        let rule = Rule::new(old.clone(), old.clone()).unwrap().scale("other[].x", 1.0, 0.0);
+        assert_eq!(rule.apply(value).unwrap_err().to_string(),
+                   "Value at other should be array, but is int8");
+
+        let md5sum = old.md5sum().unwrap();
+        let looping = Migrator::new().rule(Rule::new(old.clone(), old).unwrap());
+        assert!(looping.migrate(Value::Bool(true), &md5sum, "other").is_err());
+    }
+}
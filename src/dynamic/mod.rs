@@ -0,0 +1,223 @@
+//! Work with messages whose types are only known at runtime.
+//!
+//! ROSMSG data is not self-describing, so decoding it requires knowing the
+//! layout of the message. When that layout is only available at runtime,
+//! like the `message_definition` within a connection header or a bag file,
+//! messages can be decoded into a [`Value`](enum.Value.html) tree, guided by
+//! a parsed [`Schema`](../schema/struct.Schema.html). Values can also be
+//! built or edited at runtime, and encoded back into ROSMSG data.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use serde_rosmsg::dynamic::{decode, encode, Value};
+//! # use serde_rosmsg::schema::Schema;
+//! let schema = Schema::from_definition("my_msgs/Reading",
+//!                                      "string sensor\nfloat32[] values\n").unwrap();
+//! let data = [19, 0, 0, 0,
+//!             3, 0, 0, 0, 73, 77, 85,
+//!             2, 0, 0, 0, 0, 0, 128, 63, 0, 0, 0, 64];
+//! let value = decode(&schema, &data).unwrap();
+//! assert_eq!(value.get("sensor"), Some(&Value::String("IMU".into())));
+//! assert_eq!(value.get("values"),
+//!            Some(&Value::Array(vec![Value::F32(1.0), Value::F32(2.0)])));
+//! assert_eq!(encode(&schema, &value).unwrap(), data.to_vec());
+//! ```
+
+use super::types::{Duration, Time};
+
+pub use self::de::{decode, decode_reader};
+pub use self::deserializer::{from_reader, from_slice, SchemaDeserializer};
+pub use self::echo::Echo;
+pub use self::layout::{layout, Content, Failure, Layout, Span};
+pub use self::migrate::{Migrator, Rule};
+pub use self::path::{patch, FieldPath};
+pub use self::ser::{encode, encode_writer};
+pub use self::transcode::transcode;
+
+mod de;
+mod deserializer;
+mod echo;
+mod layout;
+mod migrate;
+mod path;
+mod ser;
+mod transcode;
+
+/// A dynamically typed ROS value.
+///
+/// Each builtin type has its own variant, so values keep the exact type
+/// they were decoded as. Arrays of `uint8` and `char` are kept as
+/// [`Bytes`](#variant.Bytes), since they usually hold big blobs of data.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    I8(i8),
+    U8(u8),
+    I16(i16),
+    U16(u16),
+    I32(i32),
+    U32(u32),
+    I64(i64),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    String(String),
+    Time(Time),
+    Duration(Duration),
+    /// Array of `uint8` or `char` values.
+    Bytes(Vec<u8>),
+    /// Array of any other type.
+    Array(Vec<Value>),
+    /// Message, with its fields listed in order of definition.
+    Message(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Get a field of a message by name.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.as_fields()
+            .and_then(|fields| fields.iter().find(|field| field.0 == name))
+            .map(|field| &field.1)
+    }
+
+    /// Get a mutable reference to a field of a message by name.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Value> {
+        match *self {
+            Value::Message(ref mut fields) => {
+                fields.iter_mut().find(|field| field.0 == name).map(|field| &mut field.1)
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the value as a boolean, if it is one.
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Value::Bool(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Get the value as a signed integer, if it is an integer that fits.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::I8(value) => Some(value as i64),
+            Value::U8(value) => Some(value as i64),
+            Value::I16(value) => Some(value as i64),
+            Value::U16(value) => Some(value as i64),
+            Value::I32(value) => Some(value as i64),
+            Value::U32(value) => Some(value as i64),
+            Value::I64(value) => Some(value),
+            Value::U64(value) if value <= i64::MAX as u64 => Some(value as i64),
+            _ => None,
+        }
+    }
+
+    /// Get the value as an unsigned integer, if it is an integer that fits.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Value::U64(value) => Some(value),
+            ref value => {
+                match value.as_i64() {
+                    Some(value) if value >= 0 => Some(value as u64),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// Get the value as a floating point number, if it is a number.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::F32(value) => Some(value as f64),
+            Value::F64(value) => Some(value),
+            Value::U64(value) => Some(value as f64),
+            ref value => value.as_i64().map(|value| value as f64),
+        }
+    }
+
+    /// Get the value as a string slice, if it is a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Value::String(ref value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Get the value as a byte slice, if it is an array of bytes.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match *self {
+            Value::Bytes(ref value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Get the items of the value, if it is an array other than bytes.
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match *self {
+            Value::Array(ref value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Get the fields of the value, if it is a message.
+    pub fn as_fields(&self) -> Option<&[(String, Value)]> {
+        match *self {
+            Value::Message(ref value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Name of the kind of value, used for error messages.
+    pub(crate) fn type_name(&self) -> &'static str {
+        match *self {
+            Value::Bool(..) => "bool",
+            Value::I8(..) => "int8",
+            Value::U8(..) => "uint8",
+            Value::I16(..) => "int16",
+            Value::U16(..) => "uint16",
+            Value::I32(..) => "int32",
+            Value::U32(..) => "uint32",
+            Value::I64(..) => "int64",
+            Value::U64(..) => "uint64",
+            Value::F32(..) => "float32",
+            Value::F64(..) => "float64",
+            Value::String(..) => "string",
+            Value::Time(..) => "time",
+            Value::Duration(..) => "duration",
+            Value::Bytes(..) => "byte array",
+            Value::Array(..) => "array",
+            Value::Message(..) => "message",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accesses_fields() {
+        let mut value = Value::Message(vec![("x".into(), Value::F64(1.5)),
+                                            ("name".into(), Value::String("a".into()))]);
+        assert_eq!(value.get("x").and_then(Value::as_f64), Some(1.5));
+        assert_eq!(value.get("name").and_then(Value::as_str), Some("a"));
+        assert_eq!(value.get("y"), None);
+        *value.get_mut("x").unwrap() = Value::F64(2.5);
+        assert_eq!(value.get("x"), Some(&Value::F64(2.5)));
+        assert_eq!(Value::F64(1.0).get("x"), None);
+    }
+
+    #[test]
+    fn converts_numbers() {
+        assert_eq!(Value::U8(200).as_i64(), Some(200));
+        assert_eq!(Value::I8(-2).as_u64(), None);
+        assert_eq!(Value::U64(u64::MAX).as_u64(), Some(u64::MAX));
+        assert_eq!(Value::U64(u64::MAX).as_i64(), None);
+        assert_eq!(Value::I32(-3).as_f64(), Some(-3.0));
+        assert_eq!(Value::F32(0.5).as_f64(), Some(0.5));
+        assert_eq!(Value::F32(0.5).as_i64(), None);
+        assert_eq!(Value::Bool(true).as_f64(), None);
+    }
+}
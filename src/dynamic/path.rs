@@ -0,0 +1,577 @@
+use byteorder::{ByteOrder, LittleEndian};
+use super::Value;
+use super::de::{decode_base, decode_field};
+use super::ser::{encode_base, encode_field};
+use super::super::de::Deserializer;
+use super::super::error::{ErrorKind, Result};
+use super::super::schema::{BaseType, FieldType, Schema};
+use super::super::ser::Serializer;
+use std::fmt;
+use std::ops::Range;
+
+/// A compiled path to a single field within a message, like
+/// `poses[3].orientation.w`.
+///
+/// The path is checked against the schema once, upfront. Reading the field
+/// then only skips over the data that precedes it, jumping over fields of
+/// constant size in one step, and decodes nothing but the field itself.
+///
+/// Paths consist of field names separated by `.`, where arrays can be
+/// indexed with `[index]`. Paths can end with any field, including arrays
+/// and nested messages.
+///
+/// # Examples
+///
+/// ```rust
+/// # use serde_rosmsg::dynamic::{FieldPath, Value};
+/// # use serde_rosmsg::schema::Schema;
+/// let schema = Schema::from_definition("my_msgs/Reading",
+///                                      "string sensor\nfloat32[] values\n").unwrap();
+/// let data = [19, 0, 0, 0,
+///             3, 0, 0, 0, 73, 77, 85,
+///             2, 0, 0, 0, 0, 0, 128, 63, 0, 0, 0, 64];
+/// let path = FieldPath::new(&schema, "values[1]").unwrap();
+/// assert_eq!(path.get(&data).unwrap(), Value::F32(2.0));
+/// assert_eq!(path.range(&data).unwrap(), 19..23);
+///
+/// assert!(FieldPath::new(&schema, "values[1].x").is_err());
+/// assert!(FieldPath::new(&schema, "value").is_err());
+/// ```
+#[derive(Clone, Debug)]
+pub struct FieldPath {
+    path: String,
+    schema: Schema,
+    steps: Vec<Step>,
+    target: Target,
+}
+
+#[derive(Clone, Debug)]
+enum Step {
+    /// Skip a constant number of bytes.
+    Skip(usize),
+    /// Skip a field whose size depends on the data.
+    SkipField(FieldType),
+    /// Jump to an item of an array.
+    Item {
+        base: BaseType,
+        index: usize,
+        length: Option<usize>,
+        size: Option<usize>,
+        path: String,
+    },
+}
+
+#[derive(Clone, Debug)]
+enum Target {
+    Field(FieldType),
+    Item(BaseType),
+}
+
+impl FieldPath {
+    /// Compile the path to a field within the root message of the schema.
+    ///
+    /// Fails if the path is malformed, or does not lead to a field.
+    pub fn new(schema: &Schema, path: &str) -> Result<FieldPath> {
+        let fail = |reason: String| ErrorKind::BadFieldPath(path.into(), reason);
+        let mut steps = Vec::new();
+        let mut message = schema.name().to_owned();
+        let mut target = None;
+        let mut prefix = String::new();
+        for segment in path.split('.') {
+            if target.is_some() {
+                bail!(fail(format!("{} is not a message", prefix)));
+            }
+            let (name, index) = parse_segment(segment).ok_or_else(|| {
+                    fail(format!("{:?} is not a field name", segment))
+                })?;
+            if !prefix.is_empty() {
+                prefix.push('.');
+            }
+            prefix.push_str(name);
+            let msg = schema.message(&message)?;
+            let position = msg.fields
+                .iter()
+                .position(|field| field.name == name)
+                .ok_or_else(|| fail(format!("{} has no field {}", message, name)))?;
+            for field in &msg.fields[..position] {
+                let size = match field.field_type {
+                    FieldType::Single(ref base) => schema.size_of(base),
+                    FieldType::FixedArray(ref base, length) => {
+                        match schema.size_of(base) {
+                            Some(size) => {
+                                Some(size.checked_mul(length).ok_or(ErrorKind::Overflow)?)
+                            }
+                            None => None,
+                        }
+                    }
+                    FieldType::Array(..) => None,
+                };
+                match (size, steps.last_mut()) {
+                    (Some(size), Some(&mut Step::Skip(ref mut skipped))) => {
+                        *skipped = skipped.checked_add(size).ok_or(ErrorKind::Overflow)?
+                    }
+                    (Some(size), _) => steps.push(Step::Skip(size)),
+                    (None, _) => steps.push(Step::SkipField(field.field_type.clone())),
+                }
+            }
+            let field_type = &msg.fields[position].field_type;
+            let base = match (field_type, index) {
+                (FieldType::Single(base), None) => base,
+                (_, None) => {
+                    target = Some(Target::Field(field_type.clone()));
+                    continue;
+                }
+                (FieldType::Single(..), Some(..)) => {
+                    bail!(fail(format!("{} is not an array", prefix)))
+                }
+                (FieldType::FixedArray(_, length), Some(index)) if index >= *length => {
+                    bail!(fail(format!("{} has only {} items", prefix, length)))
+                }
+                (FieldType::Array(base), Some(index)) |
+                (FieldType::FixedArray(base, _), Some(index)) => {
+                    prefix.push_str(&format!("[{}]", index));
+                    steps.push(Step::Item {
+                                   base: base.clone(),
+                                   index,
+                                   length: match *field_type {
+                                       FieldType::FixedArray(_, length) => Some(length),
+                                       _ => None,
+                                   },
+                                   size: schema.size_of(base),
+                                   path: prefix.clone(),
+                               });
+                    base
+                }
+            };
+            match *base {
+                BaseType::Message(ref name) => message = name.clone(),
+                _ => {
+                    target = Some(match index {
+                                      Some(..) => Target::Item(base.clone()),
+                                      None => Target::Field(field_type.clone()),
+                                  })
+                }
+            }
+        }
+        let target = match target {
+            Some(target) => target,
+            None => Target::Item(BaseType::Message(message)),
+        };
+        Ok(FieldPath {
+               path: path.into(),
+               schema: schema.clone(),
+               steps,
+               target,
+           })
+    }
+
+    /// The path, as it was given.
+    pub fn as_str(&self) -> &str {
+        &self.path
+    }
+
+    /// Read the value of the field from bytes of ROSMSG data.
+    ///
+    /// Just like with `decode`, the data needs to start with the length of
+    /// the message.
+    pub fn get(&self, bytes: &[u8]) -> Result<Value> {
+        let range = self.range(bytes)?;
+        let length = range.len() as u32;
+        let mut deserializer = Deserializer::new(&bytes[range], length);
+        match self.target {
+            Target::Field(ref field_type) => {
+                decode_field(&self.schema, field_type, &mut deserializer)
+            }
+            Target::Item(ref base) => decode_base(&self.schema, base, &mut deserializer),
+        }
+    }
+
+    /// Find the range of bytes taken by the field within ROSMSG data.
+    ///
+    /// The range includes the length prefixes of strings and variable
+    /// size arrays, and is relative to the start of the data, which needs
+    /// to start with the length of the message.
+    pub fn range(&self, bytes: &[u8]) -> Result<Range<usize>> {
+        let bytes = message_bytes(bytes)?;
+        let mut position = 4;
+        for step in &self.steps {
+            position = match *step {
+                Step::Skip(size) => advance(bytes, position, size)?,
+                Step::SkipField(ref field_type) => {
+                    skip_field(&self.schema, field_type, bytes, position)?
+                }
+                Step::Item { ref base, index, length, size, ref path } => {
+                    let (length, position) = match length {
+                        Some(length) => (length, position),
+                        None => (read_length(bytes, position)?, position + 4),
+                    };
+                    if index >= length {
+                        bail!(ErrorKind::ArrayIndexOutOfBounds(path.clone(), length));
+                    }
+                    match size {
+                        Some(size) => {
+                            let size = size.checked_mul(index).ok_or(ErrorKind::Overflow)?;
+                            advance(bytes, position, size)?
+                        }
+                        None => skip_items(&self.schema, base, index, bytes, position)?,
+                    }
+                }
+            };
+        }
+        let end = match self.target {
+            Target::Field(ref field_type) => skip_field(&self.schema, field_type, bytes, position)?,
+            Target::Item(ref base) => skip_base(&self.schema, base, bytes, position)?,
+        };
+        Ok(position..end)
+    }
+}
+
+impl fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.path)
+    }
+}
+
+/// Replace the value of a field within bytes of ROSMSG data.
+///
+/// Only the field itself gets encoded anew. If that changes its size, the
+/// data after it gets shifted, and the length of the message gets updated.
+/// Arrays are prefixed with their number of items rather than their size,
+/// so there are no other length prefixes to update.
+///
+/// The data is left untouched if the value does not match the field, or
+/// the field cannot be found.
+///
+/// # Examples
+///
+/// ```rust
+/// # use serde_rosmsg::dynamic::{patch, FieldPath, Value};
+/// # use serde_rosmsg::schema::Schema;
+/// let schema = Schema::from_definition("my_msgs/Reading",
+///                                      "string sensor\nfloat32[] values\n").unwrap();
+/// let mut data = vec![19, 0, 0, 0,
+///                     3, 0, 0, 0, 73, 77, 85,
+///                     2, 0, 0, 0, 0, 0, 128, 63, 0, 0, 0, 64];
+/// let path = FieldPath::new(&schema, "sensor").unwrap();
+/// patch(&mut data, &path, &Value::String("GPS2".into())).unwrap();
+/// assert_eq!(data,
+///            vec![20, 0, 0, 0,
+///                 4, 0, 0, 0, 71, 80, 83, 50,
+///                 2, 0, 0, 0, 0, 0, 128, 63, 0, 0, 0, 64]);
+///
+/// assert!(patch(&mut data, &path, &Value::U8(1)).is_err());
+/// ```
+pub fn patch(bytes: &mut Vec<u8>, path: &FieldPath, value: &Value) -> Result<()> {
+    let range = path.range(bytes)?;
+    let mut buffer = Vec::new();
+    match path.target {
+        Target::Field(ref field_type) => {
+            encode_field(&path.schema,
+                         field_type,
+                         value,
+                         &path.path,
+                         &mut Serializer::new(&mut buffer))?
+        }
+        Target::Item(ref base) => {
+            encode_base(&path.schema,
+                        base,
+                        value,
+                        &path.path,
+                        &mut Serializer::new(&mut buffer))?
+        }
+    }
+    let length = read_length(bytes, 0)? + buffer.len() - range.len();
+    bytes.splice(range, buffer);
+    LittleEndian::write_u32(bytes, length as u32);
+    Ok(())
+}
+
+fn parse_segment(segment: &str) -> Option<(&str, Option<usize>)> {
+    let (name, index) = match segment.find('[') {
+        Some(start) if segment.ends_with(']') => {
+            let index = segment[start + 1..segment.len() - 1].parse().ok()?;
+            (&segment[..start], Some(index))
+        }
+        Some(..) => return None,
+        None => (segment, None),
+    };
+    let valid = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic()) &&
+                name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid { Some((name, index)) } else { None }
+}
+
+/// Limit the bytes to the message, as given by its length prefix.
+pub(crate) fn message_bytes(bytes: &[u8]) -> Result<&[u8]> {
+    let length = read_length(bytes, 0)?;
+    Ok(&bytes[..::std::cmp::min(bytes.len(), length.saturating_add(4))])
+}
+
+pub(crate) fn read_length(bytes: &[u8], position: usize) -> Result<usize> {
+    advance(bytes, position, 4)?;
+    Ok(LittleEndian::read_u32(&bytes[position..]) as usize)
+}
+
+pub(crate) fn advance(bytes: &[u8], position: usize, size: usize) -> Result<usize> {
+    match position.checked_add(size) {
+        Some(end) if end <= bytes.len() => Ok(end),
+        _ => bail!(ErrorKind::EndOfBuffer),
+    }
+}
+
+/// Find the end of the field starting at the given position.
+pub(crate) fn skip_field(schema: &Schema,
+                         field_type: &FieldType,
+                         bytes: &[u8],
+                         position: usize)
+                         -> Result<usize> {
+    match *field_type {
+        FieldType::Single(ref base) => skip_base(schema, base, bytes, position),
+        FieldType::Array(ref base) => {
+            let length = read_length(bytes, position)?;
+            skip_items(schema, base, length, bytes, position + 4)
+        }
+        FieldType::FixedArray(ref base, length) => {
+            skip_items(schema, base, length, bytes, position)
+        }
+    }
+}
+
+fn skip_items(schema: &Schema,
+              base: &BaseType,
+              count: usize,
+              bytes: &[u8],
+              mut position: usize)
+              -> Result<usize> {
+    if let Some(size) = schema.size_of(base) {
+        let size = size.checked_mul(count).ok_or(ErrorKind::EndOfBuffer)?;
+        return advance(bytes, position, size);
+    }
+    for _ in 0..count {
+        position = skip_base(schema, base, bytes, position)?;
+    }
+    Ok(position)
+}
+
+pub(crate) fn skip_base(schema: &Schema,
+                        base: &BaseType,
+                        bytes: &[u8],
+                        position: usize)
+                        -> Result<usize> {
+    if let Some(size) = schema.size_of(base) {
+        return advance(bytes, position, size);
+    }
+    match *base {
+        BaseType::Message(ref name) => {
+            let mut position = position;
+            for field in &schema.message(name)?.fields {
+                position = skip_field(schema, &field.field_type, bytes, position)?;
+            }
+            Ok(position)
+        }
+        _ => {
+            let length = read_length(bytes, position)?;
+            advance(bytes, position + 4, length)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{decode, encode};
+    use super::super::super::to_vec;
+    use super::super::super::types::Time;
+
+    fn pose_array_schema() -> Schema {
+        Schema::from_definition("geometry_msgs/PoseArray",
+                                include_str!("../datatests/pose_array_message_definition.txt"))
+            .unwrap()
+    }
+
+    fn mixed_schema() -> Schema {
+        Schema::from_definition("my_msgs/Mixed",
+                                "string[] names\nuint8[] data\nPoint[] points\n\
+                                 Named[] named\nuint8 last\n\
+                                 ================================================\
+                                 ================================\n\
+                                 MSG: my_msgs/Point\nint16 x\nint16 y\n\
+                                 ================================================\
+                                 ================================\n\
+                                 MSG: my_msgs/Named\nstring name\nPoint point\n")
+            .unwrap()
+    }
+
+    fn mixed_data() -> Vec<u8> {
+        to_vec(&(vec!["a", "bc"],
+                 vec![1u8, 2, 3],
+                 vec![(4i16, 5i16), (6, 7)],
+                 vec![("d", (8i16, 9i16)), ("efg", (10, 11))],
+                 12u8))
+            .unwrap()
+    }
+
+    fn get(schema: &Schema, path: &str, data: &[u8]) -> Value {
+        FieldPath::new(schema, path).unwrap().get(data).unwrap()
+    }
+
+    #[test]
+    fn reads_fields_of_recorded_messages() {
+        let schema = pose_array_schema();
+        let data = include_bytes!("../datatests/pose_array_msg.bin");
+        let value = decode(&schema, data).unwrap();
+        assert_eq!(get(&schema, "header.stamp", data),
+                   Value::Time(Time { sec: 0, nsec: 0 }));
+        assert_eq!(get(&schema, "header.frame_id", data), Value::String("ABC".into()));
+        assert_eq!(get(&schema, "header", data), value.get("header").unwrap().clone());
+        assert_eq!(get(&schema, "poses", data), value.get("poses").unwrap().clone());
+        for i in 0..5 {
+            assert_eq!(get(&schema, &format!("poses[{}].orientation.w", i), data),
+                       Value::F64(7.0));
+            assert_eq!(get(&schema, &format!("poses[{}].position", i), data),
+                       value.get("poses").unwrap().as_array().unwrap()[i]
+                           .get("position")
+                           .unwrap()
+                           .clone());
+        }
+    }
+
+    #[test]
+    fn skips_variable_length_fields() {
+        let schema = mixed_schema();
+        let data = mixed_data();
+        assert_eq!(get(&schema, "names[1]", &data), Value::String("bc".into()));
+        assert_eq!(get(&schema, "data", &data), Value::Bytes(vec![1, 2, 3]));
+        assert_eq!(get(&schema, "data[2]", &data), Value::U8(3));
+        assert_eq!(get(&schema, "points[1].x", &data), Value::I16(6));
+        assert_eq!(get(&schema, "named[1].name", &data), Value::String("efg".into()));
+        assert_eq!(get(&schema, "named[1].point.y", &data), Value::I16(11));
+        assert_eq!(get(&schema, "last", &data), Value::U8(12));
+        let path = FieldPath::new(&schema, "last").unwrap();
+        assert_eq!(path.range(&data).unwrap(), data.len() - 1..data.len());
+    }
+
+    #[test]
+    fn fails_on_oversized_fields() {
+        let schema = Schema::from_definition("my_msgs/Data",
+                                             "Big[4294967295] a\nint8 b\n\
+                                              ================================================\
+                                              ================================\n\
+                                              MSG: my_msgs/Big\ntime[4294967295] c\n")
+            .unwrap();
+        match *FieldPath::new(&schema, "b").unwrap_err().kind() {
+            ErrorKind::Overflow => {}
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+        let path = FieldPath::new(&schema, "a[4294967294].c[0]").unwrap();
+        match *path.get(&[0, 0, 0, 0]).unwrap_err().kind() {
+            ErrorKind::Overflow => {}
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn reports_out_of_bounds_items() {
+        let schema = mixed_schema();
+        let data = mixed_data();
+        let err = FieldPath::new(&schema, "named[2].point.y").unwrap().get(&data).unwrap_err();
+        assert_eq!(err.to_string(),
+                   "Field named[2] is out of bounds of an array with 2 items");
+        assert!(FieldPath::new(&schema, "last").unwrap().get(&data[..data.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_paths() {
+        let schema = pose_array_schema();
+        let error = |path| FieldPath::new(&schema, path).unwrap_err().to_string();
+        assert_eq!(error("header.stamp.secs"),
+                   "Field path \"header.stamp.secs\" is invalid: header.stamp is not a message");
+        assert_eq!(error("header.time"),
+                   "Field path \"header.time\" is invalid: std_msgs/Header has no field time");
+        assert_eq!(error("header[0]"),
+                   "Field path \"header[0]\" is invalid: header is not an array");
+        assert_eq!(error("poses.position"),
+                   "Field path \"poses.position\" is invalid: poses is not a message");
+        assert_eq!(error("poses[x]"),
+                   "Field path \"poses[x]\" is invalid: \"poses[x]\" is not a field name");
+        assert!(FieldPath::new(&schema, "").is_err());
+        assert!(FieldPath::new(&schema, "header.").is_err());
+        let schema = Schema::from_definition("my_msgs/Fixed", "int32[3] values\n").unwrap();
+        assert_eq!(FieldPath::new(&schema, "values[3]").unwrap_err().to_string(),
+                   "Field path \"values[3]\" is invalid: values has only 3 items");
+    }
+
+    fn set(value: &mut Value, path: &str, new_value: Value) {
+        let mut target = value;
+        for segment in path.split('.') {
+            let (name, index) = parse_segment(segment).unwrap();
+            target = target.get_mut(name).unwrap();
+            if let Some(index) = index {
+                target = match *target {
+                    Value::Array(ref mut items) => &mut items[index],
+                    _ => panic!("{} is not an array", name),
+                };
+            }
+        }
+        *target = new_value;
+    }
+
+    #[test]
+    fn patches_recorded_messages() {
+        let schema = pose_array_schema();
+        let mut data = include_bytes!("../datatests/pose_array_msg.bin").to_vec();
+        let mut value = decode(&schema, &data).unwrap();
+        let point = |x, y, z| {
+            Value::Message(vec![("x".into(), Value::F64(x)),
+                                ("y".into(), Value::F64(y)),
+                                ("z".into(), Value::F64(z))])
+        };
+        let changes = vec![("header.frame_id", Value::String("base_link".into())),
+                           ("header.stamp", Value::Time(Time { sec: 100, nsec: 5 })),
+                           ("poses[2].orientation.w", Value::F64(0.5)),
+                           ("header.frame_id", Value::String(String::new())),
+                           ("poses[4].position", point(3.0, 2.0, 1.0))];
+        for (path, new_value) in changes {
+            let path = FieldPath::new(&schema, path).unwrap();
+            patch(&mut data, &path, &new_value).unwrap();
+            set(&mut value, path.as_str(), new_value);
+            assert_eq!(decode(&schema, &data).unwrap(), value);
+        }
+        assert_eq!(data, encode(&schema, &value).unwrap());
+    }
+
+    #[test]
+    fn patches_variable_length_fields() {
+        let schema = mixed_schema();
+        let mut data = mixed_data();
+        let names = FieldPath::new(&schema, "names").unwrap();
+        let new_names = Value::Array(vec![Value::String("x".into())]);
+        patch(&mut data, &names, &new_names).unwrap();
+        let name = FieldPath::new(&schema, "named[0].name").unwrap();
+        patch(&mut data, &name, &Value::String("longer".into())).unwrap();
+        let item = FieldPath::new(&schema, "data[1]").unwrap();
+        patch(&mut data, &item, &Value::U64(20)).unwrap();
+        assert_eq!(data,
+                   to_vec(&(vec!["x"],
+                            vec![1u8, 20, 3],
+                            vec![(4i16, 5i16), (6, 7)],
+                            vec![("longer", (8i16, 9i16)), ("efg", (10, 11))],
+                            12u8))
+                       .unwrap());
+    }
+
+    #[test]
+    fn keeps_data_on_failed_patches() {
+        let schema = mixed_schema();
+        let mut data = mixed_data();
+        let item = FieldPath::new(&schema, "data[1]").unwrap();
+        assert_eq!(patch(&mut data, &item, &Value::I16(256)).unwrap_err().to_string(),
+                   "Value at data[1] does not fit into uint8");
+        let point = FieldPath::new(&schema, "points[1]").unwrap();
+        let bad_point = Value::Message(vec![("x".into(), Value::I16(1))]);
+        assert_eq!(patch(&mut data, &point, &bad_point).unwrap_err().to_string(),
+                   "Message is missing the field points[1].y");
+        let item = FieldPath::new(&schema, "points[2]").unwrap();
+        assert!(patch(&mut data, &item, &bad_point).is_err());
+        assert_eq!(data, mixed_data());
+    }
+}
@@ -0,0 +1,331 @@
+use byteorder::{LittleEndian, WriteBytesExt};
+use serde::ser::{Serialize, Serializer as SerdeSerializer};
+use super::Value;
+use super::super::error::{ErrorKind, Result};
+use super::super::schema::{BaseType, FieldType, Schema};
+use super::super::ser::Serializer;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+
+pub(crate) type Output<'a> = Serializer<&'a mut Vec<u8>>;
+
+/// Encode a value as ROSMSG bytes, following the schema.
+///
+/// The output is identical to what `to_vec` produces for a matching struct,
+/// including the length prefix.
+///
+/// Fields of messages are matched by name, so their order within the value
+/// does not matter. Integers are accepted for any integer type they fit
+/// into, and any number is accepted for floating point types.
+///
+/// Encoding fails, naming the path of the offending field, if the value
+/// does not match the schema.
+///
+/// # Examples
+///
+/// ```rust
+/// # use serde_rosmsg::dynamic::{encode, Value};
+/// # use serde_rosmsg::schema::Schema;
+/// let schema = Schema::from_definition("my_msgs/Pair", "uint16 a\nint8 b\n").unwrap();
+/// let value = Value::Message(vec![("b".into(), Value::I64(-1)),
+///                                 ("a".into(), Value::U16(258))]);
+/// assert_eq!(encode(&schema, &value).unwrap(), vec![3, 0, 0, 0, 2, 1, 255]);
+///
+/// let value = Value::Message(vec![("a".into(), Value::U16(258)),
+///                                 ("b".into(), Value::I64(-1000))]);
+/// assert_eq!(encode(&schema, &value).unwrap_err().to_string(),
+///            "Value at b does not fit into int8");
+/// ```
+pub fn encode(schema: &Schema, value: &Value) -> Result<Vec<u8>> {
+    let mut writer = Vec::with_capacity(128);
+    encode_writer(schema, value, &mut writer)?;
+    Ok(writer)
+}
+
+/// Encode a value as ROSMSG into the IO stream, following the schema.
+///
+/// Behaves just like `encode`, but writes into the given writer.
+pub fn encode_writer<W>(schema: &Schema, value: &Value, writer: &mut W) -> Result<()>
+    where W: io::Write
+{
+    let mut buffer = Vec::new();
+    encode_message(schema, schema.name(), value, "", &mut Serializer::new(&mut buffer))?;
+    if buffer.len() > u32::MAX as usize {
+        bail!(ErrorKind::FrameTooLarge(buffer.len(), u32::MAX as usize));
+    }
+    writer
+        .write_u32::<LittleEndian>(buffer.len() as u32)
+        .and_then(|_| writer.write_all(&buffer))
+        .map_err(|v| v.into())
+}
+
+fn mismatch<T: fmt::Display>(path: &str, expected: T, value: &Value) -> ErrorKind {
+    ErrorKind::MismatchedValueType(path.into(), expected.to_string(), value.type_name().into())
+}
+
+fn child(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.into()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+fn encode_message(schema: &Schema,
+                  name: &str,
+                  value: &Value,
+                  path: &str,
+                  output: &mut Output)
+                  -> Result<()> {
+    let msg = schema.message(name)?;
+    let fields = match value.as_fields() {
+        Some(fields) => fields,
+        // The root message is named after its type, since it has no path
+        None if path.is_empty() => bail!(mismatch(name, name, value)),
+        None => bail!(mismatch(path, name, value)),
+    };
+    for (key, _) in fields {
+        if !msg.fields.iter().any(|field| field.name == *key) {
+            bail!(ErrorKind::UnknownField(child(path, key)));
+        }
+    }
+    for field in &msg.fields {
+        let path = child(path, &field.name);
+        match fields.iter().find(|item| item.0 == field.name) {
+            Some(item) => encode_field(schema, &field.field_type, &item.1, &path, output)?,
+            None => bail!(ErrorKind::MissingField(path)),
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn encode_field(schema: &Schema,
+                           field_type: &FieldType,
+                           value: &Value,
+                           path: &str,
+                           output: &mut Output)
+                           -> Result<()> {
+    let (base, length) = match *field_type {
+        FieldType::Single(ref base) => return encode_base(schema, base, value, path, output),
+        FieldType::Array(ref base) => (base, None),
+        FieldType::FixedArray(ref base, length) => (base, Some(length)),
+    };
+    let actual = match *value {
+        Value::Bytes(ref items) => items.len(),
+        Value::Array(ref items) => items.len(),
+        _ => bail!(mismatch(path, field_type, value)),
+    };
+    match length {
+        Some(length) if length != actual => {
+            bail!(ErrorKind::MismatchedArrayLength(path.into(), length, actual))
+        }
+        Some(_) => {}
+        None => {
+            let actual = u32::try_from(actual)
+                .map_err(|_| ErrorKind::ValueOutOfRange(path.into(), field_type.to_string()))?;
+            output.serialize_u32(actual)?
+        }
+    }
+    match *value {
+        Value::Bytes(ref items) => {
+            match *base {
+                BaseType::UInt8 | BaseType::Char => output.put_bytes(items)?,
+                _ => bail!(mismatch(path, field_type, value)),
+            }
+        }
+        Value::Array(ref items) => {
+            for (index, item) in items.iter().enumerate() {
+                encode_base(schema, base, item, &format!("{}[{}]", path, index), output)?;
+            }
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+pub(crate) fn encode_base(schema: &Schema,
+                          base: &BaseType,
+                          value: &Value,
+                          path: &str,
+                          output: &mut Output)
+                          -> Result<()> {
+    if let Some((min, max)) = base.integer_range() {
+        let number = match value.as_i64() {
+            Some(number) => number as i128,
+            None => {
+                match *value {
+                    Value::U64(number) => number as i128,
+                    _ => bail!(mismatch(path, base, value)),
+                }
+            }
+        };
+        if number < min || number > max {
+            bail!(ErrorKind::ValueOutOfRange(path.into(), base.to_string()));
+        }
+        return match *base {
+                   BaseType::Int8 | BaseType::Byte => output.serialize_i8(number as i8),
+                   BaseType::UInt8 | BaseType::Char => output.serialize_u8(number as u8),
+                   BaseType::Int16 => output.serialize_i16(number as i16),
+                   BaseType::UInt16 => output.serialize_u16(number as u16),
+                   BaseType::Int32 => output.serialize_i32(number as i32),
+                   BaseType::UInt32 => output.serialize_u32(number as u32),
+                   BaseType::Int64 => output.serialize_i64(number as i64),
+                   _ => output.serialize_u64(number as u64),
+               };
+    }
+    match *base {
+        BaseType::Float32 if value.as_f64().is_some() => {
+            return output.serialize_f32(value.as_f64().unwrap_or_default() as f32);
+        }
+        BaseType::Float64 if value.as_f64().is_some() => {
+            return output.serialize_f64(value.as_f64().unwrap_or_default());
+        }
+        BaseType::Message(ref name) => return encode_message(schema, name, value, path, output),
+        _ => {}
+    }
+    match *value {
+        Value::Bool(v) if *base == BaseType::Bool => output.serialize_bool(v),
+        Value::String(ref v) if *base == BaseType::String => output.serialize_str(v),
+        Value::Time(v) if *base == BaseType::Time => v.serialize(&mut *output),
+        Value::Duration(v) if *base == BaseType::Duration => v.serialize(&mut *output),
+        _ => bail!(mismatch(path, base, value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::decode;
+    use super::super::super::to_vec;
+    use super::super::super::types::Time;
+
+    fn pose_array_schema() -> Schema {
+        Schema::from_definition("geometry_msgs/PoseArray",
+                                include_str!("../datatests/pose_array_message_definition.txt"))
+            .unwrap()
+    }
+
+    #[test]
+    fn reencodes_recorded_messages() {
+        let schema = pose_array_schema();
+        let data = include_bytes!("../datatests/pose_array_msg.bin");
+        let value = decode(&schema, data).unwrap();
+        assert_eq!(encode(&schema, &value).unwrap(), data.to_vec());
+    }
+
+    #[test]
+    fn matches_static_serialization() {
+        #[derive(Serialize)]
+        struct Data {
+            flag: bool,
+            small: i8,
+            stamp: Time,
+            name: String,
+            blob: Vec<u8>,
+            fixed: [u8; 3],
+            values: Vec<f32>,
+            counts: [u64; 2],
+        }
+        let schema = Schema::from_definition("my_msgs/Data",
+                                             "bool flag\nint8 small\ntime stamp\n\
+                                              string name\nuint8[] blob\nchar[3] fixed\n\
+                                              float32[] values\nuint64[2] counts\n")
+            .unwrap();
+        let data = Data {
+            flag: true,
+            small: -3,
+            stamp: Time { sec: 10, nsec: 20 },
+            name: "hello".into(),
+            blob: vec![1, 2, 3, 4],
+            fixed: [5, 6, 7],
+            values: vec![0.5, 1.5],
+            counts: [u64::MAX, 8],
+        };
+        let value = Value::Message(vec![("counts".into(),
+                                         Value::Array(vec![Value::U64(u64::MAX), Value::U8(8)])),
+                                        ("flag".into(), Value::Bool(true)),
+                                        ("small".into(), Value::I32(-3)),
+                                        ("stamp".into(), Value::Time(Time { sec: 10, nsec: 20 })),
+                                        ("name".into(), Value::String("hello".into())),
+                                        ("blob".into(), Value::Bytes(vec![1, 2, 3, 4])),
+                                        ("fixed".into(),
+                                         Value::Array(vec![Value::U8(5),
+                                                           Value::U8(6),
+                                                           Value::U8(7)])),
+                                        ("values".into(),
+                                         Value::Array(vec![Value::F64(0.5), Value::F32(1.5)]))]);
+        assert_eq!(encode(&schema, &value).unwrap(), to_vec(&data).unwrap());
+    }
+
+    #[test]
+    fn reports_paths_of_bad_values() {
+        let schema = pose_array_schema();
+        let data = include_bytes!("../datatests/pose_array_msg.bin");
+        let original = decode(&schema, data).unwrap();
+
+        let mut value = original.clone();
+        *value.get_mut("poses").and_then(|poses| match *poses {
+                                              Value::Array(ref mut poses) => poses.get_mut(3),
+                                              _ => None,
+                                          })
+             .and_then(|pose| pose.get_mut("orientation"))
+             .and_then(|orientation| orientation.get_mut("w"))
+             .unwrap() = Value::String("1.0".into());
+        assert_eq!(encode(&schema, &value).unwrap_err().to_string(),
+                   "Value at poses[3].orientation.w should be float64, but is string");
+
+        let mut value = original.clone();
+        *value.get_mut("header").and_then(|header| header.get_mut("seq")).unwrap() =
+            Value::I8(-1);
+        assert_eq!(encode(&schema, &value).unwrap_err().to_string(),
+                   "Value at header.seq does not fit into uint32");
+
+        let mut value = original.clone();
+        *value.get_mut("poses").unwrap() = Value::Bytes(vec![]);
+        assert_eq!(encode(&schema, &value).unwrap_err().to_string(),
+                   "Value at poses should be geometry_msgs/Pose[], but is byte array");
+
+        let mut value = original.clone();
+        if let Value::Message(ref mut fields) = *value.get_mut("header").unwrap() {
+            fields.remove(0);
+        }
+        assert_eq!(encode(&schema, &value).unwrap_err().to_string(),
+                   "Message is missing the field header.seq");
+
+        let mut value = original.clone();
+        if let Value::Message(ref mut fields) = value {
+            fields.push(("extra".into(), Value::Bool(false)));
+        }
+        assert_eq!(encode(&schema, &value).unwrap_err().to_string(),
+                   "Message has the unknown field extra");
+
+        assert_eq!(encode(&schema, &Value::U8(0)).unwrap_err().to_string(),
+                   "Value at geometry_msgs/PoseArray should be geometry_msgs/PoseArray, \
+                    but is uint8");
+    }
+
+    #[test]
+    fn checks_fixed_array_lengths() {
+        let schema = Schema::from_definition("my_msgs/Data", "uint8[4] a\nint16[2] b\n").unwrap();
+        let value = Value::Message(vec![("a".into(), Value::Bytes(vec![1, 2, 3, 4])),
+                                        ("b".into(), Value::Array(vec![Value::I16(-1)]))]);
+        assert_eq!(encode(&schema, &value).unwrap_err().to_string(),
+                   "Array at b should have 2 items, but has 1");
+        let value = Value::Message(vec![("a".into(), Value::Bytes(vec![1, 2, 3])),
+                                        ("b".into(),
+                                         Value::Array(vec![Value::I16(-1), Value::I16(1)]))]);
+        assert_eq!(encode(&schema, &value).unwrap_err().to_string(),
+                   "Array at a should have 4 items, but has 3");
+    }
+
+    #[test]
+    fn rejects_oversized_arrays() {
+        let schema = Schema::from_definition("my_msgs/Data", "uint8[] a\n").unwrap();
+        // Zeroed memory gets mapped lazily, so this does not take up 4 GiB
+        let value = Value::Message(vec![("a".into(),
+                                         Value::Bytes(vec![0; u32::MAX as usize + 1]))]);
+        assert_eq!(encode(&schema, &value).unwrap_err().to_string(),
+                   "Value at a does not fit into uint8[]");
+    }
+}
@@ -0,0 +1,326 @@
+use byteorder::{LittleEndian, ReadBytesExt};
+use serde::de::Deserialize;
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq};
+use super::deserializer::Kind;
+use super::super::de::Deserializer;
+use super::super::error::{Error, ErrorKind, Result};
+use super::super::schema::{BaseType, FieldType, Schema};
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::io;
+
+/// Convert ROSMSG data into another format, following the schema.
+///
+/// The message is streamed straight from the reader into the serializer,
+/// without building any intermediate tree of values. Messages are passed on
+/// as maps, keeping all the field names, `time` and `duration` values as
+/// maps with `secs` and `nsecs` fields, arrays of `uint8` and `char` as
+/// bytes, and all other arrays as sequences.
+///
+/// Just like with `from_reader`, the stream needs to start with the length
+/// of the message.
+///
+/// The data is consumed from the reader while it gets serialized, so the
+/// serializer needs to serialize the message exactly once. Serializers that
+/// go over their input twice, for example to find out its size first, get
+/// garbage on the second pass.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serde_json;
+/// # extern crate serde_rosmsg;
+/// # use serde_rosmsg::dynamic::transcode;
+/// # use serde_rosmsg::schema::Schema;
+/// # fn main() {
+/// let schema = Schema::from_definition("my_msgs/Reading",
+///                                      "string sensor\nfloat32[] values\n").unwrap();
+/// let data = [19, 0, 0, 0,
+///             3, 0, 0, 0, 73, 77, 85,
+///             2, 0, 0, 0, 0, 0, 128, 63, 0, 0, 0, 64];
+/// let mut json = Vec::new();
+/// transcode(&schema,
+///           &mut std::io::Cursor::new(&data),
+///           &mut serde_json::Serializer::new(&mut json))
+///     .unwrap();
+/// assert_eq!(String::from_utf8(json).unwrap(),
+///            r#"{"sensor":"IMU","values":[1.0,2.0]}"#);
+/// # }
+/// ```
+pub fn transcode<R, S>(schema: &Schema, mut reader: R, serializer: S) -> Result<S::Ok>
+    where R: io::Read,
+          S: ser::Serializer
+{
+    let length = reader.read_u32::<LittleEndian>()?;
+    let context = Context {
+        deserializer: RefCell::new(Deserializer::new(reader, length)),
+        schema,
+        error: RefCell::new(None),
+    };
+    let output = Item {
+            context: &context,
+            kind: Kind::Message(schema.name()),
+        }
+        .serialize(serializer);
+    // Reading errors reach this point only as text, so the original
+    // gets reported instead
+    if let Some(error) = context.error.into_inner() {
+        return Err(error);
+    }
+    let output = output.map_err(|err| Error::from(err.to_string()))?;
+    if !context.deserializer.into_inner().is_fully_read() {
+        bail!(ErrorKind::Underflow);
+    }
+    Ok(output)
+}
+
+struct Context<'s, R> {
+    deserializer: RefCell<Deserializer<R>>,
+    schema: &'s Schema,
+    error: RefCell<Option<Error>>,
+}
+
+impl<'s, R: io::Read> Context<'s, R> {
+    fn read<T, F, E>(&self, read: F) -> ::std::result::Result<T, E>
+        where F: FnOnce(&mut Deserializer<R>) -> Result<T>,
+              E: ser::Error
+    {
+        read(&mut self.deserializer.borrow_mut()).map_err(|err| self.fail(err))
+    }
+
+    fn fail<E: ser::Error>(&self, err: Error) -> E {
+        let message = err.to_string();
+        *self.error.borrow_mut() = Some(err);
+        E::custom(message)
+    }
+}
+
+static UINT32: BaseType = BaseType::UInt32;
+static INT32: BaseType = BaseType::Int32;
+
+/// The value that comes next in the data.
+struct Item<'c, 's: 'c, R: 'c> {
+    context: &'c Context<'s, R>,
+    kind: Kind<'s>,
+}
+
+impl<'c, 's, R: io::Read> Item<'c, 's, R> {
+    fn child(&self, kind: Kind<'s>) -> Self {
+        Item {
+            context: self.context,
+            kind,
+        }
+    }
+
+    fn items<S>(&self,
+                serializer: S,
+                base: &'s BaseType,
+                length: u32)
+                -> ::std::result::Result<S::Ok, S::Error>
+        where S: ser::Serializer
+    {
+        match *base {
+            BaseType::UInt8 | BaseType::Char => {
+                let bytes = self.context.read(|de| de.get_bytes(length))?;
+                serializer.serialize_bytes(&bytes)
+            }
+            _ => {
+                let mut seq = serializer.serialize_seq(Some(length as usize))?;
+                for _ in 0..length {
+                    seq.serialize_element(&self.child(Kind::Base(base)))?;
+                }
+                seq.end()
+            }
+        }
+    }
+
+    fn base<S>(&self,
+               serializer: S,
+               base: &'s BaseType)
+               -> ::std::result::Result<S::Ok, S::Error>
+        where S: ser::Serializer
+    {
+        let context = self.context;
+        match *base {
+            BaseType::Bool => serializer.serialize_bool(context.read(|de| bool::deserialize(de))?),
+            BaseType::Int8 | BaseType::Byte => {
+                serializer.serialize_i8(context.read(|de| i8::deserialize(de))?)
+            }
+            BaseType::UInt8 | BaseType::Char => {
+                serializer.serialize_u8(context.read(|de| u8::deserialize(de))?)
+            }
+            BaseType::Int16 => serializer.serialize_i16(context.read(|de| i16::deserialize(de))?),
+            BaseType::UInt16 => serializer.serialize_u16(context.read(|de| u16::deserialize(de))?),
+            BaseType::Int32 => serializer.serialize_i32(context.read(|de| i32::deserialize(de))?),
+            BaseType::UInt32 => serializer.serialize_u32(context.read(|de| u32::deserialize(de))?),
+            BaseType::Int64 => serializer.serialize_i64(context.read(|de| i64::deserialize(de))?),
+            BaseType::UInt64 => serializer.serialize_u64(context.read(|de| u64::deserialize(de))?),
+            BaseType::Float32 => serializer.serialize_f32(context.read(|de| f32::deserialize(de))?),
+            BaseType::Float64 => serializer.serialize_f64(context.read(|de| f64::deserialize(de))?),
+            BaseType::String => {
+                serializer.serialize_str(&context.read(|de| String::deserialize(de))?)
+            }
+            BaseType::Time | BaseType::Duration => {
+                let part = match *base {
+                    BaseType::Time => &UINT32,
+                    _ => &INT32,
+                };
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("secs", &self.child(Kind::Base(part)))?;
+                map.serialize_entry("nsecs", &self.child(Kind::Base(part)))?;
+                map.end()
+            }
+            BaseType::Message(ref name) => self.child(Kind::Message(name)).serialize(serializer),
+        }
+    }
+}
+
+impl<'c, 's, R: io::Read> Serialize for Item<'c, 's, R> {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+        where S: ser::Serializer
+    {
+        match self.kind {
+            Kind::Message(name) => {
+                let msg = self.context
+                    .schema
+                    .message(name)
+                    .map_err(|err| self.context.fail(err))?;
+                let mut map = serializer.serialize_map(Some(msg.fields.len()))?;
+                for field in &msg.fields {
+                    map.serialize_entry(&field.name,
+                                         &self.child(Kind::Field(&field.field_type)))?;
+                }
+                map.end()
+            }
+            Kind::Field(FieldType::Single(base)) |
+            Kind::Base(base) => self.base(serializer, base),
+            Kind::Field(FieldType::Array(base)) => {
+                let length = self.context.read(|de| de.pop_length())?;
+                self.items(serializer, base, length)
+            }
+            Kind::Field(FieldType::FixedArray(base, length)) => {
+                let length = u32::try_from(*length)
+                    .map_err(|_| self.context.fail(ErrorKind::Overflow.into()))?;
+                self.items(serializer, base, length)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use super::super::super::{from_slice, to_vec};
+    use super::super::super::ser::Serializer;
+    use super::super::super::schema::Msg;
+
+    fn to_json(schema: &Schema, data: &[u8]) -> Result<String> {
+        let mut json = Vec::new();
+        transcode(schema,
+                  io::Cursor::new(data),
+                  &mut serde_json::Serializer::new(&mut json))?;
+        Ok(String::from_utf8(json).unwrap())
+    }
+
+    #[test]
+    fn keeps_field_names() {
+        // String fields transcoded into ROSMSG maps form a connection header
+        let schema = Schema::from_definition("my_msgs/Names", "string callerid\nstring topic\n")
+            .unwrap();
+        let data = to_vec(&("/node", "/chatter")).unwrap();
+        let mut output = Vec::new();
+        transcode(&schema, io::Cursor::new(&data), &mut Serializer::new(&mut output)).unwrap();
+        assert_eq!(output, b"\x0e\0\0\0callerid=/node\x0e\0\0\0topic=/chatter".to_vec());
+        let mut framed = vec![output.len() as u8, 0, 0, 0];
+        framed.extend(output);
+        let fields: HashMap<String, String> = from_slice(&framed).unwrap();
+        assert_eq!(fields["callerid"], "/node");
+        assert_eq!(fields["topic"], "/chatter");
+    }
+
+    #[test]
+    fn passes_bytes_in_bulk() {
+        let schema = Schema::from_definition("my_msgs/Blob", "uint8[] data\nchar[2] fixed\n")
+            .unwrap();
+        let data = to_vec(&(vec![1u8, 2, 3], [4u8, 5])).unwrap();
+        let mut output = Vec::new();
+        let mut cursor = io::Cursor::new(&data);
+        transcode(&schema, &mut cursor, &mut Serializer::new(&mut output)).unwrap();
+        assert_eq!(cursor.position(), data.len() as u64);
+        assert_eq!(output, b"\x08\0\0\0data=\x01\x02\x03\x08\0\0\0fixed=\x04\x05".to_vec());
+    }
+
+    #[test]
+    fn reports_original_errors() {
+        let schema = Schema::from_definition("my_msgs/Names", "string a\nstring b\n").unwrap();
+        let data = to_vec(&("x", "y")).unwrap();
+        let mut output = Vec::new();
+        let err = transcode(&schema,
+                            io::Cursor::new(&data[..data.len() - 1]),
+                            &mut Serializer::new(&mut output))
+            .unwrap_err();
+        match *err.kind() {
+            ErrorKind::EndOfBuffer => {}
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+        let mut data = data;
+        data[0] += 1;
+        data.push(0);
+        let err = transcode(&schema, io::Cursor::new(&data), &mut Serializer::new(&mut output))
+            .unwrap_err();
+        match *err.kind() {
+            ErrorKind::Underflow => {}
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn converts_nested_messages_to_json() {
+        let schema = Schema::from_definition("my_msgs/Pose",
+                                             "Header header\nPoint position\n\
+                                              ================================================\
+                                              ================================\n\
+                                              MSG: std_msgs/Header\n\
+                                              uint32 seq\ntime stamp\nstring frame_id\n\
+                                              ================================================\
+                                              ================================\n\
+                                              MSG: my_msgs/Point\nint16 x\nint16 y\n")
+            .unwrap();
+        let data = to_vec(&((7u32, (1u32, 2u32), "map"), (-3i16, 4i16))).unwrap();
+        assert_eq!(to_json(&schema, &data).unwrap(),
+                   r#"{"header":{"seq":7,"stamp":{"secs":1,"nsecs":2},"frame_id":"map"},"#
+                       .to_owned() + r#""position":{"x":-3,"y":4}}"#);
+    }
+
+    #[test]
+    fn converts_times_and_arrays_to_json() {
+        let schema = Schema::from_definition("my_msgs/Arrays",
+                                             "duration[] waits\nint8[2] fixed\n\
+                                              float64[] values\nstring[1] names\n\
+                                              uint8[] data\nchar[2] chars\n")
+            .unwrap();
+        let data = to_vec(&(vec![(-1i32, 500u32)],
+                            [-5i8, 6],
+                            vec![0.5f64],
+                            ["a"],
+                            vec![1u8, 2, 3],
+                            [4u8, 5]))
+            .unwrap();
+        assert_eq!(to_json(&schema, &data).unwrap(),
+                   r#"{"waits":[{"secs":-1,"nsecs":500}],"fixed":[-5,6],"values":[0.5],"#
+                       .to_owned() +
+                   r#""names":["a"],"data":[1,2,3],"chars":[4,5]}"#);
+    }
+
+    #[test]
+    fn fails_on_oversized_fixed_arrays() {
+        let mut msg = Msg::new("my_msgs/Data", "int8[1] a\n").unwrap();
+        msg.fields[0].field_type = FieldType::FixedArray(BaseType::Int8, u32::MAX as usize + 1);
+        let schema = Schema::new(msg, Vec::new()).unwrap();
+        match *to_json(&schema, &[0, 0, 0, 0]).unwrap_err().kind() {
+            ErrorKind::Overflow => {}
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+    }
+}
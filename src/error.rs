@@ -39,6 +39,85 @@ error_chain! {
             description("Enumerations are not supported in ROSMSG")
                 display("Enumerations are not supported in ROSMSG")
         }
+        BadMessageDefinition(t: String) {
+            description("Message definition is invalid")
+                display("Message definition is invalid: {}", t)
+        }
+        UnknownMessageType(t: String) {
+            description("Message type is not known")
+                display("Message type is not known: {}", t)
+        }
+        MissingHeaderField(t: String) {
+            description("Connection header is missing a field")
+                display("Connection header is missing the field: {}", t)
+        }
+        HeaderMismatch(field: String, expected: String, actual: String) {
+            description("Connection header field does not match")
+                display("Connection header field {} should be {:?}, but is {:?}",
+                        field,
+                        expected,
+                        actual)
+        }
+        RemoteError(t: String) {
+            description("Remote node reported an error")
+                display("Remote node reported an error: {}", t)
+        }
+        ServiceFailed(t: String) {
+            description("Service server failed to handle the call")
+                display("Service server failed to handle the call: {}", t)
+        }
+        FrameTooLarge(length: usize, limit: usize) {
+            description("Frame exceeds the size limit")
+                display("Frame of {} bytes exceeds the limit of {} bytes", length, limit)
+        }
+        BadDatagram(t: String) {
+            description("UDPROS datagram is invalid")
+                display("UDPROS datagram is invalid: {}", t)
+        }
+        NotPersistent {
+            description("Service connection is not persistent")
+                display("Service connection is not persistent, and already carried a call")
+        }
+        MismatchedMd5sum(expected: String, actual: String) {
+            description("Message has a different MD5 sum than expected")
+                display("Message should have the MD5 sum {:?}, but has {:?}", expected, actual)
+        }
+        MismatchedValueType(path: String, expected: String, actual: String) {
+            description("Value does not match the type in the message definition")
+                display("Value at {} should be {}, but is {}", path, expected, actual)
+        }
+        MismatchedArrayLength(path: String, expected: usize, actual: usize) {
+            description("Array length does not match the message definition")
+                display("Array at {} should have {} items, but has {}", path, expected, actual)
+        }
+        ValueOutOfRange(path: String, expected: String) {
+            description("Value does not fit the type in the message definition")
+                display("Value at {} does not fit into {}", path, expected)
+        }
+        MissingField(path: String) {
+            description("Message is missing a field from its definition")
+                display("Message is missing the field {}", path)
+        }
+        UnknownField(path: String) {
+            description("Message has a field not present in its definition")
+                display("Message has the unknown field {}", path)
+        }
+        BadFieldPath(path: String, reason: String) {
+            description("Field path is invalid")
+                display("Field path {:?} is invalid: {}", path, reason)
+        }
+        ArrayIndexOutOfBounds(path: String, length: usize) {
+            description("Array index is out of bounds")
+                display("Field {} is out of bounds of an array with {} items", path, length)
+        }
+        MissingMigration(from: String, to: String) {
+            description("No migration leads to the expected message")
+                display("No migration leads from MD5 sum {} to {}", from, to)
+        }
+        UnrepresentableType(t: String) {
+            description("Type cannot be represented within a ROS message")
+                display("Type cannot be represented within a ROS message: {}", t)
+        }
         VariableArraySizeAnnotation {
             description("Size annotation in variable size array is missing")
                 display("Size annotation in variable size array is missing")
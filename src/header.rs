@@ -0,0 +1,340 @@
+//! Connection headers exchanged when establishing ROS connections.
+//!
+//! Every TCPROS and UDPROS connection starts with both sides sending a
+//! connection header, a map of fields describing the caller and the data
+//! that will be exchanged. Headers are serialized like any other map, with
+//! every entry written as a `key=value` string.
+//!
+//! # Examples
+//!
+//! ```rust
+//! extern crate serde_rosmsg;
+//! #[macro_use]
+//! extern crate serde_derive;
+//! use serde_rosmsg::{to_vec, from_slice, RosMessage};
+//! use serde_rosmsg::header::ConnectionHeader;
+//!
+//! fn main() {
+//! #[derive(Debug,Serialize,Deserialize,PartialEq)]
+//! struct StringMsg {
+//!     data: String,
+//! }
+//!
+//! impl RosMessage for StringMsg {
+//!     const ROS_TYPE: &'static str = "std_msgs/String";
+//!     const MD5SUM: &'static str = "992ce8a1687cec8c8bd883ec73ca41d1";
+//!     const DEFINITION: &'static str = "string data\n";
+//! }
+//!
+//! let header = ConnectionHeader::for_subscriber::<StringMsg>("/listener", "/chatter");
+//! let rosmsg_data = to_vec(&header).unwrap();
+//! let received: ConnectionHeader = from_slice(&rosmsg_data).unwrap();
+//! assert_eq!(received.get("topic"), Some("/chatter"));
+//! assert!(received.check_message::<StringMsg>().is_ok());
+//! }
+//! ```
+
+use super::error::{ErrorKind, Result};
+use super::message::{RosMessage, RosService};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+
+/// Map of fields sent at the start of a connection.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConnectionHeader {
+    fields: HashMap<String, String>,
+}
+
+impl ConnectionHeader {
+    /// Create an empty header.
+    pub fn new() -> ConnectionHeader {
+        ConnectionHeader::default()
+    }
+
+    /// Create the header a publisher of the given message type responds with.
+    pub fn for_publisher<T>(caller_id: &str, topic: &str) -> ConnectionHeader
+        where T: RosMessage
+    {
+        let mut header = ConnectionHeader::for_message::<T>(caller_id);
+        header.insert("topic", topic);
+        header.insert("latching", "0");
+        header
+    }
+
+    /// Create the header a subscriber of the given message type sends.
+    pub fn for_subscriber<T>(caller_id: &str, topic: &str) -> ConnectionHeader
+        where T: RosMessage
+    {
+        let mut header = ConnectionHeader::for_message::<T>(caller_id);
+        header.insert("topic", topic);
+        header.insert("tcp_nodelay", "0");
+        header
+    }
+
+    /// Create the header a client of the given service type sends.
+    pub fn for_service_client<S>(caller_id: &str, service: &str) -> ConnectionHeader
+        where S: RosService
+    {
+        let mut header = ConnectionHeader::new();
+        header.insert("callerid", caller_id);
+        header.insert("service", service);
+        header.insert("md5sum", S::MD5SUM);
+        header.insert("type", S::ROS_TYPE);
+        header
+    }
+
+    /// Create the header a server of the given service type responds with.
+    pub fn for_service_server<S>(caller_id: &str) -> ConnectionHeader
+        where S: RosService
+    {
+        let mut header = ConnectionHeader::new();
+        header.insert("callerid", caller_id);
+        header.insert("md5sum", S::MD5SUM);
+        header.insert("type", S::ROS_TYPE);
+        header.insert("request_type", <S::Request as RosMessage>::ROS_TYPE);
+        header.insert("response_type", <S::Response as RosMessage>::ROS_TYPE);
+        header
+    }
+
+    /// Create the header sent to reject a connection.
+    pub fn for_error(message: &str) -> ConnectionHeader {
+        let mut header = ConnectionHeader::new();
+        header.insert("error", message);
+        header
+    }
+
+    fn for_message<T>(caller_id: &str) -> ConnectionHeader
+        where T: RosMessage
+    {
+        let mut header = ConnectionHeader::new();
+        header.insert("callerid", caller_id);
+        header.insert("md5sum", T::MD5SUM);
+        header.insert("type", T::ROS_TYPE);
+        header.insert("message_definition", T::DEFINITION);
+        header
+    }
+
+    /// Get the value of a field.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields.get(key).map(String::as_str)
+    }
+
+    /// Set the value of a field, returning the previous value.
+    pub fn insert<K, V>(&mut self, key: K, value: V) -> Option<String>
+        where K: Into<String>,
+              V: Into<String>
+    {
+        self.fields.insert(key.into(), value.into())
+    }
+
+    /// Remove a field, returning its value.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.fields.remove(key)
+    }
+
+    /// All fields within the header.
+    pub fn fields(&self) -> &HashMap<String, String> {
+        &self.fields
+    }
+
+    /// Consume the header, returning all fields within it.
+    pub fn into_fields(self) -> HashMap<String, String> {
+        self.fields
+    }
+
+    /// Check if the header comes from a peer compatible with the message
+    /// type.
+    ///
+    /// The MD5 sum has to match, and so does the type if it's provided.
+    /// Either side can use `*` as a wildcard. Headers with an `error` field
+    /// are always rejected.
+    pub fn check_message<T>(&self) -> Result<()>
+        where T: RosMessage
+    {
+        self.check(T::ROS_TYPE, T::MD5SUM)
+    }
+
+    /// Check if the header comes from a peer compatible with the service
+    /// type.
+    ///
+    /// The rules are the same as for
+    /// [`check_message`](#method.check_message).
+    pub fn check_service<S>(&self) -> Result<()>
+        where S: RosService
+    {
+        self.check(S::ROS_TYPE, S::MD5SUM)
+    }
+
+    fn check(&self, ros_type: &str, md5sum: &str) -> Result<()> {
+        if let Some(error) = self.get("error") {
+            bail!(ErrorKind::RemoteError(error.into()));
+        }
+        match self.get("md5sum") {
+            Some(value) => check_field("md5sum", md5sum, value)?,
+            None => bail!(ErrorKind::MissingHeaderField("md5sum".into())),
+        }
+        if let Some(value) = self.get("type") {
+            check_field("type", ros_type, value)?;
+        }
+        Ok(())
+    }
+}
+
+fn check_field(field: &str, expected: &str, actual: &str) -> Result<()> {
+    if expected != "*" && actual != "*" && expected != actual {
+        bail!(ErrorKind::HeaderMismatch(field.into(), expected.into(), actual.into()));
+    }
+    Ok(())
+}
+
+impl From<HashMap<String, String>> for ConnectionHeader {
+    fn from(fields: HashMap<String, String>) -> ConnectionHeader {
+        ConnectionHeader { fields }
+    }
+}
+
+impl Serialize for ConnectionHeader {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        self.fields.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ConnectionHeader {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<ConnectionHeader, D::Error>
+        where D: Deserializer<'de>
+    {
+        HashMap::deserialize(deserializer).map(ConnectionHeader::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{from_slice, to_vec};
+    use super::super::error::ErrorKind;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Point {
+        x: f64,
+        y: f64,
+        z: f64,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Pose {
+        position: Point,
+        orientation: (f64, f64, f64, f64),
+    }
+
+    impl RosMessage for Pose {
+        const ROS_TYPE: &'static str = "geometry_msgs/Pose";
+        const MD5SUM: &'static str = "e45d45a5a1ce597b249e23fb30fc871f";
+        const DEFINITION: &'static str = include_str!("datatests/pose_message_definition.txt");
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct AddTwoIntsRequest {
+        a: i64,
+        b: i64,
+    }
+
+    impl RosMessage for AddTwoIntsRequest {
+        const ROS_TYPE: &'static str = "rospy_tutorials/AddTwoIntsRequest";
+        const MD5SUM: &'static str = "36d09b846be0b371c5f190354dd3153e";
+        const DEFINITION: &'static str = "int64 a\nint64 b\n";
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct AddTwoIntsResponse {
+        sum: i64,
+    }
+
+    impl RosMessage for AddTwoIntsResponse {
+        const ROS_TYPE: &'static str = "rospy_tutorials/AddTwoIntsResponse";
+        const MD5SUM: &'static str = "b88405221c77b1878a3cbbfff53428d7";
+        const DEFINITION: &'static str = "int64 sum\n";
+    }
+
+    struct AddTwoInts;
+
+    impl RosService for AddTwoInts {
+        const ROS_TYPE: &'static str = "rospy_tutorials/AddTwoInts";
+        const MD5SUM: &'static str = "6a2e34150c00229791cc89ff309fff21";
+        type Request = AddTwoIntsRequest;
+        type Response = AddTwoIntsResponse;
+    }
+
+    #[test]
+    fn checks_recorded_headers() {
+        let header: ConnectionHeader = from_slice(include_bytes!("datatests/pose_req.bin"))
+            .unwrap();
+        header.check_message::<Pose>().unwrap();
+        assert_eq!(header.get("topic"), Some("/meow"));
+        let header: ConnectionHeader = from_slice(include_bytes!("datatests/pose_res.bin"))
+            .unwrap();
+        header.check_message::<Pose>().unwrap();
+        assert!(header.check_message::<AddTwoIntsRequest>().is_err());
+    }
+
+    #[test]
+    fn creates_message_headers() {
+        let header = ConnectionHeader::for_subscriber::<Pose>("/sub", "/meow");
+        assert_eq!(header.fields().len(), 6);
+        assert_eq!(header.get("callerid"), Some("/sub"));
+        assert_eq!(header.get("tcp_nodelay"), Some("0"));
+        assert_eq!(header.get("message_definition"), Some(Pose::DEFINITION));
+        let header = ConnectionHeader::for_publisher::<Pose>("/pub", "/meow");
+        assert_eq!(header.fields().len(), 6);
+        assert_eq!(header.get("latching"), Some("0"));
+        assert_eq!(header.get("md5sum"), Some(Pose::MD5SUM));
+        assert_eq!(from_slice::<ConnectionHeader>(&to_vec(&header).unwrap()).unwrap(),
+                   header);
+    }
+
+    #[test]
+    fn creates_service_headers() {
+        let header = ConnectionHeader::for_service_client::<AddTwoInts>("/client", "/add");
+        assert_eq!(header.get("service"), Some("/add"));
+        assert_eq!(header.get("md5sum"), Some("6a2e34150c00229791cc89ff309fff21"));
+        header.check_service::<AddTwoInts>().unwrap();
+        let header = ConnectionHeader::for_service_server::<AddTwoInts>("/server");
+        assert_eq!(header.get("request_type"),
+                   Some("rospy_tutorials/AddTwoIntsRequest"));
+        assert_eq!(header.get("response_type"),
+                   Some("rospy_tutorials/AddTwoIntsResponse"));
+        header.check_service::<AddTwoInts>().unwrap();
+    }
+
+    #[test]
+    fn accepts_wildcards() {
+        let mut header = ConnectionHeader::for_subscriber::<Pose>("/sub", "/meow");
+        header.insert("md5sum", "*");
+        header.insert("type", "*");
+        header.check_message::<Pose>().unwrap();
+        header.check_message::<AddTwoIntsRequest>().unwrap();
+        header.remove("type");
+        header.check_message::<Pose>().unwrap();
+    }
+
+    #[test]
+    fn rejects_mismatches() {
+        let mut header = ConnectionHeader::for_subscriber::<Pose>("/sub", "/meow");
+        header.insert("type", "geometry_msgs/Point");
+        match *header.check_message::<Pose>().unwrap_err().kind() {
+            ErrorKind::HeaderMismatch(ref field, ..) => assert_eq!(field, "type"),
+            ref kind => panic!("Unexpected error: {}", kind),
+        }
+        header.remove("md5sum");
+        match *header.check_message::<Pose>().unwrap_err().kind() {
+            ErrorKind::MissingHeaderField(ref field) => assert_eq!(field, "md5sum"),
+            ref kind => panic!("Unexpected error: {}", kind),
+        }
+        let header = ConnectionHeader::for_error("no such topic");
+        match *header.check_message::<Pose>().unwrap_err().kind() {
+            ErrorKind::RemoteError(ref message) => assert_eq!(message, "no such topic"),
+            ref kind => panic!("Unexpected error: {}", kind),
+        }
+    }
+}
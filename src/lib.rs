@@ -62,12 +62,23 @@
 
 #![recursion_limit = "1024"]
 
+#[cfg(feature = "bytes")]
+extern crate bytes;
 extern crate byteorder;
+#[cfg(feature = "async")]
+extern crate futures_io;
 #[macro_use]
 extern crate error_chain;
+#[cfg(test)]
+extern crate self as serde_rosmsg;
+extern crate md5;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde;
+#[cfg(feature = "async")]
+extern crate tokio;
+#[cfg(feature = "async")]
+extern crate tokio_util;
 
 #[doc(inline)]
 pub use self::ser::*;
@@ -75,8 +86,29 @@ pub use self::ser::*;
 pub use self::de::*;
 #[doc(inline)]
 pub use self::error::Error;
+#[doc(inline)]
+pub use self::message::{RosMessage, RosService};
 
 pub mod ser;
+#[cfg(feature = "bytes")]
+pub mod vectored;
 pub mod de;
 pub mod error;
+pub mod header;
+pub mod tcpros;
+pub mod udpros;
+#[cfg(feature = "async")]
+pub mod codec;
+#[cfg(feature = "async")]
+pub mod async_io;
+pub mod message;
+pub mod raw;
+pub mod schema;
+pub mod compat;
+pub mod codegen;
+pub mod reflect;
+pub mod types;
+pub mod dynamic;
+#[cfg(feature = "catalog")]
+pub mod catalog;
 mod datatests;
@@ -0,0 +1,57 @@
+//! Traits tying Rust types to their ROS identity.
+//!
+//! ROS identifies every message and service type by its name and MD5 sum.
+//! Message types also carry their full `message_definition`. All of those
+//! get exchanged within connection headers, and checked when connecting.
+//!
+//! The [`codegen`](../codegen/index.html) module implements these traits
+//! for all generated types.
+//!
+//! # Examples
+//!
+//! ```rust
+//! extern crate serde_rosmsg;
+//! #[macro_use]
+//! extern crate serde_derive;
+//! use serde_rosmsg::RosMessage;
+//!
+//! fn main() {
+//! #[derive(Debug,Serialize,Deserialize,PartialEq)]
+//! struct StringMsg {
+//!     data: String,
+//! }
+//!
+//! impl RosMessage for StringMsg {
+//!     const ROS_TYPE: &'static str = "std_msgs/String";
+//!     const MD5SUM: &'static str = "992ce8a1687cec8c8bd883ec73ca41d1";
+//!     const DEFINITION: &'static str = "string data\n";
+//! }
+//!
+//! assert_eq!(StringMsg::ROS_TYPE, "std_msgs/String");
+//! }
+//! ```
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// A message type with a known ROS identity.
+pub trait RosMessage: Serialize + DeserializeOwned {
+    /// Full name of the message type, like `geometry_msgs/Pose`.
+    const ROS_TYPE: &'static str;
+    /// MD5 sum of the message definition.
+    const MD5SUM: &'static str;
+    /// Full `message_definition`, including all dependencies.
+    const DEFINITION: &'static str;
+}
+
+/// A service type with a known ROS identity.
+pub trait RosService {
+    /// Full name of the service type, like `std_srvs/Trigger`.
+    const ROS_TYPE: &'static str;
+    /// MD5 sum of the service, combining the request and the response.
+    const MD5SUM: &'static str;
+    /// Message sent by clients.
+    type Request: RosMessage;
+    /// Message sent back by servers.
+    type Response: RosMessage;
+}
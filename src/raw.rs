@@ -0,0 +1,517 @@
+//! Messages of any type, kept in their encoded form.
+//!
+//! Nodes that relay, record or multiplex topics often don't care about the
+//! contents of the messages passing through them, and might not even know
+//! their types at compile time. A [`RawMessage`](struct.RawMessage.html)
+//! deserializes by capturing all of its data as it is, and serializes by
+//! writing that data back verbatim. Like `topic_tools::ShapeShifter`, it
+//! also carries the type, MD5 sum and definition of the message, usually
+//! taken from the connection header.
+//!
+//! When the type is known, but the message is only inspected now and then,
+//! [`Lazy`](struct.Lazy.html) keeps the captured data, and decodes it on
+//! first access instead.
+//!
+//! # Examples
+//!
+//! ```rust
+//! extern crate serde_rosmsg;
+//! #[macro_use]
+//! extern crate serde_derive;
+//! use serde_rosmsg::{from_slice, to_vec, RosMessage};
+//! use serde_rosmsg::header::ConnectionHeader;
+//! use serde_rosmsg::raw::RawMessage;
+//!
+//! fn main() {
+//! #[derive(Debug,Serialize,Deserialize,PartialEq)]
+//! struct StringMsg {
+//!     data: String,
+//! }
+//!
+//! impl RosMessage for StringMsg {
+//!     const ROS_TYPE: &'static str = "std_msgs/String";
+//!     const MD5SUM: &'static str = "992ce8a1687cec8c8bd883ec73ca41d1";
+//!     const DEFINITION: &'static str = "string data\n";
+//! }
+//!
+//! let header = ConnectionHeader::for_publisher::<StringMsg>("/talker", "/chatter");
+//! let data = to_vec(&StringMsg { data: "Hello".into() }).unwrap();
+//!
+//! let message = from_slice::<RawMessage>(&data).unwrap().with_header(&header);
+//! assert_eq!(message.ros_type(), "std_msgs/String");
+//! assert_eq!(message.data(), b"\x05\0\0\0Hello");
+//! assert_eq!(to_vec(&message).unwrap(), data);
+//! assert_eq!(message.decode::<StringMsg>().unwrap().data, "Hello");
+//! }
+//! ```
+
+use super::de::from_slice;
+use super::error::{ErrorKind, Result};
+use super::header::ConnectionHeader;
+use super::message::RosMessage;
+use super::ser::to_vec;
+use serde::de::DeserializeOwned;
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use std::cell::OnceCell;
+use std::fmt;
+
+/// Name of the newtype struct that captures or writes data verbatim.
+pub(crate) const TOKEN: &str = "$serde_rosmsg::RawMessage";
+
+/// Encoded message of any type, along with its type information.
+///
+/// When deserialized, the message takes up all remaining data, so it
+/// should be the whole value, or its last field. The type information
+/// starts out empty, and can be filled in with
+/// [`with_header`](#method.with_header) or
+/// [`with_type`](#method.with_type).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RawMessage {
+    ros_type: String,
+    md5sum: String,
+    definition: String,
+    data: Vec<u8>,
+}
+
+impl RawMessage {
+    /// Create a message from its encoded data, without the length prefix.
+    pub fn new(data: Vec<u8>) -> RawMessage {
+        RawMessage {
+            data,
+            ..RawMessage::default()
+        }
+    }
+
+    /// Encode a message of a known type.
+    pub fn encode<T>(message: &T) -> Result<RawMessage>
+        where T: RosMessage
+    {
+        let mut data = to_vec(message)?;
+        data.drain(..4);
+        Ok(RawMessage::new(data).with_type(T::ROS_TYPE, T::MD5SUM, T::DEFINITION))
+    }
+
+    /// Set the type information.
+    pub fn with_type(mut self, ros_type: &str, md5sum: &str, definition: &str) -> RawMessage {
+        self.ros_type = ros_type.into();
+        self.md5sum = md5sum.into();
+        self.definition = definition.into();
+        self
+    }
+
+    /// Set the type information from the header of the connection the
+    /// message came from.
+    ///
+    /// Missing fields are left empty.
+    pub fn with_header(self, header: &ConnectionHeader) -> RawMessage {
+        let ros_type = header.get("type").unwrap_or("");
+        let md5sum = header.get("md5sum").unwrap_or("");
+        let definition = header.get("message_definition").unwrap_or("");
+        self.with_type(ros_type, md5sum, definition)
+    }
+
+    /// Full name of the message type, like `geometry_msgs/Pose`.
+    pub fn ros_type(&self) -> &str {
+        &self.ros_type
+    }
+
+    /// MD5 sum of the message definition.
+    pub fn md5sum(&self) -> &str {
+        &self.md5sum
+    }
+
+    /// Full `message_definition`, including all dependencies.
+    pub fn definition(&self) -> &str {
+        &self.definition
+    }
+
+    /// Encoded data, without the length prefix.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Consume the message, returning its encoded data.
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Create the header for publishing messages of this type.
+    pub fn publisher_header(&self, caller_id: &str, topic: &str) -> ConnectionHeader {
+        let mut header = ConnectionHeader::new();
+        header.insert("callerid", caller_id);
+        header.insert("topic", topic);
+        header.insert("type", self.ros_type.as_str());
+        header.insert("md5sum", self.md5sum.as_str());
+        header.insert("message_definition", self.definition.as_str());
+        header.insert("latching", "0");
+        header
+    }
+
+    /// Decode the message as type `T`.
+    ///
+    /// Fails with
+    /// [`MismatchedMd5sum`](../error/enum.ErrorKind.html#variant.MismatchedMd5sum)
+    /// before decoding anything if the MD5 sum is not the one of `T`. The
+    /// MD5 sum `*` matches any type.
+    pub fn decode<T>(&self) -> Result<T>
+        where T: RosMessage
+    {
+        if self.md5sum != "*" && self.md5sum != T::MD5SUM {
+            bail!(ErrorKind::MismatchedMd5sum(T::MD5SUM.into(), self.md5sum.clone()));
+        }
+        decode_data(&self.data)
+    }
+}
+
+/// Decode data captured without its length prefix.
+fn decode_data<T>(data: &[u8]) -> Result<T>
+    where T: DeserializeOwned
+{
+    let mut message = Vec::with_capacity(4 + data.len());
+    message.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    message.extend_from_slice(data);
+    from_slice(&message)
+}
+
+/// Data written without a length prefix by the ROSMSG serializer.
+struct Verbatim<'a>(&'a [u8]);
+
+impl<'a> Serialize for Verbatim<'a> {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+impl Serialize for RawMessage {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        serializer.serialize_newtype_struct(TOKEN, &Verbatim(&self.data))
+    }
+}
+
+impl<'de> Deserialize<'de> for RawMessage {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<RawMessage, D::Error>
+        where D: Deserializer<'de>
+    {
+        struct RawVisitor;
+
+        impl<'de> de::Visitor<'de> for RawVisitor {
+            type Value = RawMessage;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("encoded message data")
+            }
+
+            fn visit_bytes<E>(self, value: &[u8]) -> ::std::result::Result<RawMessage, E>
+                where E: de::Error
+            {
+                Ok(RawMessage::new(value.to_vec()))
+            }
+
+            fn visit_byte_buf<E>(self, value: Vec<u8>) -> ::std::result::Result<RawMessage, E>
+                where E: de::Error
+            {
+                Ok(RawMessage::new(value))
+            }
+
+            fn visit_newtype_struct<D>(self,
+                                       deserializer: D)
+                                       -> ::std::result::Result<RawMessage, D::Error>
+                where D: Deserializer<'de>
+            {
+                ::types::bytes::deserialize(deserializer).map(RawMessage::new)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(TOKEN, RawVisitor)
+    }
+}
+
+/// Message that gets decoded on first access.
+///
+/// Like [`RawMessage`](struct.RawMessage.html), it captures all remaining
+/// data when deserialized, so it should be the whole value, or its last
+/// field. The decoded value is cached, and as long as it is not accessed
+/// mutably, the message serializes by writing the captured data back
+/// verbatim.
+///
+/// Capturing and writing data verbatim only happens with formats that are
+/// not human readable, like ROSMSG itself. With formats like JSON, the
+/// message deserializes and serializes just like `T`, decoding the captured
+/// data first if needed. Other binary formats take the verbatim path too,
+/// and store the ROSMSG data as a byte array.
+///
+/// # Examples
+///
+/// ```rust
+/// # use serde_rosmsg::{from_slice, to_vec};
+/// # use serde_rosmsg::raw::Lazy;
+/// let data = to_vec(&(3u8, String::from("Hi"))).unwrap();
+/// let mut message: Lazy<(u8, String)> = from_slice(&data).unwrap();
+/// assert!(!message.is_decoded());
+/// assert_eq!(message.get().unwrap().1, "Hi");
+/// assert_eq!(to_vec(&message).unwrap(), data);
+///
+/// message.get_mut().unwrap().0 = 4;
+/// assert_eq!(message.data(), None);
+/// assert_eq!(to_vec(&message).unwrap(), to_vec(&(4u8, "Hi")).unwrap());
+/// ```
+///
+/// Human readable formats see the decoded value:
+///
+/// ```rust
+/// # extern crate serde_json;
+/// # extern crate serde_rosmsg;
+/// # use serde_rosmsg::{from_slice, to_vec};
+/// # use serde_rosmsg::raw::Lazy;
+/// # fn main() {
+/// let data = to_vec(&(3u8, String::from("Hi"))).unwrap();
+/// let message: Lazy<(u8, String)> = from_slice(&data).unwrap();
+/// let json = serde_json::to_string(&message).unwrap();
+/// assert_eq!(json, r#"[3,"Hi"]"#);
+///
+/// let message: Lazy<(u8, String)> = serde_json::from_str(&json).unwrap();
+/// assert_eq!(message.data(), None);
+/// assert_eq!(to_vec(&message).unwrap(), data);
+///
+/// let broken: Lazy<(u8, String)> = Lazy::from_data(vec![1, 2]);
+/// assert!(serde_json::to_string(&broken).is_err());
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Lazy<T> {
+    data: Option<Vec<u8>>,
+    value: OnceCell<T>,
+}
+
+impl<T> Lazy<T>
+    where T: DeserializeOwned
+{
+    /// Wrap encoded data, without the length prefix.
+    pub fn from_data(data: Vec<u8>) -> Lazy<T> {
+        Lazy {
+            data: Some(data),
+            value: OnceCell::new(),
+        }
+    }
+
+    /// Get the value, decoding it if this is the first access.
+    ///
+    /// If decoding fails, the error is returned, and the next access tries
+    /// again.
+    pub fn get(&self) -> Result<&T> {
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+        let value = match self.data {
+            Some(ref data) => decode_data(data)?,
+            None => unreachable!("Lazy message has neither data nor value"),
+        };
+        Ok(self.value.get_or_init(|| value))
+    }
+
+    /// Get the value mutably, decoding it if this is the first access.
+    ///
+    /// The captured data gets dropped, so the message serializes from the
+    /// value afterwards.
+    pub fn get_mut(&mut self) -> Result<&mut T> {
+        self.get()?;
+        self.data = None;
+        Ok(self.value.get_mut().expect("Lazy message was just decoded"))
+    }
+
+    /// Consume the message, returning the value.
+    pub fn into_inner(self) -> Result<T> {
+        self.get()?;
+        Ok(self.value.into_inner().expect("Lazy message was just decoded"))
+    }
+}
+
+impl<T> Lazy<T> {
+    /// Wrap an already decoded value.
+    pub fn new(value: T) -> Lazy<T> {
+        Lazy {
+            data: None,
+            value: OnceCell::from(value),
+        }
+    }
+
+    /// Check if the value was decoded already.
+    pub fn is_decoded(&self) -> bool {
+        self.value.get().is_some()
+    }
+
+    /// Captured data, without the length prefix.
+    ///
+    /// This is `None` once the value was accessed mutably, or if the message
+    /// was created from a value.
+    pub fn data(&self) -> Option<&[u8]> {
+        self.data.as_ref().map(|data| &data[..])
+    }
+}
+
+impl<T> From<T> for Lazy<T> {
+    fn from(value: T) -> Lazy<T> {
+        Lazy::new(value)
+    }
+}
+
+impl<T> Serialize for Lazy<T>
+    where T: Serialize + DeserializeOwned
+{
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        match self.data() {
+            Some(data) if !serializer.is_human_readable() => {
+                serializer.serialize_newtype_struct(TOKEN, &Verbatim(data))
+            }
+            _ => self.get().map_err(ser::Error::custom)?.serialize(serializer),
+        }
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Lazy<T>
+    where T: DeserializeOwned
+{
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Lazy<T>, D::Error>
+        where D: Deserializer<'de>
+    {
+        if deserializer.is_human_readable() {
+            return T::deserialize(deserializer).map(Lazy::new);
+        }
+        RawMessage::deserialize(deserializer).map(|message| Lazy::from_data(message.into_data()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{from_reader, from_slice, to_vec};
+
+    #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+    struct Point {
+        x: f64,
+        y: f64,
+        z: f64,
+    }
+
+    impl RosMessage for Point {
+        const ROS_TYPE: &'static str = "geometry_msgs/Point";
+        const MD5SUM: &'static str = "4a842b65f413084dc2b10fb484ea7f17";
+        const DEFINITION: &'static str = "float64 x\nfloat64 y\nfloat64 z\n";
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Envelope {
+        id: u32,
+        payload: RawMessage,
+    }
+
+    fn point() -> Point {
+        Point {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        }
+    }
+
+    #[test]
+    fn relays_data_verbatim() {
+        let data = to_vec(&point()).unwrap();
+        let message: RawMessage = from_slice(&data).unwrap();
+        assert_eq!(message.data(), &data[4..]);
+        assert_eq!(message.md5sum(), "");
+        assert_eq!(to_vec(&message).unwrap(), data);
+        let mut cursor = ::std::io::Cursor::new(data.clone());
+        assert_eq!(from_reader::<_, RawMessage>(&mut cursor).unwrap().into_data(),
+                   data[4..].to_vec());
+    }
+
+    #[test]
+    fn captures_remaining_data() {
+        let mut data = to_vec(&(7u32, point())).unwrap();
+        let envelope: Envelope = from_slice(&data).unwrap();
+        assert_eq!(envelope.id, 7);
+        assert_eq!(envelope.payload.data(), &data[8..]);
+        assert_eq!(to_vec(&envelope).unwrap(), data);
+        data[0] += 1;
+        data.push(0);
+        assert_eq!(from_slice::<Envelope>(&data).unwrap().payload.data().len(), 25);
+    }
+
+    #[test]
+    fn checks_md5sum_when_decoding() {
+        let message = RawMessage::encode(&point()).unwrap();
+        assert_eq!(message.ros_type(), Point::ROS_TYPE);
+        assert_eq!(message.decode::<Point>().unwrap(), point());
+        let header = message.publisher_header("/relay", "/points");
+        header.check_message::<Point>().unwrap();
+
+        let message = RawMessage::new(message.into_data());
+        match *message.decode::<Point>().unwrap_err().kind() {
+            ErrorKind::MismatchedMd5sum(ref expected, ref actual) => {
+                assert_eq!(expected, Point::MD5SUM);
+                assert_eq!(actual, "");
+            }
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+        let message = message.with_type("*", "*", "");
+        assert_eq!(message.decode::<Point>().unwrap(), point());
+    }
+
+    #[test]
+    fn decodes_lazily_once() {
+        let data = to_vec(&point()).unwrap();
+        let message: Lazy<Point> = from_slice(&data).unwrap();
+        assert!(!message.is_decoded());
+        assert_eq!(message.data(), Some(&data[4..]));
+        let first = message.get().unwrap() as *const Point;
+        assert!(message.is_decoded());
+        assert_eq!(message.get().unwrap() as *const Point, first);
+        assert_eq!(to_vec(&message).unwrap(), data);
+        assert_eq!(message.clone().into_inner().unwrap(), point());
+    }
+
+    #[test]
+    fn serializes_value_once_modified() {
+        #[derive(Serialize, Deserialize)]
+        struct Tagged {
+            id: u32,
+            point: Lazy<Point>,
+        }
+
+        let data = to_vec(&(7u32, point())).unwrap();
+        let mut tagged: Tagged = from_slice(&data).unwrap();
+        assert_eq!(to_vec(&tagged).unwrap(), data);
+        tagged.point.get_mut().unwrap().z = 4.0;
+        assert_eq!(tagged.point.data(), None);
+        let expected = Point { z: 4.0, ..point() };
+        assert_eq!(to_vec(&tagged).unwrap(), to_vec(&(7u32, expected)).unwrap());
+        assert_eq!(to_vec(&Lazy::new(point())).unwrap(), to_vec(&point()).unwrap());
+    }
+
+    #[test]
+    fn keeps_data_when_decoding_fails() {
+        let message: Lazy<Point> = Lazy::from_data(vec![1, 2, 3]);
+        assert!(message.get().is_err());
+        assert!(!message.is_decoded());
+        assert_eq!(to_vec(&message).unwrap(), b"\x03\0\0\0\x01\x02\x03");
+    }
+
+    #[test]
+    fn decodes_with_self_describing_deserializers() {
+        use dynamic;
+        use schema::Schema;
+
+        let data = to_vec(&point()).unwrap();
+        let schema = Schema::from_definition(Point::ROS_TYPE, Point::DEFINITION).unwrap();
+        let message: Lazy<Point> = dynamic::from_slice(&schema, &data).unwrap();
+        assert!(message.is_decoded());
+        assert_eq!(message.data(), None);
+        assert_eq!(to_vec(&message).unwrap(), data);
+    }
+}
@@ -0,0 +1,793 @@
+//! Generate ROS message definitions out of Rust types.
+//!
+//! This goes the opposite way of [`codegen`](../codegen/index.html). Types
+//! that already get serialized with this crate can be traced, to find the
+//! message definition that matches their serialized form. That definition
+//! provides the MD5 sum and the `message_definition` that tools like `rqt`
+//! and `rosbag` need.
+//!
+//! Tracing works just like with
+//! [`serde-reflection`](https://crates.io/crates/serde-reflection). The type
+//! gets deserialized out of a deserializer that makes up a sample value,
+//! with a single item in each sequence. That sample then gets serialized
+//! into a serializer that records the layout.
+//!
+//! Rust primitives map onto the matching builtin types, `String` onto
+//! `string`, `Vec` onto variable size arrays, fixed size arrays and tuples
+//! of a single type onto fixed size arrays, and structs onto messages.
+//! The crate's `Time` and `Duration` map onto `time` and `duration`.
+//!
+//! Anything that ROS messages cannot express, like options, enums, maps,
+//! `char`s, or arrays of arrays, makes tracing fail.
+//!
+//! # Examples
+//!
+//! ```rust
+//! extern crate serde_rosmsg;
+//! #[macro_use]
+//! extern crate serde_derive;
+//! use serde_rosmsg::reflect::trace;
+//!
+//! fn main() {
+//! #[derive(Serialize, Deserialize)]
+//! struct Point {
+//!     x: f64,
+//!     y: f64,
+//!     z: f64,
+//! }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Path {
+//!     name: String,
+//!     points: Vec<Point>,
+//! }
+//!
+//! let schema = trace::<Path>("my_msgs/Path").unwrap();
+//! assert_eq!(schema.message_definition().unwrap(),
+//!            "string name\nmy_msgs/Point[] points\n\n\
+//!             ================================================\
+//!             ================================\n\
+//!             MSG: my_msgs/Point\nfloat64 x\nfloat64 y\nfloat64 z\n");
+//! assert_eq!(schema.md5sum().unwrap(), "c34d94320f42d4394bd5df55a820bc9f");
+//! }
+//! ```
+
+use serde::de::{self, Deserialize, DeserializeSeed, Visitor};
+use serde::ser::{self, Serialize};
+use super::error::{Error, ErrorKind, Result};
+use super::schema::{BaseType, FieldType, Msg, Schema};
+use std::collections::HashMap;
+
+/// Trace the type, naming its message after the given full name.
+///
+/// Nested structs become messages within the same package, named after
+/// their Rust names, apart from `Header`, which maps onto `std_msgs/Header`.
+/// Use a [`Tracer`](struct.Tracer.html) to name them differently.
+pub fn trace<'de, T>(ros_type: &str) -> Result<Schema>
+    where T: Serialize + Deserialize<'de>
+{
+    Tracer::new().trace::<T>(ros_type)
+}
+
+/// Traces Rust types into message definitions, with configurable names.
+///
+/// # Examples
+///
+/// ```rust
+/// extern crate serde_rosmsg;
+/// #[macro_use]
+/// extern crate serde_derive;
+/// use serde_rosmsg::reflect::Tracer;
+///
+/// fn main() {
+/// #[derive(Serialize, Deserialize)]
+/// struct Vector3 {
+///     x: f64,
+///     y: f64,
+///     z: f64,
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Twist {
+///     linear: Vector3,
+///     angular: Vector3,
+/// }
+///
+/// let schema = Tracer::new()
+///     .message("Vector3", "geometry_msgs/Vector3")
+///     .trace::<Twist>("geometry_msgs/Twist")
+///     .unwrap();
+/// assert_eq!(schema.md5sum().unwrap(), "9f195f881246fdfa2798d1d3eebca84a");
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Tracer {
+    names: HashMap<String, String>,
+}
+
+impl Default for Tracer {
+    fn default() -> Tracer {
+        Tracer::new()
+    }
+}
+
+impl Tracer {
+    /// Create a tracer, which only maps `Header` onto `std_msgs/Header`.
+    pub fn new() -> Tracer {
+        let mut names = HashMap::new();
+        names.insert("Header".into(), "std_msgs/Header".into());
+        Tracer { names }
+    }
+
+    /// Name the message of the struct with the given Rust name.
+    pub fn message(mut self, rust_name: &str, ros_type: &str) -> Tracer {
+        self.names.insert(rust_name.into(), ros_type.into());
+        self
+    }
+
+    /// Trace the type, naming its message after the given full name.
+    pub fn trace<'de, T>(&self, ros_type: &str) -> Result<Schema>
+        where T: Serialize + Deserialize<'de>
+    {
+        let package = match ros_type.find('/') {
+            Some(index) => &ros_type[..index],
+            None => {
+                bail!(ErrorKind::BadMessageDefinition(format!("{} is missing a package",
+                                                              ros_type)))
+            }
+        };
+        let sample = T::deserialize(Sampler { stack: &mut Vec::new() })?;
+        let mut recorder = Recorder {
+            tracer: self,
+            package,
+            root: ros_type,
+            depth: 0,
+            messages: Vec::new(),
+        };
+        match sample.serialize(&mut recorder)? {
+            FieldType::Single(BaseType::Message(ref name)) if name == ros_type => {}
+            _ => bail!(ErrorKind::UnrepresentableType("messages need to be structs".into())),
+        }
+        let mut messages = recorder
+            .messages
+            .into_iter()
+            .map(|(name, fields)| Msg::new(&name, &definition(&fields)))
+            .collect::<Result<Vec<_>>>()?;
+        // Nested messages get finished first, so the root comes last
+        let root = messages.pop().ok_or(ErrorKind::UnknownMessageType(ros_type.into()))?;
+        Schema::new(root, messages)
+    }
+}
+
+fn definition(fields: &[(String, FieldType)]) -> String {
+    fields
+        .iter()
+        .map(|(name, field_type)| match *field_type.base() {
+                 BaseType::Message(ref message) if message == "std_msgs/Header" => {
+                     format!("{} {}\n", field_type.to_string().replace(message, "Header"), name)
+                 }
+                 _ => format!("{} {}\n", field_type, name),
+             })
+        .collect()
+}
+
+fn unrepresentable<T>(description: &str) -> Result<T> {
+    bail!(ErrorKind::UnrepresentableType(description.into()))
+}
+
+/// Deserializer making up a sample value.
+struct Sampler<'s> {
+    stack: &'s mut Vec<&'static str>,
+}
+
+macro_rules! sample_primitive {
+    ($method:ident, $visit:ident, $value:expr) => {
+        fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where V: Visitor<'de>
+        {
+            visitor.$visit($value)
+        }
+    }
+}
+
+macro_rules! fail_on {
+    ($($method:ident: $description:expr),*) => {
+        $(
+            fn $method<V>(self, _visitor: V) -> Result<V::Value>
+                where V: Visitor<'de>
+            {
+                unrepresentable($description)
+            }
+        )*
+    }
+}
+
+impl<'de, 's> de::Deserializer<'de> for Sampler<'s> {
+    type Error = Error;
+
+    sample_primitive!(deserialize_bool, visit_bool, false);
+    sample_primitive!(deserialize_i8, visit_i8, 0);
+    sample_primitive!(deserialize_i16, visit_i16, 0);
+    sample_primitive!(deserialize_i32, visit_i32, 0);
+    sample_primitive!(deserialize_i64, visit_i64, 0);
+    sample_primitive!(deserialize_u8, visit_u8, 0);
+    sample_primitive!(deserialize_u16, visit_u16, 0);
+    sample_primitive!(deserialize_u32, visit_u32, 0);
+    sample_primitive!(deserialize_u64, visit_u64, 0);
+    sample_primitive!(deserialize_f32, visit_f32, 0.0);
+    sample_primitive!(deserialize_f64, visit_f64, 0.0);
+    sample_primitive!(deserialize_str, visit_borrowed_str, "");
+    sample_primitive!(deserialize_string, visit_string, String::new());
+    sample_primitive!(deserialize_bytes, visit_borrowed_bytes, &[]);
+    sample_primitive!(deserialize_byte_buf, visit_byte_buf, Vec::new());
+
+    fail_on!(deserialize_any: "types that are not known upfront",
+             deserialize_i128: "128-bit integers",
+             deserialize_u128: "128-bit integers",
+             deserialize_option: "options",
+             deserialize_unit: "units",
+             deserialize_map: "maps",
+             deserialize_identifier: "identifiers",
+             deserialize_ignored_any: "ignored values");
+
+    fn deserialize_char<V>(self, _visitor: V) -> Result<V::Value>
+        where V: Visitor<'de>
+    {
+        bail!(ErrorKind::UnsupportedCharType)
+    }
+
+    fn deserialize_unit_struct<V>(self, name: &'static str, _visitor: V) -> Result<V::Value>
+        where V: Visitor<'de>
+    {
+        unrepresentable(&format!("unit struct {}", name))
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+        where V: Visitor<'de>
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+        where V: Visitor<'de>
+    {
+        visitor.visit_seq(Items {
+                              stack: self.stack,
+                              remaining: 1,
+                          })
+    }
+
+    fn deserialize_tuple<V>(self, length: usize, visitor: V) -> Result<V::Value>
+        where V: Visitor<'de>
+    {
+        visitor.visit_seq(Items {
+                              stack: self.stack,
+                              remaining: length,
+                          })
+    }
+
+    fn deserialize_tuple_struct<V>(self,
+                                   _name: &'static str,
+                                   length: usize,
+                                   visitor: V)
+                                   -> Result<V::Value>
+        where V: Visitor<'de>
+    {
+        self.deserialize_tuple(length, visitor)
+    }
+
+    fn deserialize_struct<V>(self,
+                             name: &'static str,
+                             fields: &'static [&'static str],
+                             visitor: V)
+                             -> Result<V::Value>
+        where V: Visitor<'de>
+    {
+        // Samples of recursive types would never end
+        if self.stack.contains(&name) {
+            return unrepresentable(&format!("recursive struct {}", name));
+        }
+        self.stack.push(name);
+        let value = visitor.visit_seq(Items {
+                                          stack: &mut *self.stack,
+                                          remaining: fields.len(),
+                                      })?;
+        self.stack.pop();
+        Ok(value)
+    }
+
+    fn deserialize_enum<V>(self,
+                           _name: &'static str,
+                           _variants: &'static [&'static str],
+                           _visitor: V)
+                           -> Result<V::Value>
+        where V: Visitor<'de>
+    {
+        bail!(ErrorKind::UnsupportedEnumType)
+    }
+}
+
+/// Made up items of a sequence, tuple or struct.
+struct Items<'s> {
+    stack: &'s mut Vec<&'static str>,
+    remaining: usize,
+}
+
+impl<'de, 's> de::SeqAccess<'de> for Items<'s> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+        where T: DeserializeSeed<'de>
+    {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(Sampler { stack: &mut *self.stack }).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+/// Serializer recording the layout of the sample value.
+struct Recorder<'a> {
+    tracer: &'a Tracer,
+    package: &'a str,
+    root: &'a str,
+    depth: usize,
+    messages: Vec<(String, Vec<(String, FieldType)>)>,
+}
+
+impl<'a> Recorder<'a> {
+    fn ros_type(&self, name: &str) -> String {
+        if self.depth == 0 {
+            return self.root.into();
+        }
+        match self.tracer.names.get(name) {
+            Some(ros_type) => ros_type.clone(),
+            None => format!("{}/{}", self.package, name),
+        }
+    }
+
+    fn record(&mut self, ros_type: String, fields: Vec<(String, FieldType)>) -> Result<()> {
+        match self.messages.iter().find(|message| message.0 == ros_type) {
+            Some(message) if message.1 != fields => {
+                bail!(ErrorKind::BadMessageDefinition(format!("conflicting definitions of {}",
+                                                              ros_type)))
+            }
+            Some(_) => {}
+            None => self.messages.push((ros_type, fields)),
+        }
+        Ok(())
+    }
+}
+
+macro_rules! record_primitive {
+    ($method:ident, $type:ty, $base:ident) => {
+        fn $method(self, _value: $type) -> Result<FieldType> {
+            Ok(FieldType::Single(BaseType::$base))
+        }
+    }
+}
+
+impl<'r, 'a> ser::Serializer for &'r mut Recorder<'a> {
+    type Ok = FieldType;
+    type Error = Error;
+    type SerializeSeq = Compound<'r, 'a>;
+    type SerializeTuple = Compound<'r, 'a>;
+    type SerializeTupleStruct = Compound<'r, 'a>;
+    type SerializeTupleVariant = ser::Impossible<FieldType, Error>;
+    type SerializeMap = ser::Impossible<FieldType, Error>;
+    type SerializeStruct = Compound<'r, 'a>;
+    type SerializeStructVariant = ser::Impossible<FieldType, Error>;
+
+    record_primitive!(serialize_bool, bool, Bool);
+    record_primitive!(serialize_i8, i8, Int8);
+    record_primitive!(serialize_i16, i16, Int16);
+    record_primitive!(serialize_i32, i32, Int32);
+    record_primitive!(serialize_i64, i64, Int64);
+    record_primitive!(serialize_u8, u8, UInt8);
+    record_primitive!(serialize_u16, u16, UInt16);
+    record_primitive!(serialize_u32, u32, UInt32);
+    record_primitive!(serialize_u64, u64, UInt64);
+    record_primitive!(serialize_f32, f32, Float32);
+    record_primitive!(serialize_f64, f64, Float64);
+    record_primitive!(serialize_str, &str, String);
+
+    fn serialize_char(self, _value: char) -> Result<FieldType> {
+        bail!(ErrorKind::UnsupportedCharType)
+    }
+
+    fn serialize_bytes(self, _value: &[u8]) -> Result<FieldType> {
+        Ok(FieldType::Array(BaseType::UInt8))
+    }
+
+    fn serialize_none(self) -> Result<FieldType> {
+        unrepresentable("options")
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<FieldType> {
+        unrepresentable("options")
+    }
+
+    fn serialize_unit(self) -> Result<FieldType> {
+        unrepresentable("units")
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<FieldType> {
+        unrepresentable(&format!("unit struct {}", name))
+    }
+
+    fn serialize_unit_variant(self,
+                              _name: &'static str,
+                              _variant_index: u32,
+                              _variant: &'static str)
+                              -> Result<FieldType> {
+        bail!(ErrorKind::UnsupportedEnumType)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self,
+                                                       _name: &'static str,
+                                                       value: &T)
+                                                       -> Result<FieldType> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self,
+                                                        _name: &'static str,
+                                                        _variant_index: u32,
+                                                        _variant: &'static str,
+                                                        _value: &T)
+                                                        -> Result<FieldType> {
+        bail!(ErrorKind::UnsupportedEnumType)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'r, 'a>> {
+        Ok(Compound::new(self, None))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Compound<'r, 'a>> {
+        Ok(Compound::new(self, Some(len)))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Compound<'r, 'a>> {
+        Ok(Compound::new(self, Some(len)))
+    }
+
+    fn serialize_tuple_variant(self,
+                               _name: &'static str,
+                               _variant_index: u32,
+                               _variant: &'static str,
+                               _len: usize)
+                               -> Result<Self::SerializeTupleVariant> {
+        bail!(ErrorKind::UnsupportedEnumType)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        unrepresentable("maps")
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Compound<'r, 'a>> {
+        let ros_type = self.ros_type(name);
+        self.depth += 1;
+        Ok(Compound {
+               recorder: self,
+               length: None,
+               items: Vec::new(),
+               message: Some((name, ros_type)),
+           })
+    }
+
+    fn serialize_struct_variant(self,
+                                _name: &'static str,
+                                _variant_index: u32,
+                                _variant: &'static str,
+                                _len: usize)
+                                -> Result<Self::SerializeStructVariant> {
+        bail!(ErrorKind::UnsupportedEnumType)
+    }
+}
+
+/// Layouts of the items of an array, or fields of a message.
+struct Compound<'r, 'a: 'r> {
+    recorder: &'r mut Recorder<'a>,
+    length: Option<usize>,
+    items: Vec<(String, FieldType)>,
+    message: Option<(&'static str, String)>,
+}
+
+impl<'r, 'a> Compound<'r, 'a> {
+    fn new(recorder: &'r mut Recorder<'a>, length: Option<usize>) -> Self {
+        Compound {
+            recorder,
+            length,
+            items: Vec::new(),
+            message: None,
+        }
+    }
+
+    fn push<T: ?Sized + Serialize>(&mut self, name: &str, value: &T) -> Result<()> {
+        let field_type = value.serialize(&mut *self.recorder)?;
+        self.items.push((name.into(), field_type));
+        Ok(())
+    }
+
+    /// Type of the items of an array, which all need to be the same.
+    fn array(self) -> Result<FieldType> {
+        let mut base = None;
+        for (_, field_type) in self.items {
+            match field_type {
+                FieldType::Single(item) => {
+                    match base {
+                        Some(ref base) if *base != item => {
+                            return unrepresentable("tuples of different types")
+                        }
+                        _ => base = Some(item),
+                    }
+                }
+                _ => return unrepresentable("arrays of arrays"),
+            }
+        }
+        let base = match base {
+            Some(base) => base,
+            None => return unrepresentable("empty arrays"),
+        };
+        Ok(match self.length {
+               Some(length) => FieldType::FixedArray(base, length),
+               None => FieldType::Array(base),
+           })
+    }
+}
+
+impl<'r, 'a> ser::SerializeSeq for Compound<'r, 'a> {
+    type Ok = FieldType;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.push("", value)
+    }
+
+    fn end(self) -> Result<FieldType> {
+        self.array()
+    }
+}
+
+impl<'r, 'a> ser::SerializeTuple for Compound<'r, 'a> {
+    type Ok = FieldType;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.push("", value)
+    }
+
+    fn end(self) -> Result<FieldType> {
+        self.array()
+    }
+}
+
+impl<'r, 'a> ser::SerializeTupleStruct for Compound<'r, 'a> {
+    type Ok = FieldType;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.push("", value)
+    }
+
+    fn end(self) -> Result<FieldType> {
+        self.array()
+    }
+}
+
+impl<'r, 'a> ser::SerializeStruct for Compound<'r, 'a> {
+    type Ok = FieldType;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self,
+                                              key: &'static str,
+                                              value: &T)
+                                              -> Result<()> {
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<FieldType> {
+        self.recorder.depth -= 1;
+        let (name, ros_type) = self.message.unwrap_or_default();
+        let builtin = match (name, &self.items[..]) {
+            ("Time", [(sec, a), (nsec, b)]) => {
+                Some((BaseType::Time, BaseType::UInt32, sec, nsec, a, b))
+            }
+            ("Duration", [(sec, a), (nsec, b)]) => {
+                Some((BaseType::Duration, BaseType::Int32, sec, nsec, a, b))
+            }
+            _ => None,
+        };
+        if let Some((base, part, sec, nsec, a, b)) = builtin {
+            let part = FieldType::Single(part);
+            if sec == "sec" && nsec == "nsec" && *a == part && *b == part {
+                return Ok(FieldType::Single(base));
+            }
+        }
+        self.recorder.record(ros_type.clone(), self.items)?;
+        Ok(FieldType::Single(BaseType::Message(ros_type)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::types::{self, Duration, Time};
+
+    #[derive(Serialize, Deserialize)]
+    struct Point {
+        x: f64,
+        y: f64,
+        z: f64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Quaternion {
+        x: f64,
+        y: f64,
+        z: f64,
+        w: f64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Pose {
+        position: Point,
+        orientation: Quaternion,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Header {
+        seq: u32,
+        stamp: Time,
+        frame_id: String,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct PoseArray {
+        header: Header,
+        poses: Vec<Pose>,
+    }
+
+    fn geometry_tracer() -> Tracer {
+        Tracer::new()
+            .message("Point", "geometry_msgs/Point")
+            .message("Quaternion", "geometry_msgs/Quaternion")
+            .message("Pose", "geometry_msgs/Pose")
+    }
+
+    #[test]
+    fn matches_standard_messages() {
+        let schema = geometry_tracer().trace::<PoseArray>("geometry_msgs/PoseArray").unwrap();
+        assert_eq!(schema.md5sum().unwrap(), "916c28c5764443f268b296bb671b9d97");
+        let recorded = Schema::from_definition("geometry_msgs/PoseArray",
+                                               include_str!("datatests/\
+                                                             pose_array_message_definition.txt"))
+            .unwrap();
+        assert_eq!(schema.md5sum_of("std_msgs/Header").unwrap(),
+                   recorded.md5sum_of("std_msgs/Header").unwrap());
+        assert_eq!(schema.root().source, "Header header\ngeometry_msgs/Pose[] poses\n");
+        let schema = geometry_tracer().trace::<Pose>("geometry_msgs/Pose").unwrap();
+        assert_eq!(schema.md5sum().unwrap(), "e45d45a5a1ce597b249e23fb30fc871f");
+    }
+
+    #[test]
+    fn maps_all_builtin_types() {
+        #[derive(Serialize, Deserialize)]
+        struct Bits(u8, u8, u8);
+
+        #[derive(Serialize, Deserialize)]
+        struct Meters(f32);
+
+        #[derive(Serialize, Deserialize)]
+        struct Data<'a> {
+            a: bool,
+            b: i8,
+            c: u8,
+            d: i16,
+            e: u16,
+            f: i32,
+            g: u32,
+            h: i64,
+            i: u64,
+            j: f32,
+            k: f64,
+            l: String,
+            m: &'a str,
+            n: Time,
+            o: Duration,
+            p: Vec<u8>,
+            #[serde(with = "types::bytes")]
+            q: Vec<u8>,
+            r: [i16; 4],
+            #[serde(with = "types::big_array")]
+            s: [f64; 36],
+            t: Vec<String>,
+            u: Vec<Time>,
+            v: Bits,
+            w: Meters,
+            x: (u32, u32),
+        }
+
+        let schema = trace::<Data>("my_msgs/Data").unwrap();
+        assert_eq!(schema.message_definition().unwrap(),
+                   "bool a\nint8 b\nuint8 c\nint16 d\nuint16 e\nint32 f\nuint32 g\nint64 h\n\
+                    uint64 i\nfloat32 j\nfloat64 k\nstring l\nstring m\ntime n\nduration o\n\
+                    uint8[] p\nuint8[] q\nint16[4] r\nfloat64[36] s\nstring[] t\ntime[] u\n\
+                    uint8[3] v\nfloat32 w\nuint32[2] x\n");
+    }
+
+    #[test]
+    fn fails_on_unrepresentable_types() {
+        #[derive(Serialize, Deserialize)]
+        struct WithOption {
+            a: Option<u8>,
+        }
+        #[derive(Serialize, Deserialize)]
+        enum Mode {
+            On,
+            Off,
+        }
+        #[derive(Serialize, Deserialize)]
+        struct WithEnum {
+            a: Mode,
+        }
+        #[derive(Serialize, Deserialize)]
+        struct WithNesting {
+            a: Vec<Vec<u8>>,
+        }
+        #[derive(Serialize, Deserialize)]
+        struct WithTuple {
+            a: (u8, i8),
+        }
+        #[derive(Serialize, Deserialize)]
+        struct WithMap {
+            a: HashMap<String, String>,
+        }
+        #[derive(Serialize, Deserialize)]
+        struct WithChar {
+            a: char,
+        }
+        #[derive(Serialize, Deserialize)]
+        struct Node {
+            children: Vec<Node>,
+        }
+
+        let error = |result: Result<Schema>| result.unwrap_err().to_string();
+        let prefix = "Type cannot be represented within a ROS message: ";
+        assert_eq!(error(trace::<WithOption>("my_msgs/A")), format!("{}options", prefix));
+        assert_eq!(error(trace::<WithNesting>("my_msgs/A")),
+                   format!("{}arrays of arrays", prefix));
+        assert_eq!(error(trace::<WithTuple>("my_msgs/A")),
+                   format!("{}tuples of different types", prefix));
+        assert_eq!(error(trace::<WithMap>("my_msgs/A")), format!("{}maps", prefix));
+        assert_eq!(error(trace::<Node>("my_msgs/A")),
+                   format!("{}recursive struct Node", prefix));
+        assert_eq!(error(trace::<u8>("my_msgs/A")),
+                   format!("{}messages need to be structs", prefix));
+        assert!(trace::<WithEnum>("my_msgs/A").is_err());
+        assert!(trace::<WithChar>("my_msgs/A").is_err());
+        assert!(trace::<Point>("Point").is_err());
+    }
+
+    #[test]
+    fn detects_conflicting_names() {
+        mod other {
+            #[derive(Serialize, Deserialize)]
+            pub struct Point {
+                pub x: i32,
+            }
+        }
+        #[derive(Serialize, Deserialize)]
+        struct Shape {
+            a: Point,
+            b: other::Point,
+        }
+        assert_eq!(trace::<Shape>("my_msgs/Shape").unwrap_err().to_string(),
+                   "Message definition is invalid: conflicting definitions of my_msgs/Point");
+    }
+}
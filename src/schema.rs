@@ -0,0 +1,818 @@
+//! Parse ROS message definitions.
+//!
+//! Message definitions are written in ROS's own
+//! [format](http://wiki.ros.org/msg#Message_Description_Specification).
+//! A parsed definition describes the layout of serialized data, and is used
+//! to compute the MD5 sum and the full `message_definition` text that get
+//! exchanged in connection headers.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use serde_rosmsg::schema::Schema;
+//! let schema = Schema::from_definition("std_msgs/String", "string data\n").unwrap();
+//! assert_eq!(schema.md5sum().unwrap(), "992ce8a1687cec8c8bd883ec73ca41d1");
+//! assert_eq!(schema.message_definition().unwrap(), "string data\n");
+//! ```
+
+use md5;
+use super::error::{ErrorKind, Result};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Type of a single value stored within a field.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BaseType {
+    Bool,
+    Int8,
+    /// Deprecated alias for `int8`, kept apart because it affects MD5 sums.
+    Byte,
+    UInt8,
+    /// Deprecated alias for `uint8`, kept apart because it affects MD5 sums.
+    Char,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    Float32,
+    Float64,
+    String,
+    Time,
+    Duration,
+    /// Nested message, identified by its full name, like `geometry_msgs/Point`.
+    Message(String),
+}
+
+impl BaseType {
+    /// Parse a type name, as written within a message of the given package.
+    ///
+    /// Message names without a package are resolved relative to the package,
+    /// except for `Header`, which always refers to `std_msgs/Header`.
+    pub fn parse(name: &str, package: &str) -> Result<BaseType> {
+        Ok(match name {
+            "bool" => BaseType::Bool,
+            "int8" => BaseType::Int8,
+            "byte" => BaseType::Byte,
+            "uint8" => BaseType::UInt8,
+            "char" => BaseType::Char,
+            "int16" => BaseType::Int16,
+            "uint16" => BaseType::UInt16,
+            "int32" => BaseType::Int32,
+            "uint32" => BaseType::UInt32,
+            "int64" => BaseType::Int64,
+            "uint64" => BaseType::UInt64,
+            "float32" => BaseType::Float32,
+            "float64" => BaseType::Float64,
+            "string" => BaseType::String,
+            "time" => BaseType::Time,
+            "duration" => BaseType::Duration,
+            "Header" => BaseType::Message("std_msgs/Header".into()),
+            _ => {
+                let full_name = if name.contains('/') {
+                    name.to_owned()
+                } else {
+                    format!("{}/{}", package, name)
+                };
+                check_full_name(&full_name)?;
+                BaseType::Message(full_name)
+            }
+        })
+    }
+
+    /// Check if the type is a builtin type, rather than a nested message.
+    pub fn is_builtin(&self) -> bool {
+        !matches!(*self, BaseType::Message(..))
+    }
+
+    /// Number of bytes taken by a value of this type, if it's constant.
+    pub fn size(&self) -> Option<usize> {
+        match *self {
+            BaseType::Bool | BaseType::Int8 | BaseType::Byte | BaseType::UInt8 |
+            BaseType::Char => Some(1),
+            BaseType::Int16 | BaseType::UInt16 => Some(2),
+            BaseType::Int32 | BaseType::UInt32 | BaseType::Float32 => Some(4),
+            BaseType::Int64 | BaseType::UInt64 | BaseType::Float64 | BaseType::Time |
+            BaseType::Duration => Some(8),
+            BaseType::String | BaseType::Message(..) => None,
+        }
+    }
+
+    /// Range of values representable by the type, if it's an integer type.
+    pub fn integer_range(&self) -> Option<(i128, i128)> {
+        Some(match *self {
+                 BaseType::Int8 | BaseType::Byte => (i8::MIN as i128, i8::MAX as i128),
+                 BaseType::UInt8 | BaseType::Char => (0, u8::MAX as i128),
+                 BaseType::Int16 => (i16::MIN as i128, i16::MAX as i128),
+                 BaseType::UInt16 => (0, u16::MAX as i128),
+                 BaseType::Int32 => (i32::MIN as i128, i32::MAX as i128),
+                 BaseType::UInt32 => (0, u32::MAX as i128),
+                 BaseType::Int64 => (i64::MIN as i128, i64::MAX as i128),
+                 BaseType::UInt64 => (0, u64::MAX as i128),
+                 _ => return None,
+             })
+    }
+}
+
+impl fmt::Display for BaseType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            BaseType::Bool => "bool",
+            BaseType::Int8 => "int8",
+            BaseType::Byte => "byte",
+            BaseType::UInt8 => "uint8",
+            BaseType::Char => "char",
+            BaseType::Int16 => "int16",
+            BaseType::UInt16 => "uint16",
+            BaseType::Int32 => "int32",
+            BaseType::UInt32 => "uint32",
+            BaseType::Int64 => "int64",
+            BaseType::UInt64 => "uint64",
+            BaseType::Float32 => "float32",
+            BaseType::Float64 => "float64",
+            BaseType::String => "string",
+            BaseType::Time => "time",
+            BaseType::Duration => "duration",
+            BaseType::Message(ref name) => name,
+        })
+    }
+}
+
+/// Type of a message field.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FieldType {
+    /// A single value.
+    Single(BaseType),
+    /// Variable size array, prefixed by its length.
+    Array(BaseType),
+    /// Fixed size array, written without a length prefix.
+    FixedArray(BaseType, usize),
+}
+
+impl FieldType {
+    /// Parse a field type, as written within a message of the given package.
+    pub fn parse(name: &str, package: &str) -> Result<FieldType> {
+        if !name.ends_with(']') {
+            return BaseType::parse(name, package).map(FieldType::Single);
+        }
+        let start = match name.find('[') {
+            Some(start) => start,
+            None => bail!(ErrorKind::BadMessageDefinition(format!("bad array type {}", name))),
+        };
+        let base = BaseType::parse(&name[..start], package)?;
+        let length = &name[start + 1..name.len() - 1];
+        if length.is_empty() {
+            return Ok(FieldType::Array(base));
+        }
+        match length.parse::<u64>() {
+            Ok(length) if length <= u64::from(u32::MAX) => {
+                Ok(FieldType::FixedArray(base, length as usize))
+            }
+            Ok(_) => {
+                bail!(ErrorKind::BadMessageDefinition(format!("array type {} is too long", name)))
+            }
+            Err(_) => bail!(ErrorKind::BadMessageDefinition(format!("bad array type {}", name))),
+        }
+    }
+
+    /// The type of the items stored within the field.
+    pub fn base(&self) -> &BaseType {
+        match *self {
+            FieldType::Single(ref base) |
+            FieldType::Array(ref base) |
+            FieldType::FixedArray(ref base, _) => base,
+        }
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FieldType::Single(ref base) => write!(f, "{}", base),
+            FieldType::Array(ref base) => write!(f, "{}[]", base),
+            FieldType::FixedArray(ref base, length) => write!(f, "{}[{}]", base, length),
+        }
+    }
+}
+
+/// A named field of a message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    pub field_type: FieldType,
+}
+
+/// A named constant declared within a message.
+///
+/// The value is kept as it was written, since that text is part of the
+/// message's MD5 sum.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Constant {
+    pub name: String,
+    pub base_type: BaseType,
+    pub value: String,
+}
+
+/// A single parsed message definition.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Msg {
+    pub package: String,
+    pub name: String,
+    pub fields: Vec<Field>,
+    pub constants: Vec<Constant>,
+    /// The original text of the definition.
+    pub source: String,
+}
+
+impl Msg {
+    /// Parse the definition of the message with the given full name.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use serde_rosmsg::schema::{BaseType, FieldType, Msg};
+    /// let msg = Msg::new("geometry_msgs/PoseStamped",
+    ///                    "Header header\nPose pose # Some comment\n").unwrap();
+    /// assert_eq!(msg.fields.len(), 2);
+    /// assert_eq!(msg.fields[1].name, "pose");
+    /// assert_eq!(msg.fields[1].field_type,
+    ///            FieldType::Single(BaseType::Message("geometry_msgs/Pose".into())));
+    /// ```
+    pub fn new(full_name: &str, source: &str) -> Result<Msg> {
+        check_full_name(full_name)?;
+        let mut parts = full_name.splitn(2, '/');
+        let package = parts.next().unwrap_or("").to_owned();
+        let name = parts.next().unwrap_or("").to_owned();
+        let mut msg = Msg {
+            package,
+            name,
+            fields: Vec::new(),
+            constants: Vec::new(),
+            source: source.into(),
+        };
+        for line in source.lines() {
+            msg.parse_line(line)?;
+        }
+        Ok(msg)
+    }
+
+    /// The full name of the message, like `geometry_msgs/Point`.
+    pub fn full_name(&self) -> String {
+        format!("{}/{}", self.package, self.name)
+    }
+
+    /// Names of all messages directly used by fields, in order of appearance.
+    pub fn dependencies(&self) -> Vec<&str> {
+        let mut dependencies = Vec::new();
+        for field in &self.fields {
+            if let BaseType::Message(ref name) = *field.field_type.base() {
+                if !dependencies.contains(&name.as_str()) {
+                    dependencies.push(name.as_str());
+                }
+            }
+        }
+        dependencies
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<()> {
+        let clean = match line.find('#') {
+            Some(index) => &line[..index],
+            None => line,
+        };
+        let clean = clean.trim();
+        if clean.is_empty() {
+            return Ok(());
+        }
+        if clean.contains('=') {
+            self.parse_constant(line, clean)
+        } else {
+            self.parse_field(clean)
+        }
+    }
+
+    fn parse_field(&mut self, line: &str) -> Result<()> {
+        let parts = line.split_whitespace().collect::<Vec<_>>();
+        if parts.len() != 2 {
+            bail!(ErrorKind::BadMessageDefinition(format!("bad field {:?}", line)));
+        }
+        check_name(parts[1])?;
+        let field_type = FieldType::parse(parts[0], &self.package)?;
+        self.fields.push(Field {
+                             name: parts[1].into(),
+                             field_type,
+                         });
+        Ok(())
+    }
+
+    fn parse_constant(&mut self, line: &str, clean: &str) -> Result<()> {
+        let type_name = clean.split_whitespace().next().unwrap_or("");
+        let base_type = BaseType::parse(type_name, &self.package)?;
+        // String constants contain everything right of the equals sign,
+        // including characters that would otherwise start a comment
+        let (name, value) = if base_type == BaseType::String {
+            let start = line.find(type_name).unwrap_or(0) + type_name.len();
+            let index = line.find('=').unwrap_or(0);
+            (&line[start..index], &line[index + 1..])
+        } else {
+            let rest = &clean[type_name.len()..];
+            let index = rest.find('=').unwrap_or(0);
+            (&rest[..index], &rest[index + 1..])
+        };
+        let (name, value) = (name.trim(), value.trim());
+        match base_type {
+            BaseType::Time | BaseType::Duration | BaseType::Message(..) => {
+                bail!(ErrorKind::BadMessageDefinition(format!("bad constant type {:?}", line)))
+            }
+            _ => {}
+        }
+        check_name(name)?;
+        self.constants.push(Constant {
+                                name: name.into(),
+                                base_type,
+                                value: value.into(),
+                            });
+        Ok(())
+    }
+}
+
+/// A message definition, together with the definitions of all messages it
+/// depends on.
+///
+/// This is the information carried by a `message_definition` connection
+/// header field.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Schema {
+    name: String,
+    messages: HashMap<String, Msg>,
+}
+
+const SEPARATOR: &str = "================================================================================";
+
+impl Schema {
+    /// Create a schema out of the root message and all its dependencies.
+    ///
+    /// Fails if any message that the root depends on is missing.
+    pub fn new(root: Msg, dependencies: Vec<Msg>) -> Result<Schema> {
+        let mut schema = Schema {
+            name: root.full_name(),
+            messages: HashMap::new(),
+        };
+        schema.messages.insert(schema.name.clone(), root);
+        for msg in dependencies {
+            schema.messages.insert(msg.full_name(), msg);
+        }
+        schema.dependencies()?;
+        Ok(schema)
+    }
+
+    /// Parse a full `message_definition` of the message with the given name.
+    ///
+    /// The text is made out of the root message's definition, followed by
+    /// the definitions of all dependencies, each introduced by a separator
+    /// line and a `MSG: package/Name` line.
+    pub fn from_definition(name: &str, definition: &str) -> Result<Schema> {
+        let separator = format!("\n{}\n", SEPARATOR);
+        let sections = definition.split(separator.as_str());
+        let mut messages = Vec::new();
+        for (index, section) in sections.into_iter().enumerate() {
+            if index == 0 {
+                messages.push(Msg::new(name, section)?);
+                continue;
+            }
+            let (header, source) = match section.find('\n') {
+                Some(end) => (&section[..end], &section[end + 1..]),
+                None => (section, ""),
+            };
+            if !header.starts_with("MSG: ") {
+                bail!(ErrorKind::BadMessageDefinition(format!("bad section header {:?}",
+                                                              header)));
+            }
+            messages.push(Msg::new(header[5..].trim(), source)?);
+        }
+        let root = messages.remove(0);
+        Schema::new(root, messages)
+    }
+
+    /// Create a schema by looking up the source of every needed message.
+    ///
+    /// The lookup function gets called with full message names, and returns
+    /// the source of the definition if the message is known.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use serde_rosmsg::schema::Schema;
+    /// let schema = Schema::build("my_msgs/Line", |name| match name {
+    ///     "my_msgs/Line" => Some("Point start\nPoint end\n".into()),
+    ///     "my_msgs/Point" => Some("float64 x\nfloat64 y\n".into()),
+    ///     _ => None,
+    /// }).unwrap();
+    /// assert_eq!(schema.dependencies().unwrap(), vec!["my_msgs/Point"]);
+    /// ```
+    pub fn build<F>(name: &str, mut lookup: F) -> Result<Schema>
+        where F: FnMut(&str) -> Option<String>
+    {
+        let mut messages = HashMap::<String, Msg>::new();
+        let mut pending = vec![name.to_owned()];
+        while let Some(name) = pending.pop() {
+            if messages.contains_key(&name) {
+                continue;
+            }
+            let source = match lookup(&name) {
+                Some(source) => source,
+                None => bail!(ErrorKind::UnknownMessageType(name)),
+            };
+            let msg = Msg::new(&name, &source)?;
+            pending.extend(msg.dependencies().into_iter().map(String::from));
+            messages.insert(name, msg);
+        }
+        let root = messages.remove(name).expect("Root message is always loaded first");
+        Schema::new(root, messages.into_values().collect())
+    }
+
+    /// The full name of the root message.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The root message.
+    pub fn root(&self) -> &Msg {
+        &self.messages[&self.name]
+    }
+
+    /// Get the message with the given full name.
+    pub fn get(&self, name: &str) -> Option<&Msg> {
+        self.messages.get(name)
+    }
+
+    /// Get the message with the given full name, failing if it's missing.
+    pub fn message(&self, name: &str) -> Result<&Msg> {
+        match self.messages.get(name) {
+            Some(msg) => Ok(msg),
+            None => bail!(ErrorKind::UnknownMessageType(name.into())),
+        }
+    }
+
+    /// Iterate over all messages contained in the schema.
+    pub fn messages(&self) -> ::std::collections::hash_map::Values<'_, String, Msg> {
+        self.messages.values()
+    }
+
+    /// Names of all messages that the root depends on, directly or not.
+    ///
+    /// Messages are listed in the order in which ROS lists them within
+    /// the `message_definition`.
+    pub fn dependencies(&self) -> Result<Vec<&str>> {
+        let mut dependencies = Vec::new();
+        let mut stack = vec![self.name.as_str()];
+        self.collect_dependencies(&self.name, &mut stack, &mut dependencies)?;
+        Ok(dependencies)
+    }
+
+    fn collect_dependencies<'a>(&'a self,
+                                name: &str,
+                                stack: &mut Vec<&'a str>,
+                                dependencies: &mut Vec<&'a str>)
+                                -> Result<()> {
+        for dependency in self.message(name)?.dependencies() {
+            if stack.contains(&dependency) {
+                bail!(ErrorKind::BadMessageDefinition(format!("{} contains itself",
+                                                              dependency)));
+            }
+            if !dependencies.contains(&dependency) {
+                dependencies.push(dependency);
+            }
+            stack.push(dependency);
+            self.collect_dependencies(dependency, stack, dependencies)?;
+            stack.pop();
+        }
+        Ok(())
+    }
+
+    /// Number of bytes taken by a value of the given type, if it's constant.
+    ///
+    /// Unlike [`BaseType::size`](enum.BaseType.html#method.size), this also
+    /// covers messages that contain no strings or variable size arrays.
+    pub fn size_of(&self, base: &BaseType) -> Option<usize> {
+        match *base {
+            BaseType::Message(ref name) => self.size_of_message(name),
+            ref base => base.size(),
+        }
+    }
+
+    /// Number of bytes taken by the message with the given full name, if
+    /// it's constant.
+    pub fn size_of_message(&self, name: &str) -> Option<usize> {
+        let mut total: usize = 0;
+        for field in &self.get(name)?.fields {
+            let size = match field.field_type {
+                FieldType::Single(ref base) => self.size_of(base)?,
+                FieldType::FixedArray(ref base, length) => {
+                    self.size_of(base)?.checked_mul(length)?
+                }
+                FieldType::Array(..) => return None,
+            };
+            total = total.checked_add(size)?;
+        }
+        Some(total)
+    }
+
+    /// The MD5 sum of the root message.
+    pub fn md5sum(&self) -> Result<String> {
+        self.md5sum_of(&self.name)
+    }
+
+    /// The MD5 sum of the message with the given full name.
+    pub fn md5sum_of(&self, name: &str) -> Result<String> {
+        Ok(format!("{:x}", md5::compute(self.md5_text_of(name)?)))
+    }
+
+    /// The text that gets hashed to calculate the message's MD5 sum.
+    ///
+    /// It contains all constants and fields, with comments and
+    /// whitespace normalized, and nested messages replaced with their own
+    /// MD5 sums.
+    pub fn md5_text_of(&self, name: &str) -> Result<String> {
+        let mut visited = HashSet::new();
+        self.md5_text_visiting(name, &mut visited)
+    }
+
+    fn md5_text_visiting<'a>(&'a self,
+                             name: &'a str,
+                             visited: &mut HashSet<&'a str>)
+                             -> Result<String> {
+        if !visited.insert(name) {
+            bail!(ErrorKind::BadMessageDefinition(format!("{} contains itself", name)));
+        }
+        let msg = self.message(name)?;
+        let mut lines = Vec::new();
+        for constant in &msg.constants {
+            lines.push(format!("{} {}={}", constant.base_type, constant.name, constant.value));
+        }
+        for field in &msg.fields {
+            match *field.field_type.base() {
+                BaseType::Message(ref name) => {
+                    let text = self.md5_text_visiting(name, visited)?;
+                    lines.push(format!("{:x} {}", md5::compute(text), field.name));
+                }
+                _ => lines.push(format!("{} {}", field.field_type, field.name)),
+            }
+        }
+        visited.remove(name);
+        Ok(lines.join("\n"))
+    }
+
+    /// The full `message_definition` text of the root message.
+    pub fn message_definition(&self) -> Result<String> {
+        let mut text = self.root().source.clone();
+        text.push('\n');
+        for dependency in self.dependencies()? {
+            text.push_str(SEPARATOR);
+            text.push_str("\nMSG: ");
+            text.push_str(dependency);
+            text.push('\n');
+            text.push_str(&self.messages[dependency].source);
+            text.push('\n');
+        }
+        text.pop();
+        Ok(text)
+    }
+}
+
+/// The MD5 sum of a service, made out of its request and response.
+///
+/// # Examples
+///
+/// ```rust
+/// # use serde_rosmsg::schema::{service_md5sum, Schema};
+/// let request = Schema::from_definition("my_srvs/AddTwoIntsRequest",
+///                                       "int64 a\nint64 b\n").unwrap();
+/// let response = Schema::from_definition("my_srvs/AddTwoIntsResponse",
+///                                        "int64 sum\n").unwrap();
+/// assert_eq!(service_md5sum(&request, &response).unwrap(),
+///            "6a2e34150c00229791cc89ff309fff21");
+/// ```
+pub fn service_md5sum(request: &Schema, response: &Schema) -> Result<String> {
+    let text = request.md5_text_of(request.name())? + &response.md5_text_of(response.name())?;
+    Ok(format!("{:x}", md5::compute(text)))
+}
+
+fn check_name(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let valid = match chars.next() {
+        Some(c) => c.is_ascii_alphabetic() && chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        None => false,
+    };
+    if !valid {
+        bail!(ErrorKind::BadMessageDefinition(format!("bad name {:?}", name)));
+    }
+    Ok(())
+}
+
+fn check_full_name(name: &str) -> Result<()> {
+    let mut parts = name.split('/');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(package), Some(name), None) => check_name(package).and_then(|_| check_name(name)),
+        _ => bail!(ErrorKind::BadMessageDefinition(format!("bad message name {:?}", name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_fields() {
+        let msg = Msg::new("my_msgs/Data",
+                           "# Comment\nint32 a\n  string  b # trailing\nuint8[] c\nHeader \
+                            d\nfloat64[36] e\nother_msgs/Thing[] f\nThing g\n")
+                .unwrap();
+        let types = msg.fields
+            .iter()
+            .map(|field| (field.name.as_str(), field.field_type.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(types,
+                   vec![("a", FieldType::Single(BaseType::Int32)),
+                        ("b", FieldType::Single(BaseType::String)),
+                        ("c", FieldType::Array(BaseType::UInt8)),
+                        ("d", FieldType::Single(BaseType::Message("std_msgs/Header".into()))),
+                        ("e", FieldType::FixedArray(BaseType::Float64, 36)),
+                        ("f", FieldType::Array(BaseType::Message("other_msgs/Thing".into()))),
+                        ("g", FieldType::Single(BaseType::Message("my_msgs/Thing".into())))]);
+        assert_eq!(msg.dependencies(),
+                   vec!["std_msgs/Header", "other_msgs/Thing", "my_msgs/Thing"]);
+    }
+
+    #[test]
+    fn parses_constants() {
+        let msg = Msg::new("my_msgs/Data",
+                           "int8 A=-1 # comment\nuint8 B = 2\nstring C= a # not a comment \
+                            \nbyte D=3\nbyte e\n")
+                .unwrap();
+        let constants = msg.constants
+            .iter()
+            .map(|c| (c.name.as_str(), c.base_type.clone(), c.value.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(constants,
+                   vec![("A", BaseType::Int8, "-1"),
+                        ("B", BaseType::UInt8, "2"),
+                        ("C", BaseType::String, "a # not a comment"),
+                        ("D", BaseType::Byte, "3")]);
+        assert_eq!(msg.fields.len(), 1);
+    }
+
+    #[test]
+    fn rejects_bad_definitions() {
+        Msg::new("my_msgs/Data", "int32\n").unwrap_err();
+        Msg::new("my_msgs/Data", "int32 a b\n").unwrap_err();
+        Msg::new("my_msgs/Data", "int32[x] a\n").unwrap_err();
+        Msg::new("my_msgs/Data", "int32[4294967296] a\n").unwrap_err();
+        Msg::new("my_msgs/Data", "int32[4294967295] a\n").unwrap();
+        Msg::new("my_msgs/Data", "int32 1a\n").unwrap_err();
+        Msg::new("my_msgs/Data", "time A=1\n").unwrap_err();
+        Msg::new("my_msgs/Data/More", "int32 a\n").unwrap_err();
+        Msg::new("Data", "int32 a\n").unwrap_err();
+    }
+
+    #[test]
+    fn calculates_md5sum_of_simple_messages() {
+        let schema = Schema::from_definition("std_msgs/String", "string data\n").unwrap();
+        assert_eq!(schema.md5sum().unwrap(), "992ce8a1687cec8c8bd883ec73ca41d1");
+        let schema = Schema::from_definition("std_msgs/Empty", "").unwrap();
+        assert_eq!(schema.md5sum().unwrap(), "d41d8cd98f00b204e9800998ecf8427e");
+    }
+
+    #[test]
+    fn includes_constants_in_md5_text() {
+        let schema = Schema::from_definition("my_msgs/Data",
+                                             "uint8 B = 2 # comment\nuint8 b\nstring S=a#b\n")
+                .unwrap();
+        assert_eq!(schema.md5_text_of("my_msgs/Data").unwrap(),
+                   "uint8 B=2\nstring S=a#b\nuint8 b");
+    }
+
+    #[test]
+    fn handles_pose_definition() {
+        let definition = include_str!("datatests/pose_message_definition.txt");
+        let schema = Schema::from_definition("geometry_msgs/Pose", definition).unwrap();
+        assert_eq!(schema.md5sum().unwrap(), "e45d45a5a1ce597b249e23fb30fc871f");
+        assert_eq!(schema.dependencies().unwrap(),
+                   vec!["geometry_msgs/Point", "geometry_msgs/Quaternion"]);
+        assert_eq!(schema.message_definition().unwrap(), definition);
+    }
+
+    #[test]
+    fn handles_pose_with_covariance_definition() {
+        let definition = include_str!("datatests/pose_with_covariance_message_definition.txt");
+        let schema = Schema::from_definition("geometry_msgs/PoseWithCovariance", definition)
+            .unwrap();
+        assert_eq!(schema.md5sum().unwrap(), "c23e848cf1b7533a8d7c259073a97e6f");
+        assert_eq!(schema.message_definition().unwrap(), definition);
+    }
+
+    #[test]
+    fn handles_pose_array_definition() {
+        let definition = include_str!("datatests/pose_array_message_definition.txt");
+        let schema = Schema::from_definition("geometry_msgs/PoseArray", definition).unwrap();
+        assert_eq!(schema.md5sum().unwrap(), "916c28c5764443f268b296bb671b9d97");
+        assert_eq!(schema.md5sum_of("std_msgs/Header").unwrap(),
+                   "2176decaecbce78abc3b96ef049fabed");
+        assert_eq!(schema.dependencies().unwrap(),
+                   vec!["std_msgs/Header",
+                        "geometry_msgs/Pose",
+                        "geometry_msgs/Point",
+                        "geometry_msgs/Quaternion"]);
+        assert_eq!(schema.message_definition().unwrap(), definition);
+    }
+
+    #[test]
+    fn calculates_constant_sizes() {
+        let schema = Schema::from_definition("geometry_msgs/PoseArray",
+                                             include_str!("datatests/\
+                                                           pose_array_message_definition.txt"))
+            .unwrap();
+        assert_eq!(schema.size_of(&BaseType::Time), Some(8));
+        assert_eq!(schema.size_of(&BaseType::String), None);
+        assert_eq!(schema.size_of_message("geometry_msgs/Point"), Some(24));
+        assert_eq!(schema.size_of(&BaseType::Message("geometry_msgs/Pose".into())),
+                   Some(56));
+        assert_eq!(schema.size_of_message("std_msgs/Header"), None);
+        assert_eq!(schema.size_of_message("geometry_msgs/PoseArray"), None);
+        assert_eq!(schema.size_of_message("geometry_msgs/Missing"), None);
+        let schema = Schema::from_definition("my_msgs/Fixed",
+                                             "uint8[3] a\ntime[2] b\nNested[2] c\n\
+                                              ================================================\
+                                              ================================\n\
+                                              MSG: my_msgs/Nested\nfloat32 d\n")
+            .unwrap();
+        assert_eq!(schema.size_of_message("my_msgs/Fixed"), Some(27));
+        let schema = Schema::from_definition("my_msgs/Huge",
+                                             "Big[4294967295] a\n\
+                                              ================================================\
+                                              ================================\n\
+                                              MSG: my_msgs/Big\ntime[4294967295] b\n")
+            .unwrap();
+        assert_eq!(schema.size_of_message("my_msgs/Huge"), None);
+    }
+
+    #[test]
+    fn calculates_md5sum_of_services() {
+        let empty = Schema::from_definition("std_srvs/EmptyRequest", "").unwrap();
+        let response = Schema::from_definition("std_srvs/TriggerResponse",
+                                               "bool success\nstring message\n")
+            .unwrap();
+        assert_eq!(service_md5sum(&empty, &empty).unwrap(),
+                   "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(service_md5sum(&empty, &response).unwrap(),
+                   "937c9679a518e3a18d831e57125ea522");
+    }
+
+    #[test]
+    fn builds_schema_from_lookup() {
+        let definition = include_str!("datatests/pose_message_definition.txt");
+        let schema = Schema::build("geometry_msgs/Pose", |name| match name {
+            "geometry_msgs/Pose" => {
+                Some("# A representation of pose in free space, composed of position and \
+                      orientation. \nPoint position\nQuaternion orientation\n"
+                             .into())
+            }
+            "geometry_msgs/Point" => {
+                Some("# This contains the position of a point in free space\nfloat64 \
+                      x\nfloat64 y\nfloat64 z\n"
+                             .into())
+            }
+            "geometry_msgs/Quaternion" => {
+                Some("# This represents an orientation in free space in quaternion \
+                      form.\n\nfloat64 x\nfloat64 y\nfloat64 z\nfloat64 w\n"
+                             .into())
+            }
+            _ => None,
+        })
+                .unwrap();
+        assert_eq!(schema.message_definition().unwrap(), definition);
+    }
+
+    #[test]
+    fn reports_missing_dependencies() {
+        let error = Schema::build("my_msgs/Line", |name| match name {
+            "my_msgs/Line" => Some("Point start\nPoint end\n".into()),
+            _ => None,
+        })
+                .unwrap_err();
+        match *error.kind() {
+            ErrorKind::UnknownMessageType(ref name) if name == "my_msgs/Point" => {}
+            _ => panic!("Unknown message type error expected, got: {:?}", error),
+        }
+        Schema::from_definition("my_msgs/Line", "Point start\n").unwrap_err();
+    }
+
+    #[test]
+    fn reports_recursive_messages() {
+        Schema::from_definition("my_msgs/Node",
+                                &format!("Node[] children\n\n{}\nMSG: my_msgs/Node\nNode[] \
+                                          children\n",
+                                         SEPARATOR))
+                .unwrap_err();
+    }
+}
@@ -10,6 +10,7 @@
 use byteorder::{LittleEndian, WriteBytesExt};
 use serde::ser::{self, Impossible};
 use super::error::{Error, ErrorKind, Result};
+use super::raw;
 use std::io;
 
 /// A structure for serializing Rust values into ROSMSG binary data.
@@ -20,6 +21,7 @@ use std::io;
 /// Prefer using `to_writer` and `to_vec`.
 pub struct Serializer<W> {
     writer: W,
+    verbatim: bool,
 }
 
 impl<W> Serializer<W>
@@ -44,7 +46,10 @@ impl<W> Serializer<W>
     /// # }
     /// ```
     pub fn new(writer: W) -> Self {
-        Serializer { writer: writer }
+        Serializer {
+            writer: writer,
+            verbatim: false,
+        }
     }
 
     /// Unwrap the `Writer` from the `Serializer`.
@@ -75,6 +80,12 @@ impl<W> Serializer<W>
         self.writer
     }
 
+    /// Write bytes as they are, without a length prefix.
+    #[inline]
+    pub(crate) fn put_bytes(&mut self, value: &[u8]) -> Result<()> {
+        self.writer.write_all(value).map_err(|v| v.into())
+    }
+
     #[inline]
     fn write_size(&mut self, len: usize) -> io::Result<()> {
         self.writer.write_u32::<LittleEndian>(len as u32)
@@ -144,6 +155,10 @@ impl<'a, W> ser::Serializer for &'a mut Serializer<W>
 
     #[inline]
     fn serialize_bytes(self, value: &[u8]) -> SerializerResult {
+        if self.verbatim {
+            self.verbatim = false;
+            return self.writer.write_all(value).map_err(|v| v.into());
+        }
         self.write_size(value.len())
             .and_then(|_| self.writer.write_all(value))
             .map_err(|v| v.into())
@@ -180,10 +195,13 @@ impl<'a, W> ser::Serializer for &'a mut Serializer<W>
 
     #[inline]
     fn serialize_newtype_struct<T: ?Sized + ser::Serialize>(self,
-                                                            _name: &'static str,
+                                                            name: &'static str,
                                                             value: &T)
                                                             -> SerializerResult {
-        value.serialize(self)
+        self.verbatim = name == raw::TOKEN;
+        let result = value.serialize(&mut *self);
+        self.verbatim = false;
+        result
     }
 
     #[inline]
@@ -247,6 +265,11 @@ impl<'a, W> ser::Serializer for &'a mut Serializer<W>
                                 -> Result<Self::SerializeStructVariant> {
         bail!(ErrorKind::UnsupportedEnumType)
     }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        false
+    }
 }
 
 #[doc(hidden)]
@@ -0,0 +1,969 @@
+//! Handshakes and message streams of TCPROS topic connections.
+//!
+//! A TCPROS connection starts with the subscriber sending its connection
+//! header, and the publisher responding with its own. Both sides check that
+//! the other one agrees on the message type, and report any disagreement
+//! with a header holding just an `error` field. After that, the publisher
+//! keeps sending messages, each prefixed by its length.
+//!
+//! Service connections start with the same exchange of headers, followed
+//! by calls. Each request is framed like a topic message, while each
+//! response starts with an `ok` byte, followed by either the response or
+//! the error message of a failed call. Only `persistent` connections carry
+//! more than one call, and `probe` connections carry none.
+//!
+//! The handshakes work over any `Read + Write` stream, like a `TcpStream`
+//! or a `UnixStream`, and return a typed [`Publisher`](struct.Publisher.html)
+//! sink or [`Subscriber`](struct.Subscriber.html) stream.
+//!
+//! Everything received from the peer starts with its length, which gets
+//! checked against a limit before any memory is allocated for it.
+//! Headers are limited to [`MAX_HEADER_LENGTH`](constant.MAX_HEADER_LENGTH.html),
+//! and messages to [`DEFAULT_MAX_LENGTH`](constant.DEFAULT_MAX_LENGTH.html)
+//! unless set otherwise.
+//!
+//! # Examples
+//!
+//! ```rust
+//! extern crate serde_rosmsg;
+//! #[macro_use]
+//! extern crate serde_derive;
+//! use serde_rosmsg::RosMessage;
+//! use serde_rosmsg::tcpros::{publish, subscribe};
+//! use std::net::{TcpListener, TcpStream};
+//! use std::thread;
+//!
+//! fn main() {
+//! #[derive(Debug,Serialize,Deserialize,PartialEq)]
+//! struct StringMsg {
+//!     data: String,
+//! }
+//!
+//! impl RosMessage for StringMsg {
+//!     const ROS_TYPE: &'static str = "std_msgs/String";
+//!     const MD5SUM: &'static str = "992ce8a1687cec8c8bd883ec73ca41d1";
+//!     const DEFINITION: &'static str = "string data\n";
+//! }
+//!
+//! let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+//! let address = listener.local_addr().unwrap();
+//! let talker = thread::spawn(move || {
+//!     let (stream, _) = listener.accept().unwrap();
+//!     let mut publisher = publish::<StringMsg, _>(stream, "/talker", "/chatter").unwrap();
+//!     publisher.send(&StringMsg { data: "hello".into() }).unwrap();
+//! });
+//!
+//! let stream = TcpStream::connect(address).unwrap();
+//! let subscriber = subscribe::<StringMsg, _>(stream, "/listener", "/chatter").unwrap();
+//! assert_eq!(subscriber.header().get("callerid"), Some("/talker"));
+//! let messages = subscriber.collect::<Result<Vec<_>, _>>().unwrap();
+//! assert_eq!(messages, vec![StringMsg { data: "hello".into() }]);
+//! talker.join().unwrap();
+//! }
+//! ```
+
+use super::de::Deserializer;
+use super::error::{Error, ErrorKind, Result};
+use super::header::ConnectionHeader;
+use super::message::{RosMessage, RosService};
+use super::ser::to_writer;
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use serde::de::DeserializeOwned;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+/// Limit on the length of connection headers, of 1 MiB.
+///
+/// Headers mostly carry the full message definition, which rarely takes
+/// more than a few kilobytes.
+pub const MAX_HEADER_LENGTH: usize = 1024 * 1024;
+
+/// Default limit on the length of messages, of 64 MiB.
+///
+/// That's far below roscpp's limit of 1 GB, since any peer can make a
+/// connection allocate up to the limit. Raise it for bigger messages, like
+/// high resolution images or point clouds.
+pub const DEFAULT_MAX_LENGTH: usize = 64 * 1024 * 1024;
+
+/// Run the subscriber side of the handshake, with a default header.
+///
+/// The header is created with
+/// [`for_subscriber`](../header/struct.ConnectionHeader.html#method.for_subscriber).
+pub fn subscribe<T, S>(stream: S, caller_id: &str, topic: &str) -> Result<Subscriber<T, S>>
+    where T: RosMessage,
+          S: Read + Write
+{
+    subscribe_with(stream, ConnectionHeader::for_subscriber::<T>(caller_id, topic))
+}
+
+/// Run the subscriber side of the handshake, sending the given header.
+///
+/// If the publisher's header doesn't match the message type, an error
+/// header is sent back before failing.
+pub fn subscribe_with<T, S>(mut stream: S, header: ConnectionHeader) -> Result<Subscriber<T, S>>
+    where T: RosMessage,
+          S: Read + Write
+{
+    send_header(&mut stream, &header)?;
+    let remote = read_header(&mut stream)?;
+    if let Err(error) = remote.check_message::<T>() {
+        return Err(reject(&mut stream, &remote, error));
+    }
+    Ok(Subscriber {
+           stream,
+           header: remote,
+           max_length: DEFAULT_MAX_LENGTH,
+           phantom: PhantomData,
+       })
+}
+
+/// Run the publisher side of the handshake, with a default header.
+///
+/// The header is created with
+/// [`for_publisher`](../header/struct.ConnectionHeader.html#method.for_publisher).
+pub fn publish<T, S>(stream: S, caller_id: &str, topic: &str) -> Result<Publisher<T, S>>
+    where T: RosMessage,
+          S: Read + Write
+{
+    publish_with(stream, ConnectionHeader::for_publisher::<T>(caller_id, topic))
+}
+
+/// Run the publisher side of the handshake, responding with the given header.
+///
+/// Subscribers asking for another topic or message type are sent an error
+/// header instead.
+pub fn publish_with<T, S>(mut stream: S, header: ConnectionHeader) -> Result<Publisher<T, S>>
+    where T: RosMessage,
+          S: Read + Write
+{
+    let remote = read_header(&mut stream)?;
+    if let Err(error) = check_topic(&header, &remote).and_then(|_| remote.check_message::<T>()) {
+        return Err(reject(&mut stream, &remote, error));
+    }
+    send_header(&mut stream, &header)?;
+    Ok(Publisher {
+           stream,
+           header: remote,
+           phantom: PhantomData,
+       })
+}
+
+fn check_topic(local: &ConnectionHeader, remote: &ConnectionHeader) -> Result<()> {
+    if remote.get("error").is_some() {
+        return Ok(());
+    }
+    match (local.get("topic"), remote.get("topic")) {
+        (Some(expected), Some(actual)) if expected != actual => {
+            bail!(ErrorKind::HeaderMismatch("topic".into(), expected.into(), actual.into()))
+        }
+        (Some(_), None) => bail!(ErrorKind::MissingHeaderField("topic".into())),
+        _ => Ok(()),
+    }
+}
+
+/// Tell the peer why its header was rejected, unless it reported an error
+/// itself.
+///
+/// Failing to send the error header is ignored, since the connection is
+/// getting dropped either way.
+fn reject<S>(stream: &mut S, remote: &ConnectionHeader, error: Error) -> Error
+    where S: Write
+{
+    if remote.get("error").is_none() {
+        let _ = send_header(stream, &ConnectionHeader::for_error(&error.to_string()));
+    }
+    error
+}
+
+fn send_header<S>(stream: &mut S, header: &ConnectionHeader) -> Result<()>
+    where S: Write
+{
+    to_writer(stream, header)?;
+    stream.flush().map_err(|v| v.into())
+}
+
+/// Typed sink of messages sent by the publisher of a TCPROS connection.
+#[derive(Debug)]
+pub struct Publisher<T, S> {
+    stream: S,
+    header: ConnectionHeader,
+    phantom: PhantomData<fn(T)>,
+}
+
+impl<T, S> Publisher<T, S>
+    where T: RosMessage,
+          S: Write
+{
+    /// Send a message to the subscriber.
+    pub fn send(&mut self, message: &T) -> Result<()> {
+        to_writer(&mut self.stream, message)?;
+        self.stream.flush().map_err(|v| v.into())
+    }
+}
+
+impl<T, S> Publisher<T, S> {
+    /// Connection header received from the subscriber.
+    pub fn header(&self) -> &ConnectionHeader {
+        &self.header
+    }
+
+    /// Get a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Consume the publisher, returning the underlying stream.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+/// Typed stream of messages received by the subscriber of a TCPROS
+/// connection.
+///
+/// Iterating over the subscriber yields messages until the publisher
+/// closes the connection.
+#[derive(Debug)]
+pub struct Subscriber<T, S> {
+    stream: S,
+    header: ConnectionHeader,
+    max_length: usize,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T, S> Subscriber<T, S>
+    where T: RosMessage,
+          S: Read
+{
+    /// Receive the next message from the publisher.
+    ///
+    /// Returns `None` if the publisher closed the connection between two
+    /// messages.
+    pub fn recv(&mut self) -> Result<Option<T>> {
+        let length = match read_length(&mut self.stream)? {
+            Some(length) => length,
+            None => return Ok(None),
+        };
+        read_message(&mut self.stream, length, self.max_length).map(Some)
+    }
+}
+
+impl<T, S> Subscriber<T, S> {
+    /// Connection header received from the publisher.
+    pub fn header(&self) -> &ConnectionHeader {
+        &self.header
+    }
+
+    /// Limit on the length of messages, excluding the length prefix.
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    /// Reject messages longer than `max_length` bytes.
+    pub fn set_max_length(&mut self, max_length: usize) {
+        self.max_length = max_length;
+    }
+
+    /// Get a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Consume the subscriber, returning the underlying stream.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<T, S> Iterator for Subscriber<T, S>
+    where T: RosMessage,
+          S: Read
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        match self.recv() {
+            Ok(value) => value.map(Ok),
+            Err(error) => Some(Err(error)),
+        }
+    }
+}
+
+/// Run the client side of a service handshake, with a default header.
+///
+/// The header is created with
+/// [`for_service_client`](../header/struct.ConnectionHeader.html#method.for_service_client).
+/// Set `persistent` to keep the connection open for several calls.
+pub fn connect_service<S, C>(stream: C,
+                             caller_id: &str,
+                             service: &str,
+                             persistent: bool)
+                             -> Result<ServiceClient<S, C>>
+    where S: RosService,
+          C: Read + Write
+{
+    let mut header = ConnectionHeader::for_service_client::<S>(caller_id, service);
+    if persistent {
+        header.insert("persistent", "1");
+    }
+    connect_service_with(stream, header)
+}
+
+/// Run the client side of a service handshake, sending the given header.
+pub fn connect_service_with<S, C>(mut stream: C,
+                                  header: ConnectionHeader)
+                                  -> Result<ServiceClient<S, C>>
+    where S: RosService,
+          C: Read + Write
+{
+    send_header(&mut stream, &header)?;
+    let remote = read_header(&mut stream)?;
+    if let Err(error) = remote.check_service::<S>() {
+        return Err(reject(&mut stream, &remote, error));
+    }
+    Ok(ServiceClient {
+           stream,
+           header: remote,
+           persistent: is_set(&header, "persistent"),
+           called: false,
+           max_length: DEFAULT_MAX_LENGTH,
+           phantom: PhantomData,
+       })
+}
+
+/// Ask a service server for its header, without making any calls.
+///
+/// This is how tools like `rosservice` find out the type of a service.
+pub fn probe_service<C>(mut stream: C, caller_id: &str, service: &str) -> Result<ConnectionHeader>
+    where C: Read + Write
+{
+    let mut header = ConnectionHeader::new();
+    header.insert("callerid", caller_id);
+    header.insert("service", service);
+    header.insert("md5sum", "*");
+    header.insert("probe", "1");
+    send_header(&mut stream, &header)?;
+    let remote = read_header(&mut stream)?;
+    if let Some(error) = remote.get("error") {
+        bail!(ErrorKind::RemoteError(error.into()));
+    }
+    Ok(remote)
+}
+
+/// Run the server side of a service handshake, with a default header.
+///
+/// The header is created with
+/// [`for_service_server`](../header/struct.ConnectionHeader.html#method.for_service_server).
+pub fn accept_service<S, C>(stream: C, caller_id: &str) -> Result<ServiceServer<S, C>>
+    where S: RosService,
+          C: Read + Write
+{
+    accept_service_with(stream, ConnectionHeader::for_service_server::<S>(caller_id))
+}
+
+/// Run the server side of a service handshake, responding with the given
+/// header.
+///
+/// Clients asking for another service type are sent an error header
+/// instead.
+pub fn accept_service_with<S, C>(mut stream: C,
+                                 header: ConnectionHeader)
+                                 -> Result<ServiceServer<S, C>>
+    where S: RosService,
+          C: Read + Write
+{
+    let remote = read_header(&mut stream)?;
+    if let Err(error) = remote.check_service::<S>() {
+        return Err(reject(&mut stream, &remote, error));
+    }
+    send_header(&mut stream, &header)?;
+    Ok(ServiceServer {
+           stream,
+           persistent: is_set(&remote, "persistent"),
+           probe: is_set(&remote, "probe"),
+           header: remote,
+           handled: false,
+           max_length: DEFAULT_MAX_LENGTH,
+           phantom: PhantomData,
+       })
+}
+
+fn is_set(header: &ConnectionHeader, field: &str) -> bool {
+    header.get(field) == Some("1")
+}
+
+/// Client of a TCPROS service connection.
+pub struct ServiceClient<S, C> {
+    stream: C,
+    header: ConnectionHeader,
+    persistent: bool,
+    called: bool,
+    max_length: usize,
+    phantom: PhantomData<fn(S)>,
+}
+
+impl<S, C> ServiceClient<S, C>
+    where S: RosService,
+          C: Read + Write
+{
+    /// Call the service.
+    ///
+    /// Requests the server refused to handle fail with
+    /// [`ServiceFailed`](../error/enum.ErrorKind.html#variant.ServiceFailed),
+    /// holding the server's error message. Connections that are not
+    /// persistent only carry a single call.
+    pub fn call(&mut self, request: &S::Request) -> Result<S::Response> {
+        if self.called && !self.persistent {
+            bail!(ErrorKind::NotPersistent);
+        }
+        self.called = true;
+        to_writer(&mut self.stream, request)?;
+        self.stream.flush()?;
+        let ok = self.stream.read_u8()? != 0;
+        let length = self.stream.read_u32::<LittleEndian>()?;
+        if !ok {
+            check_length(length, self.max_length)?;
+            let mut message = Vec::new();
+            (&mut self.stream).take(length as u64).read_to_end(&mut message)?;
+            if message.len() < length as usize {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            bail!(ErrorKind::ServiceFailed(String::from_utf8_lossy(&message).into_owned()));
+        }
+        read_message(&mut self.stream, length, self.max_length)
+    }
+}
+
+impl<S, C> fmt::Debug for ServiceClient<S, C>
+    where C: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ServiceClient")
+            .field("stream", &self.stream)
+            .field("header", &self.header)
+            .field("persistent", &self.persistent)
+            .finish()
+    }
+}
+
+impl<S, C> ServiceClient<S, C> {
+    /// Connection header received from the server.
+    pub fn header(&self) -> &ConnectionHeader {
+        &self.header
+    }
+
+    /// Check if the connection can carry several calls.
+    pub fn is_persistent(&self) -> bool {
+        self.persistent
+    }
+
+    /// Limit on the length of responses, excluding the length prefix.
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    /// Reject responses and error messages longer than `max_length` bytes.
+    pub fn set_max_length(&mut self, max_length: usize) {
+        self.max_length = max_length;
+    }
+
+    /// Consume the client, returning the underlying stream.
+    pub fn into_inner(self) -> C {
+        self.stream
+    }
+}
+
+/// Server of a TCPROS service connection.
+pub struct ServiceServer<S, C> {
+    stream: C,
+    header: ConnectionHeader,
+    persistent: bool,
+    probe: bool,
+    handled: bool,
+    max_length: usize,
+    phantom: PhantomData<fn(S)>,
+}
+
+impl<S, C> ServiceServer<S, C>
+    where S: RosService,
+          C: Read + Write
+{
+    /// Receive the next request from the client.
+    ///
+    /// Returns `None` once the client closed the connection, or once no
+    /// more calls are expected: after the handshake of a probe, or after
+    /// the first call of a connection that is not persistent.
+    pub fn recv(&mut self) -> Result<Option<S::Request>> {
+        if self.probe || (self.handled && !self.persistent) {
+            return Ok(None);
+        }
+        let length = match read_length(&mut self.stream)? {
+            Some(length) => length,
+            None => return Ok(None),
+        };
+        self.handled = true;
+        read_message(&mut self.stream, length, self.max_length).map(Some)
+    }
+
+    /// Respond to the last request with either a response or an error
+    /// message.
+    ///
+    /// Error messages that don't fit the length prefix fail with
+    /// [`FrameTooLarge`](../error/enum.ErrorKind.html#variant.FrameTooLarge)
+    /// before anything gets sent.
+    pub fn respond(&mut self, response: ::std::result::Result<&S::Response, &str>) -> Result<()> {
+        match response {
+            Ok(response) => {
+                self.stream.write_u8(1)?;
+                to_writer(&mut self.stream, response)?;
+            }
+            Err(message) => {
+                let length = u32::try_from(message.len())
+                    .map_err(|_| ErrorKind::FrameTooLarge(message.len(), u32::MAX as usize))?;
+                self.stream.write_u8(0)?;
+                self.stream.write_u32::<LittleEndian>(length)?;
+                self.stream.write_all(message.as_bytes())?;
+            }
+        }
+        self.stream.flush().map_err(|v| v.into())
+    }
+
+    /// Handle all calls on the connection.
+    ///
+    /// Errors returned by the handler are sent back to the client, and
+    /// don't stop the server.
+    pub fn serve<F>(&mut self, mut handler: F) -> Result<()>
+        where F: FnMut(S::Request) -> ::std::result::Result<S::Response, String>
+    {
+        while let Some(request) = self.recv()? {
+            match handler(request) {
+                Ok(response) => self.respond(Ok(&response))?,
+                Err(message) => self.respond(Err(&message))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S, C> fmt::Debug for ServiceServer<S, C>
+    where C: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ServiceServer")
+            .field("stream", &self.stream)
+            .field("header", &self.header)
+            .field("persistent", &self.persistent)
+            .field("probe", &self.probe)
+            .finish()
+    }
+}
+
+impl<S, C> ServiceServer<S, C> {
+    /// Connection header received from the client.
+    pub fn header(&self) -> &ConnectionHeader {
+        &self.header
+    }
+
+    /// Check if the client only asked for the server's header.
+    pub fn is_probe(&self) -> bool {
+        self.probe
+    }
+
+    /// Check if the connection can carry several calls.
+    pub fn is_persistent(&self) -> bool {
+        self.persistent
+    }
+
+    /// Limit on the length of requests, excluding the length prefix.
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    /// Reject requests longer than `max_length` bytes.
+    pub fn set_max_length(&mut self, max_length: usize) {
+        self.max_length = max_length;
+    }
+
+    /// Consume the server, returning the underlying stream.
+    pub fn into_inner(self) -> C {
+        self.stream
+    }
+}
+
+/// Read a connection header, rejecting it before reading its body if it's
+/// too long.
+fn read_header<R>(reader: &mut R) -> Result<ConnectionHeader>
+    where R: Read
+{
+    let length = reader.read_u32::<LittleEndian>()?;
+    read_message(reader, length, MAX_HEADER_LENGTH)
+}
+
+/// Read the body of a message, after its length prefix.
+fn read_message<R, T>(reader: &mut R, length: u32, max_length: usize) -> Result<T>
+    where R: Read,
+          T: DeserializeOwned
+{
+    check_length(length, max_length)?;
+    let mut deserializer = Deserializer::new(reader, length);
+    let value = T::deserialize(&mut deserializer)?;
+    if !deserializer.is_fully_read() {
+        bail!(ErrorKind::Underflow);
+    }
+    Ok(value)
+}
+
+fn check_length(length: u32, max_length: usize) -> Result<()> {
+    if length as usize > max_length {
+        bail!(ErrorKind::FrameTooLarge(length as usize, max_length));
+    }
+    Ok(())
+}
+
+/// Read the length prefix of the next message, if the stream isn't closed.
+fn read_length<R>(reader: &mut R) -> Result<Option<u32>>
+    where R: Read
+{
+    let mut buffer = [0u8; 4];
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            Ok(count) => filled += count,
+            Err(ref error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Ok(Some(LittleEndian::read_u32(&buffer)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::error::ErrorKind;
+    use std::net::{TcpListener, TcpStream};
+    #[cfg(unix)]
+    use std::os::unix::net::UnixStream;
+    use std::thread;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Point {
+        x: f64,
+        y: f64,
+        z: f64,
+    }
+
+    impl RosMessage for Point {
+        const ROS_TYPE: &'static str = "geometry_msgs/Point";
+        const MD5SUM: &'static str = "4a842b65f413084dc2b10fb484ea7f17";
+        const DEFINITION: &'static str = "float64 x\nfloat64 y\nfloat64 z\n";
+    }
+
+    #[cfg(unix)]
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct StringMsg {
+        data: String,
+    }
+
+    #[cfg(unix)]
+    impl RosMessage for StringMsg {
+        const ROS_TYPE: &'static str = "std_msgs/String";
+        const MD5SUM: &'static str = "992ce8a1687cec8c8bd883ec73ca41d1";
+        const DEFINITION: &'static str = "string data\n";
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct AddTwoIntsRequest {
+        a: i64,
+        b: i64,
+    }
+
+    impl RosMessage for AddTwoIntsRequest {
+        const ROS_TYPE: &'static str = "rospy_tutorials/AddTwoIntsRequest";
+        const MD5SUM: &'static str = "36d09b846be0b371c5f190354dd3153e";
+        const DEFINITION: &'static str = "int64 a\nint64 b\n";
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct AddTwoIntsResponse {
+        sum: i64,
+    }
+
+    impl RosMessage for AddTwoIntsResponse {
+        const ROS_TYPE: &'static str = "rospy_tutorials/AddTwoIntsResponse";
+        const MD5SUM: &'static str = "b88405221c77b1878a3cbbfff53428d7";
+        const DEFINITION: &'static str = "int64 sum\n";
+    }
+
+    struct AddTwoInts;
+
+    impl RosService for AddTwoInts {
+        const ROS_TYPE: &'static str = "rospy_tutorials/AddTwoInts";
+        const MD5SUM: &'static str = "6a2e34150c00229791cc89ff309fff21";
+        type Request = AddTwoIntsRequest;
+        type Response = AddTwoIntsResponse;
+    }
+
+    #[cfg(unix)]
+    struct DescribePoint;
+
+    #[cfg(unix)]
+    impl RosService for DescribePoint {
+        const ROS_TYPE: &'static str = "my_srvs/DescribePoint";
+        const MD5SUM: &'static str = "0b7fc2f1af5ca5f0a5cb7a6c5d8e0a13";
+        type Request = Point;
+        type Response = StringMsg;
+    }
+
+    fn add(request: AddTwoIntsRequest) -> ::std::result::Result<AddTwoIntsResponse, String> {
+        request
+            .a
+            .checked_add(request.b)
+            .map(|sum| AddTwoIntsResponse { sum })
+            .ok_or_else(|| "Sum overflows".into())
+    }
+
+    fn point(x: f64) -> Point {
+        Point { x, y: 2.0, z: 3.0 }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exchanges_messages_over_unix_sockets() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let talker = thread::spawn(move || {
+            let mut publisher = publish::<Point, _>(a, "/talker", "/points").unwrap();
+            assert_eq!(publisher.header().get("callerid"), Some("/listener"));
+            for i in 0..3 {
+                publisher.send(&point(i as f64)).unwrap();
+            }
+        });
+        let subscriber = subscribe::<Point, _>(b, "/listener", "/points").unwrap();
+        assert_eq!(subscriber.header().get("callerid"), Some("/talker"));
+        assert_eq!(subscriber.header().get("latching"), Some("0"));
+        let messages = subscriber.collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(messages, vec![point(0.0), point(1.0), point(2.0)]);
+        talker.join().unwrap();
+    }
+
+    #[test]
+    fn exchanges_messages_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let talker = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut publisher = publish::<Point, _>(stream, "/talker", "/points").unwrap();
+            publisher.send(&point(5.0)).unwrap();
+        });
+        let stream = TcpStream::connect(address).unwrap();
+        let mut subscriber = subscribe::<Point, _>(stream, "/listener", "/points").unwrap();
+        assert_eq!(subscriber.recv().unwrap(), Some(point(5.0)));
+        assert_eq!(subscriber.recv().unwrap(), None);
+        talker.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_subscribers_of_other_types() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let talker = thread::spawn(move || publish::<Point, _>(a, "/talker", "/points").err());
+        match *subscribe::<StringMsg, _>(b, "/listener", "/points").unwrap_err().kind() {
+            ErrorKind::RemoteError(ref message) => assert!(message.contains("md5sum")),
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+        match *talker.join().unwrap().unwrap().kind() {
+            ErrorKind::HeaderMismatch(ref field, ..) => assert_eq!(field, "md5sum"),
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_subscribers_of_other_topics() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let talker = thread::spawn(move || publish::<Point, _>(a, "/talker", "/points").err());
+        assert!(subscribe::<Point, _>(b, "/listener", "/lines").is_err());
+        match *talker.join().unwrap().unwrap().kind() {
+            ErrorKind::HeaderMismatch(ref field, ..) => assert_eq!(field, "topic"),
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_publishers_of_other_types() {
+        let (a, mut b) = UnixStream::pair().unwrap();
+        let listener =
+            thread::spawn(move || subscribe::<Point, _>(a, "/listener", "/points").err());
+        let request = read_header(&mut b).unwrap();
+        assert_eq!(request.get("md5sum"), Some(Point::MD5SUM));
+        send_header(&mut b, &ConnectionHeader::for_publisher::<StringMsg>("/talker", "/points"))
+            .unwrap();
+        let response = read_header(&mut b).unwrap();
+        assert!(response.get("error").unwrap().contains("md5sum"));
+        match *listener.join().unwrap().unwrap().kind() {
+            ErrorKind::HeaderMismatch(ref field, ..) => assert_eq!(field, "md5sum"),
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn accepts_wildcard_subscribers() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let talker = thread::spawn(move || {
+            let mut publisher = publish::<Point, _>(a, "/talker", "/points").unwrap();
+            publisher.send(&point(1.0)).unwrap();
+        });
+        let mut header = ConnectionHeader::for_subscriber::<Point>("/rostopic", "/points");
+        header.insert("md5sum", "*");
+        header.insert("type", "*");
+        let mut subscriber = subscribe_with::<Point, _>(b, header).unwrap();
+        assert_eq!(subscriber.header().get("md5sum"), Some(Point::MD5SUM));
+        assert_eq!(subscriber.recv().unwrap(), Some(point(1.0)));
+        talker.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn fails_on_truncated_messages() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let talker = thread::spawn(move || {
+            let publisher = publish::<Point, _>(a, "/talker", "/points").unwrap();
+            let mut stream = publisher.into_inner();
+            stream.write_all(&[24, 0, 0, 0, 1, 2, 3]).unwrap();
+        });
+        let mut subscriber = subscribe::<Point, _>(b, "/listener", "/points").unwrap();
+        talker.join().unwrap();
+        assert!(subscriber.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_headers_and_messages() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let talker = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(&[255, 255, 255, 255]).unwrap();
+            let (stream, _) = listener.accept().unwrap();
+            let mut publisher = publish::<Point, _>(stream, "/talker", "/points").unwrap();
+            publisher.send(&point(1.0)).unwrap();
+        });
+        let stream = TcpStream::connect(address).unwrap();
+        match *subscribe::<Point, _>(stream, "/listener", "/points").unwrap_err().kind() {
+            ErrorKind::FrameTooLarge(length, limit) => {
+                assert_eq!((length, limit), (u32::MAX as usize, MAX_HEADER_LENGTH))
+            }
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+        let stream = TcpStream::connect(address).unwrap();
+        let mut subscriber = subscribe::<Point, _>(stream, "/listener", "/points").unwrap();
+        assert_eq!(subscriber.max_length(), DEFAULT_MAX_LENGTH);
+        subscriber.set_max_length(16);
+        match *subscriber.recv().unwrap_err().kind() {
+            ErrorKind::FrameTooLarge(24, 16) => {}
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+        talker.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn calls_services_over_unix_sockets() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || {
+            let mut server = accept_service::<AddTwoInts, _>(a, "/server").unwrap();
+            assert!(!server.is_persistent());
+            assert_eq!(server.header().get("service"), Some("/add"));
+            server.serve(add).unwrap();
+        });
+        let mut client = connect_service::<AddTwoInts, _>(b, "/client", "/add", false).unwrap();
+        assert_eq!(client.header().get("request_type"),
+                   Some("rospy_tutorials/AddTwoIntsRequest"));
+        assert_eq!(client.call(&AddTwoIntsRequest { a: 2, b: 3 }).unwrap(),
+                   AddTwoIntsResponse { sum: 5 });
+        match *client.call(&AddTwoIntsRequest { a: 2, b: 3 }).unwrap_err().kind() {
+            ErrorKind::NotPersistent => {}
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn calls_persistent_services_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut server = accept_service::<AddTwoInts, _>(stream, "/server").unwrap();
+            assert!(server.is_persistent());
+            server.serve(add).unwrap();
+        });
+        let stream = TcpStream::connect(address).unwrap();
+        let mut client = connect_service::<AddTwoInts, _>(stream, "/client", "/add", true)
+            .unwrap();
+        assert_eq!(client.call(&AddTwoIntsRequest { a: 2, b: 3 }).unwrap().sum, 5);
+        match *client.call(&AddTwoIntsRequest { a: i64::MAX, b: 1 }).unwrap_err().kind() {
+            ErrorKind::ServiceFailed(ref message) => assert_eq!(message, "Sum overflows"),
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+        assert_eq!(client.call(&AddTwoIntsRequest { a: -4, b: 1 }).unwrap().sum, -3);
+        drop(client);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn limits_service_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut server = accept_service::<AddTwoInts, _>(stream, "/server").unwrap();
+            server.set_max_length(8);
+            server.recv().err()
+        });
+        let stream = TcpStream::connect(address).unwrap();
+        let mut client = connect_service::<AddTwoInts, _>(stream, "/client", "/add", false)
+            .unwrap();
+        assert!(client.call(&AddTwoIntsRequest { a: 2, b: 3 }).is_err());
+        match *server.join().unwrap().unwrap().kind() {
+            ErrorKind::FrameTooLarge(16, 8) => {}
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn answers_probes() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || {
+            let mut server = accept_service::<AddTwoInts, _>(a, "/server").unwrap();
+            assert!(server.is_probe());
+            server.serve(add).unwrap();
+        });
+        let header = probe_service(b, "/rosservice", "/add").unwrap();
+        assert_eq!(header.get("type"), Some(AddTwoInts::ROS_TYPE));
+        assert_eq!(header.get("md5sum"), Some(AddTwoInts::MD5SUM));
+        server.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_clients_of_other_services() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || accept_service::<AddTwoInts, _>(a, "/server").err());
+        match *connect_service::<DescribePoint, _>(b, "/client", "/add", false)
+                   .unwrap_err()
+                   .kind() {
+            ErrorKind::RemoteError(ref message) => assert!(message.contains("md5sum")),
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+        match *server.join().unwrap().unwrap().kind() {
+            ErrorKind::HeaderMismatch(ref field, ..) => assert_eq!(field, "md5sum"),
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+    }
+}
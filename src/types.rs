@@ -0,0 +1,345 @@
+//! Types and helpers for values with a special representation in ROSMSG.
+//!
+//! Besides primitives, strings and arrays, ROS messages can contain `time`
+//! and `duration` values, which are represented by [`Time`](struct.Time.html)
+//! and [`Duration`](struct.Duration.html).
+//!
+//! Fields can also use the helper modules with serde's `with` attribute:
+//!
+//! * [`bytes`](bytes/index.html) serializes `uint8[]` data in bulk, instead
+//!   of one item at a time.
+//! * [`big_array`](big_array/index.html) supports fixed size arrays with more
+//!   than 32 items, like the covariance matrices of `geometry_msgs`.
+//! * [`shared_bytes`](shared_bytes/index.html) serializes `Bytes` data in
+//!   bulk, and avoids copying it where possible. Requires the `bytes`
+//!   feature.
+//!
+//! # Examples
+//!
+//! ```rust
+//! extern crate serde_rosmsg;
+//! #[macro_use]
+//! extern crate serde_derive;
+//! use serde_rosmsg::{to_vec, from_slice};
+//! use serde_rosmsg::types::Time;
+//!
+//! fn main() {
+//! #[derive(Debug,Serialize,Deserialize,PartialEq)]
+//! struct Stamped {
+//!     stamp: Time,
+//!     #[serde(with = "serde_rosmsg::types::big_array")]
+//!     covariance: [u8; 36],
+//!     #[serde(with = "serde_rosmsg::types::bytes")]
+//!     data: Vec<u8>,
+//! }
+//!
+//! let data = Stamped {
+//!     stamp: Time { sec: 1, nsec: 2 },
+//!     covariance: [3; 36],
+//!     data: vec![4, 5],
+//! };
+//!
+//! let rosmsg_data = to_vec(&data).unwrap();
+//! assert_eq!(rosmsg_data.len(), 4 + 8 + 36 + 6);
+//! let rust_data: Stamped = from_slice(&rosmsg_data).unwrap();
+//! assert_eq!(rust_data, data);
+//! }
+//! ```
+
+/// Point in time, represented by ROS's `time` type.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize,
+         Deserialize)]
+pub struct Time {
+    /// Seconds since the epoch.
+    #[serde(alias = "secs")]
+    pub sec: u32,
+    /// Nanoseconds since the last whole second.
+    #[serde(alias = "nsecs")]
+    pub nsec: u32,
+}
+
+/// Span of time, represented by ROS's `duration` type.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize,
+         Deserialize)]
+pub struct Duration {
+    /// Whole seconds.
+    #[serde(alias = "secs")]
+    pub sec: i32,
+    /// Additional nanoseconds.
+    #[serde(alias = "nsecs")]
+    pub nsec: i32,
+}
+
+/// Serialize `Vec<u8>` fields as a single block of bytes.
+///
+/// The wire format is identical to serializing a `Vec<u8>` directly, but
+/// the data is written with one call, rather than byte by byte.
+pub mod bytes {
+    use serde::{de, Deserializer, Serializer};
+    use std::fmt;
+
+    /// Serialize the bytes as a variable length array.
+    pub fn serialize<S>(value: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        serializer.serialize_bytes(value)
+    }
+
+    /// Deserialize a variable length array of bytes.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+        where D: Deserializer<'de>
+    {
+        struct BytesVisitor;
+
+        impl<'de> de::Visitor<'de> for BytesVisitor {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an array of bytes")
+            }
+
+            fn visit_bytes<E>(self, value: &[u8]) -> Result<Vec<u8>, E>
+                where E: de::Error
+            {
+                Ok(value.to_vec())
+            }
+
+            fn visit_byte_buf<E>(self, value: Vec<u8>) -> Result<Vec<u8>, E>
+                where E: de::Error
+            {
+                Ok(value)
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Vec<u8>, A::Error>
+                where A: de::SeqAccess<'de>
+            {
+                let mut value = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(item) = seq.next_element()? {
+                    value.push(item);
+                }
+                Ok(value)
+            }
+        }
+
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+}
+
+/// Serialize fixed size arrays of any length.
+///
+/// Serde only implements its traits for arrays of up to 32 items, while
+/// ROS messages often contain bigger ones.
+pub mod big_array {
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+    use serde::ser::SerializeTuple;
+    use std::convert::TryInto;
+    use std::fmt;
+    use std::marker::PhantomData;
+
+    /// Serialize the array as a fixed size array.
+    pub fn serialize<S, T, const N: usize>(value: &[T; N],
+                                           serializer: S)
+                                           -> Result<S::Ok, S::Error>
+        where S: Serializer,
+              T: Serialize
+    {
+        let mut tuple = serializer.serialize_tuple(N)?;
+        for item in value {
+            tuple.serialize_element(item)?;
+        }
+        tuple.end()
+    }
+
+    /// Deserialize a fixed size array.
+    pub fn deserialize<'de, D, T, const N: usize>(deserializer: D) -> Result<[T; N], D::Error>
+        where D: Deserializer<'de>,
+              T: Deserialize<'de>
+    {
+        struct ArrayVisitor<T, const N: usize>(PhantomData<T>);
+
+        impl<'de, T, const N: usize> de::Visitor<'de> for ArrayVisitor<T, N>
+            where T: Deserialize<'de>
+        {
+            type Value = [T; N];
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(formatter, "an array of {} items", N)
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<[T; N], A::Error>
+                where A: de::SeqAccess<'de>
+            {
+                let mut items = Vec::with_capacity(N);
+                while items.len() < N {
+                    match seq.next_element()? {
+                        Some(item) => items.push(item),
+                        None => return Err(de::Error::invalid_length(items.len(), &self)),
+                    }
+                }
+                match items.try_into() {
+                    Ok(array) => Ok(array),
+                    Err(_) => unreachable!("Vector has exactly N items"),
+                }
+            }
+        }
+
+        deserializer.deserialize_tuple(N, ArrayVisitor(PhantomData))
+    }
+}
+
+/// Serialize `Bytes` fields as a single block of bytes, shared when
+/// possible.
+///
+/// Requires the `bytes` feature.
+///
+/// The wire format is the same as with [`bytes`](../bytes/index.html).
+/// When serializing into [chunks](../../vectored/index.html), big buffers
+/// are referenced by the chunks instead of getting copied. When
+/// deserializing with [`from_bytes`](../../de/fn.from_bytes.html), fields
+/// are sliced out of the input buffer instead of getting copied.
+#[cfg(feature = "bytes")]
+pub mod shared_bytes {
+    use bytes::Bytes;
+    use serde::{de, Deserializer, Serializer};
+    use std::cell::RefCell;
+    use std::fmt;
+
+    /// Name of the newtype struct that asks for shared bytes.
+    pub(crate) const TOKEN: &str = "$serde_rosmsg::SharedBytes";
+
+    thread_local! {
+        /// Buffer being serialized, for writers that can reference it.
+        static LENT: RefCell<Option<Bytes>> = const { RefCell::new(None) };
+        /// Buffer sliced out by the deserializer, for the visitor to take.
+        ///
+        /// Serde has no way of handing arbitrary values to visitors, so the
+        /// deserializer passes it alongside a call to `visit_unit`.
+        static HANDED_OVER: RefCell<Option<Bytes>> = const { RefCell::new(None) };
+    }
+
+    /// Serialize the bytes as a variable length array.
+    pub fn serialize<S>(value: &Bytes, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        lend(value.clone(), || serializer.serialize_bytes(value))
+    }
+
+    /// Deserialize a variable length array of bytes.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Bytes, D::Error>
+        where D: Deserializer<'de>
+    {
+        struct SharedVisitor;
+
+        impl<'de> de::Visitor<'de> for SharedVisitor {
+            type Value = Bytes;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an array of bytes")
+            }
+
+            fn visit_unit<E>(self) -> Result<Bytes, E>
+                where E: de::Error
+            {
+                HANDED_OVER.with(|handed_over| handed_over.borrow_mut().take())
+                    .ok_or_else(|| E::invalid_type(de::Unexpected::Unit, &self))
+            }
+
+            fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Bytes, D::Error>
+                where D: Deserializer<'de>
+            {
+                super::bytes::deserialize(deserializer).map(Bytes::from)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(TOKEN, SharedVisitor)
+    }
+
+    /// Make `value` available to writers while running `f`.
+    pub(crate) fn lend<F, T>(value: Bytes, f: F) -> T
+        where F: FnOnce() -> T
+    {
+        LENT.with(|lent| *lent.borrow_mut() = Some(value));
+        let result = f();
+        LENT.with(|lent| lent.borrow_mut().take());
+        result
+    }
+
+    /// Take the buffer that's being serialized, if `data` is its content.
+    pub(crate) fn take_lent(data: &[u8]) -> Option<Bytes> {
+        LENT.with(|lent| {
+            let mut lent = lent.borrow_mut();
+            let matches = lent.as_ref()
+                .is_some_and(|value| value.as_ptr() == data.as_ptr() && value.len() == data.len());
+            if matches { lent.take() } else { None }
+        })
+    }
+
+    /// Make `value` available to the visitor while running `f`.
+    pub(crate) fn hand_over<F, T>(value: Bytes, f: F) -> T
+        where F: FnOnce() -> T
+    {
+        HANDED_OVER.with(|handed_over| *handed_over.borrow_mut() = Some(value));
+        let result = f();
+        HANDED_OVER.with(|handed_over| handed_over.borrow_mut().take());
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{from_slice, to_vec};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Data {
+        stamp: Time,
+        elapsed: Duration,
+        #[serde(with = "big_array")]
+        covariance: [f64; 36],
+        #[serde(with = "bytes")]
+        data: Vec<u8>,
+    }
+
+    #[test]
+    fn serializes_like_plain_types() {
+        let mut covariance = [0.0; 36];
+        for (index, item) in covariance.iter_mut().enumerate() {
+            *item = index as f64;
+        }
+        let data = Data {
+            stamp: Time { sec: 10, nsec: 20 },
+            elapsed: Duration { sec: -1, nsec: 30 },
+            covariance,
+            data: vec![1, 2, 3],
+        };
+        let plain = ((10u32, 20u32),
+                     (-1i32, 30i32),
+                     covariance.to_vec(),
+                     vec![1u8, 2, 3]);
+        let mut expected = to_vec(&plain).unwrap();
+        // Remove the length annotation of the covariance vector
+        expected.drain(20..24);
+        expected[0] -= 4;
+        let bytes = to_vec(&data).unwrap();
+        assert_eq!(bytes, expected);
+        assert_eq!(from_slice::<Data>(&bytes).unwrap(), data);
+    }
+
+    #[test]
+    fn fails_on_short_arrays() {
+        #[derive(Debug, Serialize)]
+        struct Short {
+            items: [u8; 3],
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Long {
+            #[serde(with = "big_array")]
+            #[allow(dead_code)]
+            items: [u8; 4],
+        }
+
+        let bytes = to_vec(&Short { items: [1, 2, 3] }).unwrap();
+        assert!(from_slice::<Long>(&bytes).is_err());
+    }
+}
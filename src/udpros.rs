@@ -0,0 +1,663 @@
+//! Datagram framing of UDPROS topic connections.
+//!
+//! UDPROS sends every serialized message within one or more datagrams,
+//! each small enough to fit the MTU negotiated for the connection. Every
+//! datagram starts with an 8 byte header, holding the connection ID, an
+//! opcode, the ID of the message, and a block number. The first datagram
+//! of a message carries the total number of blocks instead.
+//!
+//! Datagrams can get lost or arrive out of order, so a
+//! [`Reassembler`](struct.Reassembler.html) collects blocks until the
+//! message is complete, and drops messages that take too long. Like
+//! roscpp, it only keeps a single message per connection, dropping it once
+//! blocks of the next message arrive.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use serde_rosmsg::{from_slice, to_vec};
+//! # use serde_rosmsg::udpros::{fragment, Reassembler};
+//! # use std::time::Duration;
+//! let data = to_vec(&vec![7u32; 20]).unwrap();
+//! let datagrams = fragment(3, 0, &data, 40).unwrap();
+//! assert_eq!(datagrams.len(), 3);
+//!
+//! let mut reassembler = Reassembler::new(Duration::from_secs(1));
+//! assert!(reassembler.push(&datagrams[2]).unwrap().is_none());
+//! assert!(reassembler.push(&datagrams[0]).unwrap().is_none());
+//! let payload = reassembler.push(&datagrams[1]).unwrap().unwrap();
+//! assert_eq!(payload.connection_id, 3);
+//! assert_eq!(from_slice::<Vec<u32>>(&payload.data).unwrap(), vec![7u32; 20]);
+//! ```
+
+use super::error::{ErrorKind, Result};
+use super::ser::to_vec;
+use super::de::from_slice;
+use byteorder::{ByteOrder, LittleEndian};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+
+/// Length of the header at the start of every datagram.
+pub const HEADER_LENGTH: usize = 8;
+
+/// Default number of connections a reassembler keeps incomplete messages
+/// for.
+pub const DEFAULT_MAX_PENDING: usize = 16;
+
+/// Default number of blocks a reassembled message can have.
+pub const DEFAULT_MAX_BLOCKS: usize = 1024;
+
+/// Default number of bytes a reassembler keeps for incomplete messages, of
+/// 16 MiB.
+///
+/// Senders choose the size of their blocks, so the number of blocks alone
+/// doesn't limit the memory used.
+pub const DEFAULT_MAX_LENGTH: usize = 16 * 1024 * 1024;
+
+/// Kind of datagram, stored in its header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Opcode {
+    /// First block of a message, with the total number of blocks.
+    Data0,
+    /// Any other block of a message, with its block number.
+    DataN,
+    /// Keepalive, carrying no data.
+    Ping,
+    /// Error reported by the publisher.
+    Err,
+}
+
+impl Opcode {
+    fn from_u8(value: u8) -> Result<Opcode> {
+        Ok(match value {
+               0 => Opcode::Data0,
+               1 => Opcode::DataN,
+               2 => Opcode::Ping,
+               3 => Opcode::Err,
+               _ => bail!(ErrorKind::BadDatagram(format!("unknown opcode {}", value))),
+           })
+    }
+
+    fn as_u8(&self) -> u8 {
+        match *self {
+            Opcode::Data0 => 0,
+            Opcode::DataN => 1,
+            Opcode::Ping => 2,
+            Opcode::Err => 3,
+        }
+    }
+}
+
+/// Header at the start of every datagram.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DatagramHeader {
+    /// ID of the connection, assigned by the publisher.
+    pub connection_id: u32,
+    /// Kind of datagram.
+    pub opcode: Opcode,
+    /// ID of the message, wrapping around after 255.
+    pub message_id: u8,
+    /// Total number of blocks for `Data0`, or the block number otherwise.
+    pub block: u16,
+}
+
+impl DatagramHeader {
+    /// Parse the header at the start of a datagram.
+    pub fn read(datagram: &[u8]) -> Result<DatagramHeader> {
+        if datagram.len() < HEADER_LENGTH {
+            bail!(ErrorKind::BadDatagram(format!("{} bytes are too short for a header",
+                                                 datagram.len())));
+        }
+        Ok(DatagramHeader {
+               connection_id: LittleEndian::read_u32(&datagram[0..4]),
+               opcode: Opcode::from_u8(datagram[4])?,
+               message_id: datagram[5],
+               block: LittleEndian::read_u16(&datagram[6..8]),
+           })
+    }
+
+    /// Encode the header into bytes.
+    pub fn to_bytes(&self) -> [u8; HEADER_LENGTH] {
+        let mut bytes = [0; HEADER_LENGTH];
+        LittleEndian::write_u32(&mut bytes[0..4], self.connection_id);
+        bytes[4] = self.opcode.as_u8();
+        bytes[5] = self.message_id;
+        LittleEndian::write_u16(&mut bytes[6..8], self.block);
+        bytes
+    }
+}
+
+/// Split serialized message data into datagrams of at most `mtu` bytes.
+///
+/// The data is usually created with [`to_vec`](../ser/fn.to_vec.html).
+/// Empty data still gets sent within a single datagram.
+pub fn fragment(connection_id: u32,
+                message_id: u8,
+                data: &[u8],
+                mtu: usize)
+                -> Result<Vec<Vec<u8>>> {
+    if mtu <= HEADER_LENGTH {
+        bail!(ErrorKind::BadDatagram(format!("MTU of {} bytes leaves no room for data", mtu)));
+    }
+    let block_size = mtu - HEADER_LENGTH;
+    let count = data.len().div_ceil(block_size).max(1);
+    if count > u16::MAX as usize {
+        bail!(ErrorKind::BadDatagram(format!("{} bytes need more than {} blocks",
+                                             data.len(),
+                                             u16::MAX)));
+    }
+    let mut datagrams = Vec::with_capacity(count);
+    for index in 0..count {
+        let header = DatagramHeader {
+            connection_id,
+            opcode: if index == 0 { Opcode::Data0 } else { Opcode::DataN },
+            message_id,
+            block: if index == 0 { count as u16 } else { index as u16 },
+        };
+        let block = &data[(index * block_size).min(data.len())..
+                     ((index + 1) * block_size).min(data.len())];
+        let mut datagram = Vec::with_capacity(HEADER_LENGTH + block.len());
+        datagram.extend_from_slice(&header.to_bytes());
+        datagram.extend_from_slice(block);
+        datagrams.push(datagram);
+    }
+    Ok(datagrams)
+}
+
+/// Complete message data, reassembled from datagrams.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Payload {
+    /// ID of the connection the message was sent over.
+    pub connection_id: u32,
+    /// ID of the message.
+    pub message_id: u8,
+    /// Serialized message, ready for [`from_slice`](../de/fn.from_slice.html).
+    pub data: Vec<u8>,
+}
+
+/// Collects blocks of messages until they are complete.
+///
+/// Blocks of a message can arrive in any order. Only one incomplete message
+/// is kept per connection, so a message gets dropped when blocks of
+/// another message arrive over the same connection, or when it is not
+/// complete within the timeout.
+///
+/// Anyone can send datagrams, so the memory used is limited. At most
+/// [`max_pending`](#method.with_max_pending) connections can have
+/// incomplete messages, messages can have at most
+/// [`max_blocks`](#method.with_max_blocks) blocks, and all incomplete
+/// messages together can hold at most
+/// [`max_length`](#method.with_max_length) bytes.
+#[derive(Debug)]
+pub struct Reassembler {
+    timeout: Duration,
+    max_pending: usize,
+    max_blocks: usize,
+    max_length: usize,
+    pending: HashMap<u32, Partial>,
+    length: usize,
+}
+
+#[derive(Debug)]
+struct Partial {
+    message_id: u8,
+    started: Instant,
+    count: Option<u16>,
+    blocks: HashMap<u16, Vec<u8>>,
+    length: usize,
+}
+
+impl Reassembler {
+    /// Create a reassembler, dropping messages not completed within
+    /// `timeout`.
+    pub fn new(timeout: Duration) -> Reassembler {
+        Reassembler {
+            timeout,
+            max_pending: DEFAULT_MAX_PENDING,
+            max_blocks: DEFAULT_MAX_BLOCKS,
+            max_length: DEFAULT_MAX_LENGTH,
+            pending: HashMap::new(),
+            length: 0,
+        }
+    }
+
+    /// Limit the number of connections with incomplete messages.
+    ///
+    /// Once reached, datagrams starting new messages are rejected, until
+    /// the pending messages complete or time out.
+    pub fn with_max_pending(mut self, max_pending: usize) -> Reassembler {
+        self.max_pending = max_pending;
+        self
+    }
+
+    /// Limit the number of blocks a message can have.
+    pub fn with_max_blocks(mut self, max_blocks: usize) -> Reassembler {
+        self.max_blocks = max_blocks;
+        self
+    }
+
+    /// Limit the number of bytes held by all incomplete messages together.
+    ///
+    /// A message that would exceed the limit gets dropped.
+    pub fn with_max_length(mut self, max_length: usize) -> Reassembler {
+        self.max_length = max_length;
+        self
+    }
+
+    /// Add a received datagram, returning the message it completes.
+    ///
+    /// Datagrams with an `Err` opcode fail with
+    /// [`RemoteError`](../error/enum.ErrorKind.html#variant.RemoteError).
+    pub fn push(&mut self, datagram: &[u8]) -> Result<Option<Payload>> {
+        self.push_at(datagram, Instant::now())
+    }
+
+    fn push_at(&mut self, datagram: &[u8], now: Instant) -> Result<Option<Payload>> {
+        self.expire_at(now);
+        let header = DatagramHeader::read(datagram)?;
+        let (count, index) = match header.opcode {
+            Opcode::Data0 if header.block == 0 => {
+                bail!(ErrorKind::BadDatagram("message has no blocks".into()))
+            }
+            Opcode::Data0 => (Some(header.block), 0),
+            Opcode::DataN if header.block == 0 => {
+                bail!(ErrorKind::BadDatagram("block 0 needs the Data0 opcode".into()))
+            }
+            Opcode::DataN => (None, header.block),
+            Opcode::Ping => return Ok(None),
+            Opcode::Err => {
+                bail!(ErrorKind::RemoteError(format!("UDPROS connection {} failed",
+                                                     header.connection_id)))
+            }
+        };
+        let limit = count.unwrap_or(index) as usize;
+        if limit > self.max_blocks || (count.is_none() && limit == self.max_blocks) {
+            bail!(ErrorKind::BadDatagram(format!("message {} has more than {} blocks",
+                                                 header.message_id,
+                                                 self.max_blocks)));
+        }
+        let key = header.connection_id;
+        let superseded = self.pending
+            .get(&key)
+            .is_some_and(|partial| partial.message_id != header.message_id);
+        if superseded {
+            self.remove(key);
+        }
+        if count == Some(1) {
+            self.remove(key);
+            return Ok(Some(Payload {
+                               connection_id: header.connection_id,
+                               message_id: header.message_id,
+                               data: datagram[HEADER_LENGTH..].to_vec(),
+                           }));
+        }
+        if !self.pending.contains_key(&key) && self.pending.len() >= self.max_pending {
+            bail!(ErrorKind::BadDatagram(format!("{} connections already have incomplete \
+                                                  messages",
+                                                 self.pending.len())));
+        }
+        let block = &datagram[HEADER_LENGTH..];
+        let complete = {
+            let partial = self.pending
+                .entry(key)
+                .or_insert_with(|| {
+                                    Partial {
+                                        message_id: header.message_id,
+                                        started: now,
+                                        count: None,
+                                        blocks: HashMap::new(),
+                                        length: 0,
+                                    }
+                                });
+            if partial.count.is_some() && count.is_some() && partial.count != count {
+                self.remove(key);
+                bail!(ErrorKind::BadDatagram(format!("message {} changed its number of blocks",
+                                                     header.message_id)));
+            }
+            if count.is_some() {
+                partial.count = count;
+            }
+            let replaced = partial.blocks.get(&index).map_or(0, |block| block.len());
+            if self.length - replaced + block.len() > self.max_length {
+                self.remove(key);
+                bail!(ErrorKind::BadDatagram(format!("incomplete messages would take more \
+                                                      than {} bytes",
+                                                     self.max_length)));
+            }
+            partial.blocks.insert(index, block.to_vec());
+            partial.length = partial.length - replaced + block.len();
+            self.length = self.length - replaced + block.len();
+            match partial.count {
+                Some(count) => {
+                    if partial.blocks.keys().any(|&index| index >= count) {
+                        self.remove(key);
+                        bail!(ErrorKind::BadDatagram(format!("message {} has more than {} blocks",
+                                                             header.message_id,
+                                                             count)));
+                    }
+                    partial.blocks.len() == count as usize
+                }
+                None => false,
+            }
+        };
+        if !complete {
+            return Ok(None);
+        }
+        let mut partial = self.remove(key).expect("Completed message is pending");
+        let mut data = Vec::new();
+        for index in 0..partial.blocks.len() as u16 {
+            data.extend(partial.blocks.remove(&index).expect("Completed message has all blocks"));
+        }
+        Ok(Some(Payload {
+                    connection_id: header.connection_id,
+                    message_id: header.message_id,
+                    data,
+                }))
+    }
+
+    /// Drop all messages that timed out, returning how many were dropped.
+    pub fn expire(&mut self) -> usize {
+        self.expire_at(Instant::now())
+    }
+
+    fn expire_at(&mut self, now: Instant) -> usize {
+        let timeout = self.timeout;
+        let before = self.pending.len();
+        let mut length = self.length;
+        self.pending
+            .retain(|_, partial| {
+                let keep = now.duration_since(partial.started) < timeout;
+                if !keep {
+                    length -= partial.length;
+                }
+                keep
+            });
+        self.length = length;
+        before - self.pending.len()
+    }
+
+    fn remove(&mut self, key: u32) -> Option<Partial> {
+        let partial = self.pending.remove(&key)?;
+        self.length -= partial.length;
+        Some(partial)
+    }
+
+    /// Number of connections with messages still waiting for blocks.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Number of bytes held by messages still waiting for blocks.
+    pub fn pending_length(&self) -> usize {
+        self.length
+    }
+}
+
+/// Sends messages over a connected `UdpSocket`.
+#[derive(Debug)]
+pub struct Sender<'a> {
+    socket: &'a UdpSocket,
+    connection_id: u32,
+    message_id: u8,
+    mtu: usize,
+}
+
+impl<'a> Sender<'a> {
+    /// Create a sender for the given connection, which sends datagrams of
+    /// at most `mtu` bytes.
+    pub fn new(socket: &'a UdpSocket, connection_id: u32, mtu: usize) -> Sender<'a> {
+        Sender {
+            socket,
+            connection_id,
+            message_id: 0,
+            mtu,
+        }
+    }
+
+    /// Serialize and send a message.
+    pub fn send<T>(&mut self, message: &T) -> Result<()>
+        where T: Serialize
+    {
+        let data = to_vec(message)?;
+        for datagram in fragment(self.connection_id, self.message_id, &data, self.mtu)? {
+            self.socket.send(&datagram)?;
+        }
+        self.message_id = self.message_id.wrapping_add(1);
+        Ok(())
+    }
+}
+
+/// Receives messages from a `UdpSocket`.
+#[derive(Debug)]
+pub struct Receiver<'a> {
+    socket: &'a UdpSocket,
+    reassembler: Reassembler,
+    buffer: Vec<u8>,
+}
+
+impl<'a> Receiver<'a> {
+    /// Create a receiver, dropping messages not completed within `timeout`.
+    ///
+    /// Waiting for datagrams is not limited by the timeout, so set a read
+    /// timeout on the socket to avoid blocking forever.
+    pub fn new(socket: &'a UdpSocket, timeout: Duration) -> Receiver<'a> {
+        Receiver {
+            socket,
+            reassembler: Reassembler::new(timeout),
+            buffer: vec![0; 65536],
+        }
+    }
+
+    /// Receive datagrams until a message is complete, and deserialize it.
+    ///
+    /// Anyone can send datagrams, so the ones the reassembler rejects as
+    /// bad get skipped.
+    pub fn recv<T>(&mut self) -> Result<(u32, T)>
+        where T: DeserializeOwned
+    {
+        loop {
+            let length = self.socket.recv(&mut self.buffer)?;
+            let payload = match self.reassembler.push(&self.buffer[..length]) {
+                Ok(payload) => payload,
+                Err(error) => {
+                    match *error.kind() {
+                        ErrorKind::BadDatagram(..) => None,
+                        _ => return Err(error),
+                    }
+                }
+            };
+            if let Some(payload) = payload {
+                return Ok((payload.connection_id, from_slice(&payload.data)?));
+            }
+        }
+    }
+
+    /// Reassembler holding incomplete messages.
+    pub fn reassembler(&self) -> &Reassembler {
+        &self.reassembler
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(message_id: u8, data: &[u8]) -> Option<Payload> {
+        Some(Payload {
+                 connection_id: 9,
+                 message_id,
+                 data: data.to_vec(),
+             })
+    }
+
+    #[test]
+    fn encodes_headers() {
+        let header = DatagramHeader {
+            connection_id: 0x01020304,
+            opcode: Opcode::DataN,
+            message_id: 7,
+            block: 0x0203,
+        };
+        assert_eq!(header.to_bytes(), [4, 3, 2, 1, 1, 7, 3, 2]);
+        assert_eq!(DatagramHeader::read(&header.to_bytes()).unwrap(), header);
+        assert!(DatagramHeader::read(&[4, 3, 2, 1, 1, 7, 3]).is_err());
+        assert!(DatagramHeader::read(&[4, 3, 2, 1, 9, 7, 3, 2]).is_err());
+    }
+
+    #[test]
+    fn fragments_data() {
+        let data = (0..25).collect::<Vec<u8>>();
+        let datagrams = fragment(9, 4, &data, 18).unwrap();
+        assert_eq!(datagrams.len(), 3);
+        assert_eq!(datagrams[0][..8], [9, 0, 0, 0, 0, 4, 3, 0]);
+        assert_eq!(datagrams[0][8..], data[..10]);
+        assert_eq!(datagrams[1][..8], [9, 0, 0, 0, 1, 4, 1, 0]);
+        assert_eq!(datagrams[2][..8], [9, 0, 0, 0, 1, 4, 2, 0]);
+        assert_eq!(datagrams[2][8..], data[20..]);
+        assert_eq!(fragment(9, 4, &[], 18).unwrap(), vec![vec![9, 0, 0, 0, 0, 4, 1, 0]]);
+        assert!(fragment(9, 4, &data, 8).is_err());
+        assert!(fragment(9, 4, &vec![0; 70000], 9).is_err());
+    }
+
+    #[test]
+    fn reassembles_blocks_in_any_order() {
+        let data = (0..25).collect::<Vec<u8>>();
+        let mut reassembler = Reassembler::new(Duration::from_secs(1));
+        let first = fragment(9, 1, &data, 18).unwrap();
+        let other = fragment(10, 2, &data[..5], 18).unwrap();
+        assert_eq!(reassembler.push(&first[1]).unwrap(), None);
+        assert_eq!(reassembler.push(&other[0]).unwrap().unwrap().data, &data[..5]);
+        assert_eq!(reassembler.push(&first[2]).unwrap(), None);
+        assert_eq!(reassembler.push(&first[2]).unwrap(), None);
+        assert_eq!(reassembler.pending(), 1);
+        assert_eq!(reassembler.push(&first[0]).unwrap(), payload(1, &data));
+        assert_eq!(reassembler.pending(), 0);
+    }
+
+    #[test]
+    fn drops_superseded_messages() {
+        let old = (0..25).collect::<Vec<u8>>();
+        let new = (100..125).collect::<Vec<u8>>();
+        let mut reassembler = Reassembler::new(Duration::from_secs(1));
+        let lost = fragment(9, 1, &old, 18).unwrap();
+        assert_eq!(reassembler.push(&lost[0]).unwrap(), None);
+        assert_eq!(reassembler.push(&lost[2]).unwrap(), None);
+        for message_id in 2..256 + 1 {
+            let datagrams = fragment(9, message_id as u8, &old[..5], 18).unwrap();
+            assert!(reassembler.push(&datagrams[0]).unwrap().is_some());
+        }
+        let reused = fragment(9, 1, &new, 18).unwrap();
+        assert_eq!(reassembler.push(&reused[0]).unwrap(), None);
+        assert_eq!(reassembler.push(&reused[1]).unwrap(), None);
+        assert_eq!(reassembler.push(&reused[2]).unwrap(), payload(1, &new));
+
+        assert_eq!(reassembler.push(&lost[0]).unwrap(), None);
+        assert_eq!(reassembler.push(&lost[2]).unwrap(), None);
+        let next = fragment(9, 2, &new, 18).unwrap();
+        assert_eq!(reassembler.push(&next[1]).unwrap(), None);
+        assert_eq!(reassembler.push(&next[0]).unwrap(), None);
+        assert_eq!(reassembler.push(&next[2]).unwrap(), payload(2, &new));
+        assert_eq!(reassembler.push(&lost[1]).unwrap(), None);
+        assert_eq!(reassembler.pending(), 1);
+    }
+
+    #[test]
+    fn limits_pending_messages() {
+        let data = (0..25).collect::<Vec<u8>>();
+        let mut reassembler = Reassembler::new(Duration::from_secs(1))
+            .with_max_pending(2)
+            .with_max_blocks(3);
+        for connection_id in 0..2 {
+            let datagrams = fragment(connection_id, 0, &data, 18).unwrap();
+            assert_eq!(reassembler.push(&datagrams[1]).unwrap(), None);
+        }
+        let datagrams = fragment(2, 0, &data, 18).unwrap();
+        assert!(reassembler.push(&datagrams[1]).is_err());
+        assert!(reassembler.push(&datagrams[0]).is_err());
+        assert_eq!(reassembler.push(&fragment(2, 1, &[], 18).unwrap()[0]).unwrap(),
+                   Some(Payload {
+                            connection_id: 2,
+                            message_id: 1,
+                            data: Vec::new(),
+                        }));
+        assert_eq!(reassembler.pending(), 2);
+
+        assert!(reassembler.push(&[0, 0, 0, 0, 1, 0, 3, 0]).is_err());
+        assert!(reassembler.push(&[0, 0, 0, 0, 0, 0, 4, 0]).is_err());
+        let datagrams = fragment(0, 0, &data, 18).unwrap();
+        assert_eq!(reassembler.push(&datagrams[2]).unwrap(), None);
+        let payload = reassembler.push(&datagrams[0]).unwrap().unwrap();
+        assert_eq!((payload.connection_id, payload.data), (0, data));
+    }
+
+    #[test]
+    fn limits_pending_length() {
+        let data = (0..50).collect::<Vec<u8>>();
+        let mut reassembler = Reassembler::new(Duration::from_secs(1)).with_max_length(40);
+        let first = fragment(9, 0, &data[..30], 18).unwrap();
+        assert_eq!(reassembler.push(&first[0]).unwrap(), None);
+        assert_eq!(reassembler.push(&first[0]).unwrap(), None);
+        assert_eq!(reassembler.pending_length(), 10);
+        let second = fragment(10, 0, &data, 18).unwrap();
+        for datagram in &second[..3] {
+            assert_eq!(reassembler.push(datagram).unwrap(), None);
+        }
+        assert!(reassembler.push(&second[3]).is_err());
+        assert_eq!((reassembler.pending(), reassembler.pending_length()), (1, 10));
+        assert_eq!(reassembler.push(&first[2]).unwrap(), None);
+        assert_eq!(reassembler.push(&first[1]).unwrap(), payload(0, &data[..30]));
+        assert_eq!(reassembler.pending_length(), 0);
+    }
+
+    #[test]
+    fn drops_messages_with_lost_blocks() {
+        let data = (0..25).collect::<Vec<u8>>();
+        let mut reassembler = Reassembler::new(Duration::from_millis(100));
+        let start = Instant::now();
+        let first = fragment(9, 1, &data, 18).unwrap();
+        let second = fragment(10, 2, &data, 18).unwrap();
+        assert_eq!(reassembler.push_at(&first[0], start).unwrap(), None);
+        assert_eq!(reassembler.push_at(&first[2], start).unwrap(), None);
+        let later = start + Duration::from_millis(150);
+        assert_eq!(reassembler.push_at(&second[0], later).unwrap(), None);
+        assert_eq!(reassembler.pending(), 1);
+        assert_eq!(reassembler.push_at(&first[1], later).unwrap(), None);
+        assert_eq!(reassembler.expire_at(later + Duration::from_millis(150)), 2);
+        assert_eq!(reassembler.pending(), 0);
+    }
+
+    #[test]
+    fn rejects_bad_datagrams() {
+        let mut reassembler = Reassembler::new(Duration::from_secs(1));
+        assert!(reassembler.push(&[9, 0, 0, 0, 0, 1, 0, 0]).is_err());
+        assert!(reassembler.push(&[9, 0, 0, 0, 1, 1, 0, 0]).is_err());
+        assert_eq!(reassembler.push(&[9, 0, 0, 0, 1, 1, 5, 0, 1]).unwrap(), None);
+        assert!(reassembler.push(&[9, 0, 0, 0, 0, 1, 2, 0, 1]).is_err());
+        assert_eq!(reassembler.pending(), 0);
+        assert_eq!(reassembler.push(&[9, 0, 0, 0, 2, 0, 0, 0]).unwrap(), None);
+        match *reassembler.push(&[9, 0, 0, 0, 3, 0, 0, 0]).unwrap_err().kind() {
+            ErrorKind::RemoteError(..) => {}
+            ref kind => panic!("Unexpected error: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn sends_messages_over_loopback() {
+        let sender_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let receiver_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        sender_socket.connect(receiver_socket.local_addr().unwrap()).unwrap();
+        receiver_socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut sender = Sender::new(&sender_socket, 42, 64);
+        let mut receiver = Receiver::new(&receiver_socket, Duration::from_secs(1));
+        let message = (0..100).map(|v| format!("item {}", v)).collect::<Vec<_>>();
+        sender_socket.send(&[1, 2, 3]).unwrap();
+        sender.send(&message).unwrap();
+        sender.send(&vec![1u16, 2, 3]).unwrap();
+        assert_eq!(receiver.recv::<Vec<String>>().unwrap(), (42, message));
+        assert_eq!(receiver.recv::<Vec<u16>>().unwrap(), (42, vec![1, 2, 3]));
+        assert_eq!(receiver.reassembler().pending(), 0);
+    }
+}
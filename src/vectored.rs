@@ -0,0 +1,313 @@
+//! Serialize messages into chunks, for vectored writes.
+//!
+//! Requires the `bytes` feature.
+//!
+//! Serializing a message with [`to_vec`](../ser/fn.to_vec.html) copies all
+//! of its data into one buffer, which gets expensive for messages carrying
+//! megabytes of data, like images or point clouds. Serializing into
+//! [`Chunks`](struct.Chunks.html) instead coalesces all small fields into a
+//! shared buffer, and references big `Bytes` fields without copying them.
+//! All the chunks can then be sent with a single vectored write.
+//!
+//! Serde only lends byte slices to serializers for the duration of a call,
+//! so only `Bytes` fields serialized with
+//! [`types::shared_bytes`](../types/shared_bytes/index.html) can be
+//! referenced. All other data gets copied into the shared buffer.
+//!
+//! # Examples
+//!
+//! ```rust
+//! extern crate bytes;
+//! extern crate serde_rosmsg;
+//! #[macro_use]
+//! extern crate serde_derive;
+//! use bytes::Bytes;
+//! use serde_rosmsg::to_vec;
+//! use serde_rosmsg::vectored::to_chunks;
+//!
+//! fn main() {
+//! #[derive(Serialize)]
+//! struct Image {
+//!     height: u32,
+//!     width: u32,
+//!     encoding: String,
+//!     #[serde(with = "serde_rosmsg::types::shared_bytes")]
+//!     data: Bytes,
+//! }
+//!
+//! let image = Image {
+//!     height: 480,
+//!     width: 640,
+//!     encoding: "mono8".into(),
+//!     data: Bytes::from(vec![7; 640 * 480]),
+//! };
+//! let chunks = to_chunks(&image).unwrap();
+//! assert_eq!(chunks.chunks().len(), 2);
+//! assert_eq!(chunks.chunks()[1].as_ptr(), image.data.as_ptr());
+//!
+//! let mut output = Vec::new();
+//! chunks.write_to(&mut output).unwrap();
+//! assert_eq!(output, to_vec(&image).unwrap());
+//! }
+//! ```
+
+use super::error::{ErrorKind, Result};
+use super::ser::Serializer;
+use super::types::shared_bytes;
+use byteorder::{ByteOrder, LittleEndian};
+use bytes::{Bytes, BytesMut};
+use serde::Serialize;
+use std::io::{self, IoSlice, Write};
+use std::ops::Range;
+
+/// Default size from which `Bytes` fields are referenced, rather than
+/// copied.
+///
+/// Smaller fields are cheaper to copy than to send as separate chunks.
+pub const DEFAULT_THRESHOLD: usize = 4096;
+
+/// Serialize a value into chunks, including the length prefix.
+pub fn to_chunks<T>(value: &T) -> Result<Chunks>
+    where T: Serialize + ?Sized
+{
+    to_chunks_with_threshold(value, DEFAULT_THRESHOLD)
+}
+
+/// Serialize a value into chunks, referencing `Bytes` fields of at least
+/// `threshold` bytes.
+pub fn to_chunks_with_threshold<T>(value: &T, threshold: usize) -> Result<Chunks>
+    where T: Serialize + ?Sized
+{
+    let mut writer = ChunkWriter {
+        buffer: BytesMut::with_capacity(128),
+        pieces: Vec::new(),
+        start: 0,
+        length: 0,
+        threshold,
+    };
+    writer.buffer.extend_from_slice(&[0; 4]);
+    value.serialize(&mut Serializer::new(&mut writer))?;
+    writer.finish()
+}
+
+/// Serialized message, split into chunks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chunks {
+    chunks: Vec<Bytes>,
+    length: usize,
+}
+
+impl Chunks {
+    /// All chunks, in order.
+    pub fn chunks(&self) -> &[Bytes] {
+        &self.chunks
+    }
+
+    /// Consume the message, returning all chunks.
+    pub fn into_chunks(self) -> Vec<Bytes> {
+        self.chunks
+    }
+
+    /// Total length of all chunks, including the length prefix.
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Check if there are no chunks.
+    ///
+    /// This is never the case, since the length prefix is always present.
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Slices of all chunks, for passing to `write_vectored`.
+    pub fn io_slices(&self) -> Vec<IoSlice<'_>> {
+        self.chunks.iter().map(|chunk| IoSlice::new(chunk)).collect()
+    }
+
+    /// Write all chunks, using vectored writes.
+    pub fn write_to<W>(&self, writer: &mut W) -> io::Result<()>
+        where W: Write + ?Sized
+    {
+        let mut slices = self.io_slices();
+        let mut slices = &mut slices[..];
+        while !slices.is_empty() {
+            match writer.write_vectored(slices) {
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::WriteZero)),
+                Ok(count) => IoSlice::advance_slices(&mut slices, count),
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy all chunks into a single buffer.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.length);
+        for chunk in &self.chunks {
+            data.extend_from_slice(chunk);
+        }
+        data
+    }
+}
+
+/// Part of the output, before the shared buffer gets frozen.
+enum Piece {
+    Buffered(Range<usize>),
+    Shared(Bytes),
+}
+
+/// Writer coalescing all data into one buffer, except for lent `Bytes`.
+struct ChunkWriter {
+    buffer: BytesMut,
+    pieces: Vec<Piece>,
+    start: usize,
+    length: usize,
+    threshold: usize,
+}
+
+impl ChunkWriter {
+    fn end_piece(&mut self) {
+        if self.buffer.len() > self.start {
+            self.pieces.push(Piece::Buffered(self.start..self.buffer.len()));
+            self.start = self.buffer.len();
+        }
+    }
+
+    fn finish(mut self) -> Result<Chunks> {
+        self.end_piece();
+        let length = self.length + self.buffer.len();
+        if length - 4 > u32::MAX as usize {
+            bail!(ErrorKind::FrameTooLarge(length - 4, u32::MAX as usize));
+        }
+        LittleEndian::write_u32(&mut self.buffer[..4], (length - 4) as u32);
+        let buffer = self.buffer.freeze();
+        let chunks = self.pieces
+            .into_iter()
+            .map(|piece| match piece {
+                     Piece::Buffered(range) => buffer.slice(range),
+                     Piece::Shared(bytes) => bytes,
+                 })
+            .collect();
+        Ok(Chunks { chunks, length })
+    }
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if data.len() >= self.threshold {
+            if let Some(bytes) = shared_bytes::take_lent(data) {
+                self.end_piece();
+                self.length += bytes.len();
+                self.pieces.push(Piece::Shared(bytes));
+                return Ok(data.len());
+            }
+        }
+        self.buffer.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{from_slice, to_vec};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Frames {
+        id: u32,
+        #[serde(with = "::types::shared_bytes")]
+        first: Bytes,
+        name: String,
+        #[serde(with = "::types::shared_bytes")]
+        second: Bytes,
+        #[serde(with = "::types::bytes")]
+        third: Vec<u8>,
+    }
+
+    fn frames() -> Frames {
+        Frames {
+            id: 3,
+            first: Bytes::from(vec![1; 100]),
+            name: "frames".into(),
+            second: Bytes::from(vec![2; 10]),
+            third: vec![3; 100],
+        }
+    }
+
+    #[test]
+    fn references_big_bytes() {
+        let value = frames();
+        let chunks = to_chunks_with_threshold(&value, 50).unwrap();
+        assert_eq!(chunks.chunks().len(), 3);
+        assert_eq!(chunks.chunks()[0].len(), 4 + 4 + 4);
+        assert_eq!(chunks.chunks()[1], value.first);
+        assert_eq!(chunks.chunks()[1].as_ptr(), value.first.as_ptr());
+        assert_eq!(chunks.len(), to_vec(&value).unwrap().len());
+        assert_eq!(chunks.to_vec(), to_vec(&value).unwrap());
+        assert_eq!(from_slice::<Frames>(&chunks.to_vec()).unwrap(), value);
+    }
+
+    #[test]
+    fn copies_small_bytes() {
+        let value = frames();
+        let chunks = to_chunks(&value).unwrap();
+        assert_eq!(chunks.chunks().len(), 1);
+        assert_eq!(chunks.to_vec(), to_vec(&value).unwrap());
+        let chunks = to_chunks_with_threshold(&value, 0).unwrap();
+        assert_eq!(chunks.chunks().len(), 5);
+        assert_eq!(chunks.to_vec(), to_vec(&value).unwrap());
+    }
+
+    #[test]
+    fn copies_other_byte_arrays() {
+        #[derive(Serialize)]
+        struct Plain {
+            #[serde(with = "::types::bytes")]
+            data: Vec<u8>,
+        }
+
+        let value = Plain { data: vec![1; 100] };
+        let chunks = to_chunks_with_threshold(&value, 1).unwrap();
+        assert_eq!(chunks.chunks().len(), 1);
+        assert_eq!(chunks.to_vec(), to_vec(&value).unwrap());
+    }
+
+    #[test]
+    fn writes_with_partial_vectored_writes() {
+        struct Limited(Vec<u8>, usize);
+
+        impl Write for Limited {
+            fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+                self.write_vectored(&[IoSlice::new(data)])
+            }
+
+            fn write_vectored(&mut self, data: &[IoSlice]) -> io::Result<usize> {
+                self.1 += 1;
+                let mut count = 0;
+                for slice in data {
+                    let taken = slice.len().min(7 - count);
+                    self.0.extend_from_slice(&slice[..taken]);
+                    count += taken;
+                }
+                Ok(count)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let value = frames();
+        let chunks = to_chunks_with_threshold(&value, 50).unwrap();
+        let mut writer = Limited(Vec::new(), 0);
+        chunks.write_to(&mut writer).unwrap();
+        assert_eq!(writer.0, to_vec(&value).unwrap());
+        assert_eq!(writer.1, chunks.len().div_ceil(7));
+    }
+}